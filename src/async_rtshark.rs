@@ -0,0 +1,478 @@
+//! Asynchronous TShark reader, based on tokio. Available with the "tokio" feature.
+
+use crate::input::InputError;
+use crate::stderr::{is_truncated, StderrLines};
+use crate::Diagnostic;
+use crate::{parse_xml, Packet, RTSharkError, Result};
+use futures_core::Stream;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncBufRead, BufReader};
+use tokio::process::{Child, ChildStderr, ChildStdout};
+
+/// AsyncRTShark structure represents a TShark process, read asynchronously.
+/// It is created by [crate::RTSharkBuilderReady::spawn_async].
+///
+/// Packets are read with [AsyncRTShark::read] or as a [Stream]. TShark is killed when
+/// this object is dropped, so that cancelled tasks do not leave TShark running.
+pub struct AsyncRTShark {
+    /// Contains the TShark process handle, when TShark is running
+    process: Option<Child>,
+    /// TShark piped output
+    stdout: BufReader<ChildStdout>,
+    /// stderr, drained each time the stream is polled
+    stderr: BufReader<ChildStderr>,
+    /// lines read from stderr
+    stderr_lines: StderrLines,
+    /// optional metadata blacklist, to prevent storing useless metadata in output packet structure
+    filters: Vec<String>,
+    /// PDML lines of the packet being read
+    chunk: Vec<u8>,
+    /// start of the last line in chunk
+    line_start: usize,
+    /// stderr line being read
+    stderr_line: Vec<u8>,
+    /// stdout is closed
+    eof: bool,
+    /// stderr is closed
+    stderr_closed: bool,
+    /// no more packets can be read
+    done: bool,
+    /// error of the input_reader copied to TShark standard input
+    pub(crate) input_error: Option<InputError>,
+}
+
+impl AsyncRTShark {
+    /// create a new AsyncRTShark instance from a successful builder call.
+    pub(crate) fn new(mut process: Child, filters: Vec<String>) -> Self {
+        let stdout = BufReader::new(process.stdout.take().unwrap());
+        let stderr = BufReader::new(process.stderr.take().unwrap());
+
+        AsyncRTShark {
+            process: Some(process),
+            stdout,
+            stderr,
+            stderr_lines: StderrLines::default(),
+            filters,
+            chunk: vec![],
+            line_start: 0,
+            stderr_line: vec![],
+            eof: false,
+            stderr_closed: false,
+            done: false,
+            input_error: None,
+        }
+    }
+
+    /// Read a packet from tshark output and map it to the [Packet] type.
+    /// Reading packet can be done until 'None' is returned, like with [crate::RTShark::read].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # async fn run() {
+    /// let builder = rtshark::RTSharkBuilder::builder()
+    ///     .input_path("/tmp/my.pcap");
+    ///
+    /// let mut rtshark = match builder.spawn_async() {
+    ///     Err(err) => { eprintln!("Error running tshark: {err}"); return; }
+    ///     Ok(rtshark) => rtshark,
+    /// };
+    ///
+    /// while let Some(packet) = rtshark.read().await.transpose() {
+    ///     match packet {
+    ///         Ok(packet) => println!("Got a packet with {} layers", packet.iter().count()),
+    ///         Err(e) => eprintln!("Got decoding error: {e}"),
+    ///     }
+    /// }
+    /// # }
+    /// ```
+    pub async fn read(&mut self) -> Result<Option<Packet>> {
+        std::future::poll_fn(|cx| Pin::new(&mut *self).poll_next(cx))
+            .await
+            .transpose()
+    }
+
+    /// Kill the running TShark process associated to this instance and wait for its termination.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # async fn run() {
+    /// # let builder = rtshark::RTSharkBuilder::builder().input_path("/tmp/my.pcap");
+    /// # let Ok(mut rtshark) = builder.spawn_async() else { return };
+    /// rtshark.kill().await;
+    /// assert!(rtshark.pid().is_none());
+    /// # }
+    /// ```
+    pub async fn kill(&mut self) {
+        if let Some(mut process) = self.process.take() {
+            // TShark and its dumpcap child are killed together
+            #[cfg(target_family = "unix")]
+            if let Some(pid) = process.id() {
+                if let Err(e) = crate::process::signal_group(pid, libc::SIGKILL) {
+                    eprintln!("Error while killing rtshark: kill: {e}");
+                }
+            }
+            if let Err(e) = process.kill().await {
+                eprintln!("Error while killing rtshark: kill: {e}");
+            }
+        }
+        self.done = true;
+    }
+
+    /// Returns tshark process id if tshark is running.
+    pub fn pid(&self) -> Option<u32> {
+        self.process.as_ref().and_then(|p| p.id())
+    }
+
+    /// Get the non-fatal messages printed by TShark on stderr so far, like [crate::RTShark::warnings].
+    pub fn warnings(&self) -> Vec<String> {
+        self.stderr_lines.warnings()
+    }
+
+    /// Get the messages printed by TShark on stderr so far, like [crate::RTShark::diagnostics].
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.stderr_lines.diagnostics()
+    }
+
+    /// Get all lines printed by TShark on stderr so far, like [crate::RTShark::stderr_lines].
+    pub fn stderr_lines(&self) -> Vec<String> {
+        self.stderr_lines.lines()
+    }
+
+    /// Read stdout until the end of a packet. Returns None at EOF.
+    fn poll_packet(&mut self, cx: &mut Context<'_>) -> Poll<Result<Option<Packet>>> {
+        loop {
+            let available = ready!(Pin::new(&mut self.stdout).poll_fill_buf(cx))?;
+            if available.is_empty() {
+                return Poll::Ready(Ok(None));
+            }
+
+            // Lines are kept until the end of the packet, which is on its own line
+            let (consumed, line_end) = match available.iter().position(|&b| b == b'\n') {
+                Some(pos) => (pos + 1, true),
+                None => (available.len(), false),
+            };
+            self.chunk.extend_from_slice(&available[..consumed]);
+            Pin::new(&mut self.stdout).consume(consumed);
+
+            if !line_end {
+                continue;
+            }
+
+            let line = &self.chunk[self.line_start..];
+            if line.trim_ascii() == b"</packet>" {
+                let mut reader = quick_xml::Reader::from_reader(self.chunk.as_slice());
+                let packet = parse_xml(&mut reader, &self.filters);
+                self.chunk.clear();
+                self.line_start = 0;
+                return Poll::Ready(packet);
+            }
+            self.line_start = self.chunk.len();
+        }
+    }
+
+    /// Read all stderr lines available, so that TShark is never blocked by a full pipe.
+    /// Ready once stderr is closed.
+    fn poll_stderr(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        while !self.stderr_closed {
+            let available = ready!(Pin::new(&mut self.stderr).poll_fill_buf(cx))?;
+            let (consumed, line_end) = match available.iter().position(|&b| b == b'\n') {
+                Some(pos) => (pos + 1, true),
+                None => (available.len(), available.is_empty()),
+            };
+            self.stderr_closed = available.is_empty();
+            self.stderr_line.extend_from_slice(&available[..consumed]);
+            Pin::new(&mut self.stderr).consume(consumed);
+
+            if line_end && !self.stderr_line.is_empty() {
+                self.stderr_lines.push(&self.stderr_line);
+                self.stderr_line.clear();
+            }
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Drop for AsyncRTShark {
+    fn drop(&mut self) {
+        // TShark is killed on drop by tokio, but not its dumpcap child
+        #[cfg(target_family = "unix")]
+        if let Some(pid) = self.pid() {
+            let _ = crate::process::signal_group(pid, libc::SIGKILL);
+        }
+    }
+}
+
+impl Stream for AsyncRTShark {
+    type Item = Result<Packet>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+
+        if this.done {
+            return Poll::Ready(None);
+        }
+
+        // stderr is drained while packets are read, waking this task when more lines come
+        if let Poll::Ready(Err(e)) = this.poll_stderr(cx) {
+            this.stderr_closed = true;
+            return Poll::Ready(Some(Err(e)));
+        }
+
+        if !this.eof {
+            match ready!(this.poll_packet(cx)) {
+                Ok(Some(packet)) => return Poll::Ready(Some(Ok(packet))),
+                Ok(None) => this.eof = true,
+                Err(e) => return Poll::Ready(Some(Err(e))),
+            }
+        }
+
+        // if process stops, there may be due to an error, we can get it in stderr
+        ready!(this.poll_stderr(cx))?;
+        // TShark has closed its outputs, its exit status tells if an error was fatal
+        let status = match &mut this.process {
+            Some(process) => {
+                // waiting for a tokio child is cancel safe: a new wait can be polled each time
+                let wait = std::pin::pin!(process.wait());
+                ready!(wait.poll(cx)).ok()
+            }
+            None => None,
+        };
+        this.process = None;
+        this.done = true;
+
+        // TShark read a short capture, if the input reader failed
+        if let Some(Err(e)) = this.input_error.as_ref().map(InputError::check) {
+            return Poll::Ready(Some(Err(e)));
+        }
+
+        if status.is_some_and(|s| s.success()) {
+            return Poll::Ready(None);
+        }
+
+        let lines = this.stderr_lines.lines();
+        match this.stderr_lines.take_error() {
+            Some(line) => Poll::Ready(Some(Err(RTSharkError::from_stderr(status, &line, &lines)))),
+            None if is_truncated(&lines) => Poll::Ready(None),
+            // crashed, or failed with warnings only
+            None => Poll::Ready(Some(Err(RTSharkError::TsharkExited {
+                status,
+                stderr: lines.join("\n"),
+            }))),
+        }
+    }
+}
+
+// fake TShark processes are shell commands
+#[cfg(all(test, target_family = "unix"))]
+mod tests {
+    use super::*;
+
+    /// Spawn a fake TShark printing the given PDML and error message, failing if there is an error message.
+    fn spawn_fake_tshark(pdml: &str, stderr: &str) -> AsyncRTShark {
+        let process = tokio::process::Command::new("sh")
+            .args([
+                "-c",
+                "printf '%s' \"$0\"; printf '%s' \"$1\" >&2; [ -z \"$1\" ]",
+                pdml,
+                stderr,
+            ])
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .unwrap();
+
+        AsyncRTShark::new(process, vec!["udp.length".to_owned()])
+    }
+
+    const PDML: &str = r#"<?xml version="1.0"?>
+<pdml version="0" creator="wireshark/4.0.6">
+<packet>
+  <proto name="geninfo" pos="0" showname="General information" size="28">
+    <field name="timestamp" pos="0" show="Jan  1, 1970 00:00:01.000000000 UTC" showname="Captured Time" value="1.000000000" size="28"/>
+  </proto>
+  <proto name="udp" showname="User Datagram Protocol" size="8" pos="20">
+    <field name="udp.srcport" showname="Source Port: 53" size="2" pos="20" show="53" value="0035"/>
+    <field name="udp.length" showname="Length: 8" size="2" pos="24" show="8" value="0008"/>
+  </proto>
+</packet>
+
+<packet>
+  <proto name="udp" showname="User Datagram Protocol" size="8" pos="20">
+    <field name="udp.srcport" showname="Source Port: 54" size="2" pos="20" show="54" value="0036"/>
+  </proto>
+</packet>
+
+</pdml>
+"#;
+
+    #[tokio::test]
+    async fn test_async_read() {
+        let mut rtshark = spawn_fake_tshark(PDML, "");
+
+        let packet = rtshark.read().await.unwrap().unwrap();
+        assert_eq!(packet.timestamp_micros(), Some(1_000_000));
+        let udp = packet.layer_name("udp").unwrap();
+        assert_eq!(udp.metadata("udp.srcport").unwrap().value(), "53");
+        assert!(udp.metadata("udp.length").is_none());
+
+        let packet = rtshark.read().await.unwrap().unwrap();
+        let udp = packet.layer_name("udp").unwrap();
+        assert_eq!(udp.metadata("udp.srcport").unwrap().value(), "54");
+
+        assert!(rtshark.read().await.unwrap().is_none());
+        assert!(rtshark.read().await.unwrap().is_none());
+        assert!(rtshark.pid().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_async_stream_error() {
+        let mut rtshark = spawn_fake_tshark("", "tshark: error\n");
+
+        let poll = std::future::poll_fn(|cx| Pin::new(&mut rtshark).poll_next(cx)).await;
+        let err = poll.unwrap().unwrap_err();
+        assert!(matches!(err, RTSharkError::TsharkExited { status, stderr }
+            if status.is_some_and(|s| !s.success()) && stderr == "tshark: error"));
+
+        let poll = std::future::poll_fn(|cx| Pin::new(&mut rtshark).poll_next(cx)).await;
+        assert!(poll.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_async_exit_after_eof() {
+        // outputs are closed before TShark exits successfully
+        let process = tokio::process::Command::new("sh")
+            .args([
+                "-c",
+                "printf '%s' \"$0\"; echo 'tshark: error' >&2; exec >&- 2>&-; sleep 0.2",
+                PDML,
+            ])
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .unwrap();
+        let mut rtshark = AsyncRTShark::new(process, vec![]);
+
+        assert!(rtshark.read().await.unwrap().is_some());
+        assert!(rtshark.read().await.unwrap().is_some());
+        assert!(rtshark.read().await.unwrap().is_none());
+        assert!(rtshark.pid().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_async_stderr_drained() {
+        // more lines than the pipe buffer can hold, before any packet
+        let process = tokio::process::Command::new("sh")
+            .args([
+                "-c",
+                "i=0; while [ $i -lt 20000 ]; do echo '1 packet dropped' >&2; i=$((i+1)); done; printf '%s' \"$0\"",
+                PDML,
+            ])
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .unwrap();
+        let mut rtshark = AsyncRTShark::new(process, vec![]);
+
+        assert!(rtshark.read().await.unwrap().is_some());
+        assert!(rtshark.read().await.unwrap().is_some());
+        assert!(rtshark.read().await.unwrap().is_none());
+        assert_eq!(rtshark.warnings().len(), crate::stderr::STDERR_CAPACITY);
+    }
+
+    #[tokio::test]
+    async fn test_async_kill() {
+        let process = tokio::process::Command::new("sleep")
+            .arg("10")
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .unwrap();
+        let mut rtshark = AsyncRTShark::new(process, vec![]);
+
+        assert!(rtshark.pid().is_some());
+        rtshark.kill().await;
+        assert!(rtshark.pid().is_none());
+        assert!(rtshark.read().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_async_input_reader() {
+        use crate::tests::{env_path, pdml, write_script};
+
+        let tmp_dir = tempdir::TempDir::new("test_async_input_reader").unwrap();
+        write_script(tmp_dir.path(), "tshark", "#!/bin/sh\ncat\n");
+
+        let env_path = env_path(tmp_dir.path());
+        let capture = std::io::Cursor::new(pdml(&[1, 2]).into_bytes());
+        let builder = crate::RTSharkBuilder::builder()
+            .input_reader(capture)
+            .env_path(&env_path);
+        let mut rtshark = builder.spawn_async().unwrap();
+
+        let packet = rtshark.read().await.unwrap().unwrap();
+        assert_eq!(packet.frame_number(), Some(1));
+        let packet = rtshark.read().await.unwrap().unwrap();
+        assert_eq!(packet.frame_number(), Some(2));
+        assert!(rtshark.read().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_async_input_reader_error() {
+        use crate::tests::{env_path, pdml, write_script};
+        use std::io::Read;
+
+        let tmp_dir = tempdir::TempDir::new("test_async_input_reader").unwrap();
+        write_script(tmp_dir.path(), "tshark", "#!/bin/sh\ncat\n");
+
+        let env_path = env_path(tmp_dir.path());
+        let failing = std::io::Cursor::new(pdml(&[1]).into_bytes()).chain(FailingReader);
+        let builder = crate::RTSharkBuilder::builder()
+            .input_reader(failing)
+            .env_path(&env_path);
+        let mut rtshark = builder.spawn_async().unwrap();
+
+        let packet = rtshark.read().await.unwrap().unwrap();
+        assert_eq!(packet.frame_number(), Some(1));
+        assert!(matches!(
+            rtshark.read().await,
+            Err(RTSharkError::Io(e)) if e.kind() == std::io::ErrorKind::ConnectionReset
+        ));
+        assert!(rtshark.read().await.unwrap().is_none());
+    }
+
+    /// Reader failing like an interrupted download.
+    struct FailingReader;
+
+    impl std::io::Read for FailingReader {
+        fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::ConnectionReset,
+                "download failed",
+            ))
+        }
+    }
+
+    #[test]
+    fn test_spawn_async_unsupported() {
+        let builder = crate::RTSharkBuilder::builder().input_path("/tmp/my.pcap");
+        assert!(matches!(
+            builder
+                .output_format(crate::OutputFormat::Json)
+                .spawn_async(),
+            Err(RTSharkError::InvalidArgument { .. })
+        ));
+        assert!(matches!(
+            builder.recover_xml_errors().spawn_async(),
+            Err(RTSharkError::InvalidArgument { .. })
+        ));
+    }
+}
@@ -0,0 +1,383 @@
+//! Catalog of protocols and fields known by TShark, built from `tshark -G fields` and `tshark -G protocols`.
+
+use crate::value::ValueError;
+use crate::{Metadata, RTSharkError, Result};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::Duration;
+
+/// Type of a field, as reported by TShark (FT_* types).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FieldType {
+    /// FT_NONE: a label, without value
+    None,
+    /// FT_PROTOCOL: a protocol
+    Protocol,
+    /// FT_BOOLEAN
+    Boolean,
+    /// FT_UINT8 to FT_UINT64, FT_CHAR and FT_FRAMENUM
+    Unsigned,
+    /// FT_INT8 to FT_INT64
+    Signed,
+    /// FT_FLOAT and FT_DOUBLE
+    Float,
+    /// FT_IPv4
+    Ipv4,
+    /// FT_IPv6
+    Ipv6,
+    /// FT_ETHER: a MAC address
+    Ether,
+    /// FT_BYTES, FT_UINT_BYTES and other types displayed as bytes
+    Bytes,
+    /// FT_STRING and other string types
+    String,
+    /// FT_ABSOLUTE_TIME
+    AbsoluteTime,
+    /// FT_RELATIVE_TIME
+    RelativeTime,
+    /// Any other type, with its TShark name
+    Other(String),
+}
+
+impl FieldType {
+    /// Map a TShark type name (example: "FT_UINT16") to a [FieldType].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rtshark::FieldType;
+    /// assert_eq!(FieldType::from_ftype("FT_UINT16"), FieldType::Unsigned);
+    /// ```
+    pub fn from_ftype(ftype: &str) -> FieldType {
+        match ftype {
+            "FT_NONE" => FieldType::None,
+            "FT_PROTOCOL" => FieldType::Protocol,
+            "FT_BOOLEAN" => FieldType::Boolean,
+            "FT_CHAR" | "FT_FRAMENUM" => FieldType::Unsigned,
+            "FT_FLOAT" | "FT_DOUBLE" => FieldType::Float,
+            "FT_IPv4" => FieldType::Ipv4,
+            "FT_IPv6" => FieldType::Ipv6,
+            "FT_ETHER" => FieldType::Ether,
+            "FT_BYTES" | "FT_UINT_BYTES" | "FT_OID" | "FT_REL_OID" | "FT_SYSTEM_ID"
+            | "FT_EUI64" | "FT_AX25" | "FT_VINES" | "FT_FCWWN" => FieldType::Bytes,
+            "FT_STRING" | "FT_STRINGZ" | "FT_UINT_STRING" | "FT_STRINGZPAD" | "FT_STRINGZTRUNC" => {
+                FieldType::String
+            }
+            "FT_ABSOLUTE_TIME" => FieldType::AbsoluteTime,
+            "FT_RELATIVE_TIME" => FieldType::RelativeTime,
+            t if t.starts_with("FT_UINT") => FieldType::Unsigned,
+            t if t.starts_with("FT_INT") => FieldType::Signed,
+            t => FieldType::Other(t.to_owned()),
+        }
+    }
+}
+
+/// A value converted according to the [FieldType] of its field.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FieldValue {
+    /// Value of a FT_NONE or FT_PROTOCOL field, or of an unknown field
+    Text(String),
+    /// Boolean value
+    Boolean(bool),
+    /// Unsigned integer value
+    Unsigned(u64),
+    /// Signed integer value
+    Signed(i64),
+    /// Floating point value
+    Float(f64),
+    /// IPv4 or IPv6 address
+    IpAddr(IpAddr),
+    /// MAC address
+    Ether([u8; 6]),
+    /// Bytes value
+    Bytes(Vec<u8>),
+    /// Relative time
+    Duration(Duration),
+}
+
+/// A protocol known by TShark.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProtocolEntry {
+    /// Full name (example: "Internet Protocol Version 4")
+    name: String,
+    /// Short name (example: "IPv4")
+    short_name: String,
+    /// Filter name, used as prefix for field names (example: "ip")
+    abbrev: String,
+}
+
+impl ProtocolEntry {
+    /// Full name of this protocol (example: "Internet Protocol Version 4").
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Short name of this protocol (example: "IPv4").
+    pub fn short_name(&self) -> &str {
+        &self.short_name
+    }
+
+    /// Filter name of this protocol (example: "ip"). This is the name of the [crate::Layer].
+    pub fn abbrev(&self) -> &str {
+        &self.abbrev
+    }
+}
+
+/// A field known by TShark. Its abbreviation is the name of the matching [Metadata].
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldEntry {
+    /// Field abbreviation (example: "ip.src")
+    abbrev: String,
+    /// Descriptive name (example: "Source Address")
+    name: String,
+    /// Type name, as reported by TShark (example: "FT_IPv4")
+    ftype: String,
+    /// Filter name of the parent protocol (example: "ip")
+    parent: String,
+    /// Base for display (example: "BASE_DEC"), if any
+    base: Option<String>,
+    /// Description of this field, if any
+    blurb: Option<String>,
+}
+
+impl FieldEntry {
+    /// Abbreviation of this field (example: "ip.src"). This is the name of the matching [Metadata].
+    pub fn abbrev(&self) -> &str {
+        &self.abbrev
+    }
+
+    /// Descriptive name of this field (example: "Source Address").
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Type name of this field, as reported by TShark (example: "FT_IPv4").
+    pub fn ftype(&self) -> &str {
+        &self.ftype
+    }
+
+    /// Type of this field.
+    pub fn field_type(&self) -> FieldType {
+        FieldType::from_ftype(&self.ftype)
+    }
+
+    /// Filter name of the parent protocol (example: "ip").
+    pub fn parent(&self) -> &str {
+        &self.parent
+    }
+
+    /// Base used by TShark to display this field (example: "BASE_HEX"), if any.
+    pub fn base(&self) -> Option<&str> {
+        self.base.as_deref()
+    }
+
+    /// Description of this field, if any.
+    pub fn blurb(&self) -> Option<&str> {
+        self.blurb.as_deref()
+    }
+}
+
+/// Catalog of all protocols and fields known by a TShark executable.
+/// It is created by [crate::RTSharkBuilder::field_catalog].
+#[derive(Clone, Debug, Default)]
+pub struct FieldCatalog {
+    /// List of protocols
+    protocols: Vec<ProtocolEntry>,
+    /// List of fields
+    fields: Vec<FieldEntry>,
+    /// Index of protocols by abbreviation
+    protocol_index: HashMap<String, usize>,
+    /// Index of fields by abbreviation
+    field_index: HashMap<String, usize>,
+}
+
+impl FieldCatalog {
+    /// Build a catalog from `tshark -G fields` and `tshark -G protocols` reports.
+    pub(crate) fn parse(fields: &str, protocols: &str) -> FieldCatalog {
+        let mut catalog = FieldCatalog::default();
+
+        // tshark -G protocols: one protocol per line
+        // name \t short name \t filter name
+        for line in protocols.lines() {
+            let mut columns = line.split('\t');
+            if let (Some(name), Some(short_name), Some(abbrev)) =
+                (columns.next(), columns.next(), columns.next())
+            {
+                catalog.add_protocol(ProtocolEntry {
+                    name: name.to_owned(),
+                    short_name: short_name.to_owned(),
+                    abbrev: abbrev.to_owned(),
+                });
+            }
+        }
+
+        // tshark -G fields: one protocol or field per line
+        // P \t name \t abbrev
+        // F \t name \t abbrev \t type \t parent protocol \t base \t bitmask \t blurb
+        for line in fields.lines() {
+            let columns: Vec<&str> = line.split('\t').collect();
+            match columns.as_slice() {
+                ["P", name, abbrev, ..] if !catalog.protocol_index.contains_key(*abbrev) => {
+                    catalog.add_protocol(ProtocolEntry {
+                        name: name.to_string(),
+                        short_name: name.to_string(),
+                        abbrev: abbrev.to_string(),
+                    });
+                }
+                ["F", name, abbrev, ftype, parent, rest @ ..] => {
+                    let non_empty =
+                        |s: Option<&&str>| s.filter(|s| !s.is_empty()).map(|s| s.to_string());
+                    catalog
+                        .field_index
+                        .insert(abbrev.to_string(), catalog.fields.len());
+                    catalog.fields.push(FieldEntry {
+                        abbrev: abbrev.to_string(),
+                        name: name.to_string(),
+                        ftype: ftype.to_string(),
+                        parent: parent.to_string(),
+                        base: non_empty(rest.first()),
+                        blurb: non_empty(rest.get(2)),
+                    });
+                }
+                _ => (),
+            }
+        }
+
+        catalog
+    }
+
+    fn add_protocol(&mut self, protocol: ProtocolEntry) {
+        self.protocol_index
+            .insert(protocol.abbrev.clone(), self.protocols.len());
+        self.protocols.push(protocol);
+    }
+
+    /// Get a field by its abbreviation (example: "ip.src").
+    pub fn field(&self, abbrev: &str) -> Option<&FieldEntry> {
+        self.field_index.get(abbrev).map(|&i| &self.fields[i])
+    }
+
+    /// Get a protocol by its filter name (example: "ip").
+    pub fn protocol(&self, abbrev: &str) -> Option<&ProtocolEntry> {
+        self.protocol_index.get(abbrev).map(|&i| &self.protocols[i])
+    }
+
+    /// Check if a name is a known field or protocol. This is how metadata names are validated.
+    pub fn contains(&self, name: &str) -> bool {
+        self.field_index.contains_key(name) || self.protocol_index.contains_key(name)
+    }
+
+    /// Get an iterator on all fields of this catalog.
+    pub fn fields(&self) -> impl Iterator<Item = &FieldEntry> {
+        self.fields.iter()
+    }
+
+    /// Get an iterator on all protocols of this catalog.
+    pub fn protocols(&self) -> impl Iterator<Item = &ProtocolEntry> {
+        self.protocols.iter()
+    }
+
+    /// Convert the value of a [Metadata] according to the type of its field.
+    /// Values of unknown fields, labels, strings and absolute times are returned as [FieldValue::Text].
+    pub fn typed_value(&self, metadata: &Metadata) -> std::result::Result<FieldValue, ValueError> {
+        let field_type = match self.field(metadata.name()) {
+            Some(field) => field.field_type(),
+            None => return Ok(FieldValue::Text(metadata.value().to_owned())),
+        };
+
+        Ok(match field_type {
+            FieldType::Boolean => FieldValue::Boolean(metadata.as_bool()?),
+            FieldType::Unsigned => FieldValue::Unsigned(metadata.as_u64()?),
+            FieldType::Signed => FieldValue::Signed(metadata.as_i64()?),
+            FieldType::Float => FieldValue::Float(metadata.as_f64()?),
+            FieldType::Ipv4 | FieldType::Ipv6 => FieldValue::IpAddr(metadata.as_ip_addr()?),
+            FieldType::Ether => FieldValue::Ether(metadata.as_mac()?),
+            FieldType::Bytes => FieldValue::Bytes(metadata.as_bytes()?),
+            FieldType::RelativeTime => FieldValue::Duration(metadata.as_duration()?),
+            _ => FieldValue::Text(metadata.value().to_owned()),
+        })
+    }
+}
+
+/// Run "tshark -G <report>" and return its output.
+pub(crate) fn glossary(report: &str) -> Result<String> {
+    let output = std::process::Command::new("tshark")
+        .args(["-G", report])
+        .output()
+        .map_err(crate::tshark_spawn_error)?;
+
+    // the first stderr lines may be warnings, like "Running as user root"
+    crate::stderr::check_output(&output)?;
+
+    String::from_utf8(output.stdout)
+        .map_err(|e| RTSharkError::invalid_data(format!("Glossary report not utf8: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIELDS: &str = "P\tInternet Protocol Version 4\tip
+F\tSource Address\tip.src\tFT_IPv4\tip\t\t0x0\t
+F\tTime to Live\tip.ttl\tFT_UINT8\tip\tBASE_DEC\t0x0\t
+F\tDon't fragment\tip.flags.df\tFT_BOOLEAN\tip\t8\t0x40\t
+F\tSource\teth.src\tFT_ETHER\teth\t\t0x0\tSource Hardware Address
+F\tTime delta from previous captured frame\tframe.time_delta\tFT_RELATIVE_TIME\tframe\t\t0x0\t
+P\tEthernet\teth
+";
+
+    const PROTOCOLS: &str = "Internet Protocol Version 4\tIPv4\tip
+Ethernet\tEthernet\teth
+";
+
+    #[test]
+    fn test_catalog_parse() {
+        let catalog = FieldCatalog::parse(FIELDS, PROTOCOLS);
+
+        assert_eq!(catalog.protocols().count(), 2);
+        assert_eq!(catalog.fields().count(), 5);
+        assert_eq!(catalog.protocol("ip").unwrap().short_name(), "IPv4");
+
+        let ttl = catalog.field("ip.ttl").unwrap();
+        assert_eq!(ttl.name(), "Time to Live");
+        assert_eq!(ttl.ftype(), "FT_UINT8");
+        assert_eq!(ttl.field_type(), FieldType::Unsigned);
+        assert_eq!(ttl.parent(), "ip");
+        assert_eq!(ttl.base(), Some("BASE_DEC"));
+        assert_eq!(ttl.blurb(), None);
+
+        let eth_src = catalog.field("eth.src").unwrap();
+        assert_eq!(eth_src.base(), None);
+        assert_eq!(eth_src.blurb(), Some("Source Hardware Address"));
+
+        assert!(catalog.contains("ip"));
+        assert!(catalog.contains("ip.src"));
+        assert!(!catalog.contains("ip.srcc"));
+    }
+
+    #[test]
+    fn test_catalog_typed_value() {
+        let catalog = FieldCatalog::parse(FIELDS, PROTOCOLS);
+
+        let value = |name: &str, value: &str| {
+            let metadata = Metadata::new(name.to_string(), value.to_string(), None, None, None);
+            catalog.typed_value(&metadata)
+        };
+
+        assert_eq!(value("ip.ttl", "64"), Ok(FieldValue::Unsigned(64)));
+        assert_eq!(value("ip.flags.df", "True"), Ok(FieldValue::Boolean(true)));
+        assert_eq!(
+            value("ip.src", "127.0.0.1"),
+            Ok(FieldValue::IpAddr(std::net::Ipv4Addr::LOCALHOST.into()))
+        );
+        assert_eq!(
+            value("frame.time_delta", "0.000001000"),
+            Ok(FieldValue::Duration(Duration::from_micros(1)))
+        );
+        assert_eq!(
+            value("unknown.field", "64"),
+            Ok(FieldValue::Text("64".to_string()))
+        );
+        assert!(value("ip.ttl", "sixty-four").is_err());
+    }
+}
@@ -0,0 +1,398 @@
+//! Packets read and parsed in a background thread, see [crate::RTShark::into_channel].
+
+use crate::{Packet, RTShark, RTSharkHandle, ReadOutcome, Result};
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// What the reader thread does with a new packet when the channel is full,
+/// see [RTShark::into_channel].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BackpressurePolicy {
+    /// Wait until the consumer receives a packet. TShark is slowed down, and may lose packets
+    /// on a live capture.
+    Block,
+    /// Drop the new packet, to keep the oldest ones
+    DropNewest,
+    /// Drop the oldest packet of the channel, to keep the most recent ones
+    DropOldest,
+}
+
+/// Channel content, shared by the reader thread and the receiver.
+struct State {
+    /// packets or errors not received yet
+    items: VecDeque<Result<Packet>>,
+    /// no more items will be sent
+    closed: bool,
+    /// the receiver is dropped: the reader thread stops
+    receiver_dropped: bool,
+    /// number of items dropped because the channel was full
+    dropped: u64,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    /// notified when an item is sent, or the channel is closed
+    not_empty: Condvar,
+    /// notified when an item is received, or the receiver is dropped
+    not_full: Condvar,
+    capacity: usize,
+    /// also notified when an item is sent, or the channel is closed
+    notifier: Option<Arc<Notifier>>,
+}
+
+/// Shared by several channels, to wait for an item sent to any of them.
+#[derive(Default)]
+pub(crate) struct Notifier {
+    /// number of items sent and channels closed
+    events: Mutex<u64>,
+    changed: Condvar,
+}
+
+impl Notifier {
+    fn notify(&self) {
+        let mut events = self.events.lock().unwrap_or_else(|e| e.into_inner());
+        *events = events.wrapping_add(1);
+        self.changed.notify_all();
+    }
+
+    /// Get the number of events so far, to wait for the next one.
+    pub(crate) fn events(&self) -> u64 {
+        *self.events.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Wait until an item is sent, or a channel is closed, after the given number of events.
+    pub(crate) fn wait(&self, events: u64) {
+        let mut current = self.events.lock().unwrap_or_else(|e| e.into_inner());
+        while *current == events {
+            current = self
+                .changed
+                .wait(current)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+    }
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Send an item, according to the backpressure policy.
+    /// Returns false if the receiver is dropped.
+    fn send(&self, item: Result<Packet>, policy: BackpressurePolicy) -> bool {
+        let mut state = self.lock();
+
+        // the last error must not be lost, so that the consumer knows why no more packets come
+        let fatal = matches!(&item, Err(e) if e.is_fatal());
+
+        while !fatal && !state.receiver_dropped && state.items.len() >= self.capacity {
+            match policy {
+                BackpressurePolicy::Block => {
+                    state = self.not_full.wait(state).unwrap_or_else(|e| e.into_inner())
+                }
+                BackpressurePolicy::DropNewest => {
+                    state.dropped += 1;
+                    return true;
+                }
+                BackpressurePolicy::DropOldest => {
+                    state.items.pop_front();
+                    state.dropped += 1;
+                }
+            }
+        }
+
+        if state.receiver_dropped {
+            return false;
+        }
+
+        state.items.push_back(item);
+        self.not_empty.notify_one();
+        drop(state);
+
+        if let Some(notifier) = &self.notifier {
+            notifier.notify();
+        }
+        true
+    }
+
+    fn close(&self) {
+        self.lock().closed = true;
+        self.not_empty.notify_all();
+
+        if let Some(notifier) = &self.notifier {
+            notifier.notify();
+        }
+    }
+}
+
+/// Receiver of packets read by a background thread, created by [RTShark::into_channel].
+///
+/// Packets and errors are received in the order they were read. Once the stream ends, after
+/// a fatal error or at the end of TShark output, no more packets are received.
+///
+/// Dropping the receiver kills TShark, or stops it gracefully if
+/// [crate::RTSharkBuilderReady::graceful_drop] was used.
+pub struct PacketReceiver {
+    shared: Arc<Shared>,
+    handle: RTSharkHandle,
+    graceful_drop: Option<Duration>,
+}
+
+impl PacketReceiver {
+    /// Start the reader thread. The notifier, if any, is also notified of sent items.
+    pub(crate) fn spawn(
+        rtshark: RTShark,
+        capacity: usize,
+        policy: BackpressurePolicy,
+        notifier: Option<Arc<Notifier>>,
+    ) -> Result<Self> {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                items: VecDeque::new(),
+                closed: false,
+                receiver_dropped: false,
+                dropped: 0,
+            }),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+            capacity: capacity.max(1),
+            notifier,
+        });
+
+        let handle = rtshark.handle();
+        let graceful_drop = rtshark.graceful_drop;
+
+        let thread_shared = shared.clone();
+        std::thread::Builder::new()
+            .name("rtshark-reader".to_owned())
+            .spawn(move || {
+                // the iteration ends after a fatal error
+                for item in rtshark {
+                    if !thread_shared.send(item, policy) {
+                        break;
+                    }
+                }
+                thread_shared.close();
+            })?;
+
+        Ok(PacketReceiver {
+            shared,
+            handle,
+            graceful_drop,
+        })
+    }
+
+    /// Receive the next packet, waiting for it if needed. Returns None once the stream ended
+    /// and all packets were received.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let builder = rtshark::RTSharkBuilder::builder().input_path("/tmp/my.pcap");
+    /// # let Ok(rtshark) = builder.spawn() else { return };
+    /// # let Ok(receiver) = rtshark.into_channel(100, rtshark::BackpressurePolicy::Block) else { return };
+    /// while let Some(packet) = receiver.recv() {
+    ///     match packet {
+    ///         Ok(packet) => println!("Got a packet"),
+    ///         Err(e) => eprintln!("Got decoding error: {e}"),
+    ///     }
+    /// }
+    /// ```
+    pub fn recv(&self) -> Option<Result<Packet>> {
+        let mut state = self.shared.lock();
+        loop {
+            if let Some(item) = state.items.pop_front() {
+                self.shared.not_full.notify_one();
+                return Some(item);
+            }
+            if state.closed {
+                return None;
+            }
+            state = self
+                .shared
+                .not_empty
+                .wait(state)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    /// Receive the next packet, like [PacketReceiver::recv], but give up if no packet is
+    /// received before the timeout. Like [RTShark::read_timeout], it returns
+    /// [ReadOutcome::Timeout] or [ReadOutcome::Eof] when no packet is available.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let builder = rtshark::RTSharkBuilder::builder().input_path("/tmp/my.pcap");
+    /// # let Ok(rtshark) = builder.spawn() else { return };
+    /// # let Ok(receiver) = rtshark.into_channel(100, rtshark::BackpressurePolicy::DropOldest) else { return };
+    /// use rtshark::ReadOutcome;
+    /// use std::time::Duration;
+    ///
+    /// loop {
+    ///     match receiver.recv_timeout(Duration::from_millis(500)) {
+    ///         Ok(ReadOutcome::Packet(packet)) => println!("Got a packet"),
+    ///         Ok(ReadOutcome::Timeout) => println!("{} packets dropped", receiver.dropped()),
+    ///         Ok(ReadOutcome::Eof) => break,
+    ///         Err(e) => eprintln!("Got decoding error: {e}"),
+    ///     }
+    /// }
+    /// ```
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<ReadOutcome> {
+        let deadline = Instant::now().checked_add(timeout);
+        let mut state = self.shared.lock();
+        loop {
+            if let Some(item) = state.items.pop_front() {
+                self.shared.not_full.notify_one();
+                return item.map(ReadOutcome::Packet);
+            }
+            if state.closed {
+                return Ok(ReadOutcome::Eof);
+            }
+
+            let remaining = match deadline {
+                Some(deadline) => deadline.saturating_duration_since(Instant::now()),
+                None => Duration::MAX,
+            };
+            if remaining.is_zero() {
+                return Ok(ReadOutcome::Timeout);
+            }
+            state = self
+                .shared
+                .not_empty
+                .wait_timeout(state, remaining)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+    }
+
+    /// Number of packets (or errors) dropped because the channel was full.
+    pub fn dropped(&self) -> u64 {
+        self.shared.lock().dropped
+    }
+
+    /// Number of packets (or errors) waiting in the channel.
+    pub fn len(&self) -> usize {
+        self.shared.lock().items.len()
+    }
+
+    /// Check if no packet is waiting in the channel.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get a handle to kill or stop TShark, see [RTShark::handle].
+    pub fn handle(&self) -> RTSharkHandle {
+        self.handle.clone()
+    }
+}
+
+impl Iterator for PacketReceiver {
+    type Item = Result<Packet>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.recv()
+    }
+}
+
+impl Drop for PacketReceiver {
+    fn drop(&mut self) {
+        self.shared.lock().receiver_dropped = true;
+        self.shared.not_full.notify_all();
+
+        // the reader thread may be blocked on a quiet interface
+        let stopped = match self.graceful_drop {
+            Some(timeout) => self.handle.stop(timeout).is_ok(),
+            None => false,
+        };
+        if !stopped {
+            self.handle.kill();
+        }
+    }
+}
+
+#[cfg(all(test, target_family = "unix"))]
+mod tests {
+    use super::*;
+    use crate::tests::fake_rtshark;
+    use crate::OutputFormat;
+
+    fn packets_script(count: usize) -> String {
+        let packets: String = (1..=count)
+            .map(|i| {
+                format!("<packet>\n<proto name=\"frame\"><field name=\"frame.number\" show=\"{i}\"/></proto>\n</packet>\n")
+            })
+            .collect();
+        format!("printf '%s' '<pdml>\n{packets}</pdml>\n'")
+    }
+
+    /// Wait until the reader thread has read everything.
+    fn wait_closed(receiver: &PacketReceiver) {
+        while !receiver.shared.lock().closed {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn numbers(receiver: PacketReceiver) -> Vec<String> {
+        receiver
+            .map(|packet| {
+                let packet = packet.unwrap();
+                let frame = packet.layer_name("frame").unwrap();
+                frame.metadata("frame.number").unwrap().value().to_owned()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_channel_block() {
+        let rtshark = fake_rtshark(&packets_script(5), OutputFormat::Pdml);
+        let receiver = rtshark.into_channel(2, BackpressurePolicy::Block).unwrap();
+
+        // the reader thread waits for the consumer
+        std::thread::sleep(Duration::from_millis(200));
+        assert_eq!(receiver.len(), 2);
+
+        assert_eq!(numbers(receiver), ["1", "2", "3", "4", "5"]);
+    }
+
+    #[test]
+    fn test_channel_drop_newest() {
+        let rtshark = fake_rtshark(&packets_script(5), OutputFormat::Pdml);
+        let receiver = rtshark
+            .into_channel(2, BackpressurePolicy::DropNewest)
+            .unwrap();
+
+        wait_closed(&receiver);
+        assert_eq!(receiver.dropped(), 3);
+        assert_eq!(numbers(receiver), ["1", "2"]);
+    }
+
+    #[test]
+    fn test_channel_drop_oldest() {
+        let rtshark = fake_rtshark(&packets_script(5), OutputFormat::Pdml);
+        let receiver = rtshark
+            .into_channel(2, BackpressurePolicy::DropOldest)
+            .unwrap();
+
+        wait_closed(&receiver);
+        assert_eq!(receiver.dropped(), 3);
+        assert_eq!(numbers(receiver), ["4", "5"]);
+    }
+
+    #[test]
+    fn test_channel_timeout() {
+        let rtshark = fake_rtshark("sleep 10", OutputFormat::Pdml);
+        let handle = rtshark.handle();
+        let receiver = rtshark.into_channel(2, BackpressurePolicy::Block).unwrap();
+
+        assert!(matches!(
+            receiver.recv_timeout(Duration::from_millis(100)),
+            Ok(ReadOutcome::Timeout)
+        ));
+
+        // dropping the receiver kills TShark
+        drop(receiver);
+        assert!(!handle.is_running());
+    }
+}
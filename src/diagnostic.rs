@@ -0,0 +1,273 @@
+//! Typed messages printed by TShark (and dumpcap) on stderr, see [crate::RTShark::diagnostics].
+
+/// Severity of a [Diagnostic].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DiagnosticSeverity {
+    /// Information about the capture, not a problem
+    Info,
+    /// Non-fatal problem: all readable packets are still delivered
+    Warning,
+    /// Fatal problem: TShark stops
+    Error,
+}
+
+/// Kind of a [Diagnostic].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DiagnosticKind {
+    /// Syntax error in a display filter
+    DisplayFilter,
+    /// Syntax error in a capture filter
+    CaptureFilter,
+    /// Capture file cut short in the middle of a packet
+    TruncatedCapture,
+    /// Input file is not a capture file, or its format is unknown
+    UnknownFileFormat,
+    /// Input file does not exist
+    FileNotFound,
+    /// Not allowed to capture on an interface or to read a file
+    PermissionDenied,
+    /// TShark is running as root
+    RunningAsRoot,
+    /// Capture information: interfaces, number of packets captured or dropped
+    CaptureInfo,
+    /// Warning from a dissector or from TShark libraries
+    DissectorWarning,
+    /// Unknown message
+    Other,
+}
+
+impl DiagnosticKind {
+    /// Get the severity of this kind of message.
+    pub fn severity(&self) -> DiagnosticSeverity {
+        match self {
+            DiagnosticKind::RunningAsRoot | DiagnosticKind::CaptureInfo => DiagnosticSeverity::Info,
+            DiagnosticKind::TruncatedCapture | DiagnosticKind::DissectorWarning => {
+                DiagnosticSeverity::Warning
+            }
+            DiagnosticKind::DisplayFilter
+            | DiagnosticKind::CaptureFilter
+            | DiagnosticKind::UnknownFileFormat
+            | DiagnosticKind::FileNotFound
+            | DiagnosticKind::PermissionDenied
+            | DiagnosticKind::Other => DiagnosticSeverity::Error,
+        }
+    }
+}
+
+/// A message printed by TShark on stderr.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    kind: DiagnosticKind,
+    message: String,
+    column: Option<usize>,
+}
+
+impl Diagnostic {
+    /// Classify one line printed by TShark on stderr.
+    pub(crate) fn parse(line: &str) -> Self {
+        let message = line.trim();
+        // quoted file names and filters may contain any word, like "filter" or "permission"
+        let lower = without_quoted(message).to_lowercase();
+
+        let kind = if message.starts_with("** (tshark:")
+            || message.starts_with("(tshark:")
+            || message.contains("WARNING")
+        {
+            DiagnosticKind::DissectorWarning
+        } else if lower.starts_with("running as user") {
+            DiagnosticKind::RunningAsRoot
+        } else if lower.starts_with("capturing on")
+            || lower.ends_with("captured")
+            || lower.contains("packet dropped")
+            || lower.contains("packets dropped")
+        {
+            DiagnosticKind::CaptureInfo
+        } else if lower.contains("cut short in the middle of a packet") {
+            DiagnosticKind::TruncatedCapture
+        } else if lower.contains("isn't a capture file in a format")
+            || lower.contains("unknown file format")
+        {
+            DiagnosticKind::UnknownFileFormat
+        } else if lower.contains("capture filter") {
+            DiagnosticKind::CaptureFilter
+        } else if lower.contains("permission") || lower.contains("operation not permitted") {
+            DiagnosticKind::PermissionDenied
+        } else if lower.contains("filter") || lower.contains("neither a field nor a protocol") {
+            DiagnosticKind::DisplayFilter
+        } else if lower.contains("doesn't exist") || lower.contains("no such file") {
+            DiagnosticKind::FileNotFound
+        } else {
+            DiagnosticKind::Other
+        };
+
+        Diagnostic {
+            kind,
+            message: message.to_owned(),
+            column: None,
+        }
+    }
+
+    /// Kind of message.
+    pub fn kind(&self) -> DiagnosticKind {
+        self.kind
+    }
+
+    /// Severity of the message.
+    pub fn severity(&self) -> DiagnosticSeverity {
+        self.kind.severity()
+    }
+
+    /// Message printed by TShark.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Position of a filter syntax error in the filter, starting at 0, when TShark shows it.
+    pub fn column(&self) -> Option<usize> {
+        self.column
+    }
+}
+
+/// Remove the double-quoted parts of a message, keeping the quotes.
+fn without_quoted(message: &str) -> String {
+    message
+        .split('"')
+        .enumerate()
+        .map(|(i, part)| if i % 2 == 0 { part } else { "" })
+        .collect::<Vec<_>>()
+        .join("\"")
+}
+
+/// Parse all lines printed by TShark on stderr. Empty lines are ignored.
+pub(crate) fn parse_diagnostics(lines: &[String]) -> Vec<Diagnostic> {
+    // filter errors are followed by the filter and a caret under the error, like:
+    //
+    // tshark: "ip.srcc" is neither a field nor a protocol name.
+    //     ip.srcc == 1
+    //     ^~~~~~~
+    let mut diagnostics = vec![];
+    let mut lines = lines.iter();
+
+    while let Some(line) = lines.next() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let mut diagnostic = Diagnostic::parse(line);
+
+        if diagnostic.severity() == DiagnosticSeverity::Error {
+            let mut next = lines.clone();
+            if let (Some(filter), Some(caret)) = (next.next(), next.next()) {
+                let indent = filter.len() - filter.trim_start().len();
+                if indent > 0 && caret.trim_start().starts_with('^') {
+                    // some syntax errors do not mention the filter
+                    if diagnostic.kind == DiagnosticKind::Other {
+                        diagnostic.kind = DiagnosticKind::DisplayFilter;
+                    }
+                    diagnostic.column = caret.find('^').map(|c| c.saturating_sub(indent));
+                    lines = next;
+                }
+            }
+        }
+
+        diagnostics.push(diagnostic);
+    }
+
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_diagnostics() {
+        let stderr: Vec<String> = [
+            "Running as user \"root\" and group \"root\". This could be dangerous.",
+            "tshark: \"ip.srcc\" is neither a field nor a protocol name.",
+            "    ip.srcc == 1 && udp",
+            "    ^~~~~~~",
+            "",
+            "tshark: The file \"/tmp/my.pcap\" appears to have been cut short in the middle of a packet.",
+            "tshark: The file \"/tmp/my.txt\" isn't a capture file in a format TShark understands.",
+            "tshark: You don't have permission to capture on that device.",
+            "tshark: Invalid capture filter \"tcp prt 80\" for interface 'lo'.",
+            " ** (tshark:1234) 10:00:00.000000 [Epan WARNING] -- Dissector bug",
+            "12 packets captured",
+            "tshark: something else",
+        ]
+        .iter()
+        .map(|l| l.to_string())
+        .collect();
+
+        let diagnostics = parse_diagnostics(&stderr);
+        let kinds: Vec<DiagnosticKind> = diagnostics.iter().map(|d| d.kind()).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                DiagnosticKind::RunningAsRoot,
+                DiagnosticKind::DisplayFilter,
+                DiagnosticKind::TruncatedCapture,
+                DiagnosticKind::UnknownFileFormat,
+                DiagnosticKind::PermissionDenied,
+                DiagnosticKind::CaptureFilter,
+                DiagnosticKind::DissectorWarning,
+                DiagnosticKind::CaptureInfo,
+                DiagnosticKind::Other,
+            ]
+        );
+
+        assert_eq!(diagnostics[0].severity(), DiagnosticSeverity::Info);
+        assert_eq!(diagnostics[1].severity(), DiagnosticSeverity::Error);
+        assert_eq!(diagnostics[1].column(), Some(0));
+        assert_eq!(
+            diagnostics[1].message(),
+            "tshark: \"ip.srcc\" is neither a field nor a protocol name."
+        );
+        assert_eq!(diagnostics[2].severity(), DiagnosticSeverity::Warning);
+        assert_eq!(diagnostics[5].column(), None);
+    }
+
+    #[test]
+    fn test_filter_column() {
+        let stderr: Vec<String> = [
+            "tshark: \"==\" was unexpected in this context.",
+            "        udp && == 53",
+            "               ^~",
+        ]
+        .iter()
+        .map(|l| l.to_string())
+        .collect();
+
+        let diagnostics = parse_diagnostics(&stderr);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].kind(), DiagnosticKind::DisplayFilter);
+        assert_eq!(diagnostics[0].column(), Some(7));
+    }
+
+    #[test]
+    fn test_quoted_file_name() {
+        let stderr: Vec<String> = [
+            "tshark: The file \"/tmp/filter.pcap\" doesn't exist.",
+            "tshark: The file \"/tmp/permission/my.pcap\" doesn't exist.",
+            "tshark: The file \"/tmp/capture filter.txt\" isn't a capture file in a format TShark understands.",
+        ]
+        .iter()
+        .map(|l| l.to_string())
+        .collect();
+
+        let kinds: Vec<DiagnosticKind> = parse_diagnostics(&stderr)
+            .iter()
+            .map(|d| d.kind())
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                DiagnosticKind::FileNotFound,
+                DiagnosticKind::FileNotFound,
+                DiagnosticKind::UnknownFileFormat,
+            ]
+        );
+    }
+}
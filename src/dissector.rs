@@ -0,0 +1,473 @@
+//! Dissect packets given as bytes by a TShark process kept running, see
+//! [crate::RTSharkBuilderReady::spawn_dissector].
+//!
+//! Packets are written to TShark standard input as a pcapng stream: a pcapng interface is
+//! declared for each link type, so that packets of several link types can be dissected.
+//!
+//! The stream is captured as a pipe (-i -), not read as a file (-r -): the file reader waits
+//! for a full buffer before dissecting anything, so a single small packet would never be printed.
+
+use crate::{tshark_spawn_error, Packet, RTShark, RTSharkBuilderReady, RTSharkError, Result};
+use std::io::{BufWriter, Write};
+use std::process::{ChildStdin, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+#[cfg(target_family = "unix")]
+use {
+    crate::ReadOutcome,
+    std::time::{Duration, Instant},
+};
+
+/// pcapng block types
+const SECTION_HEADER_BLOCK: u32 = 0x0A0D_0D0A;
+const INTERFACE_DESCRIPTION_BLOCK: u32 = 0x0000_0001;
+const ENHANCED_PACKET_BLOCK: u32 = 0x0000_0006;
+
+/// A TShark process dissecting packets one by one, created by
+/// [crate::RTSharkBuilderReady::spawn_dissector].
+///
+/// Each call to [Dissector::dissect] writes a packet to TShark standard input and waits for
+/// its dissection. TShark is started once, instead of once per packet.
+///
+/// Packets are dissected as if they were read from a single capture file: TCP reassembly, and
+/// other dissections relying on previous packets, take the previous calls into account.
+///
+/// By default, [Dissector::dissect] waits for TShark as long as needed. On unix, a timeout can
+/// be set with [Dissector::set_timeout].
+pub struct Dissector {
+    /// pcapng stream read by TShark. It is closed first when dropped, so that TShark exits.
+    stdin: BufWriter<ChildStdin>,
+    /// TShark process, printing dissected packets
+    rtshark: RTShark,
+    /// link types of the pcapng interfaces already declared, indexed by interface id
+    link_types: Vec<u16>,
+    /// number of packets written to TShark
+    frames: u64,
+    /// maximum time waiting for a dissection, see [Dissector::set_timeout]
+    #[cfg(target_family = "unix")]
+    timeout: Option<Duration>,
+}
+
+impl Dissector {
+    /// Start TShark reading a pcapng stream on its standard input.
+    pub(crate) fn spawn(builder: &RTSharkBuilderReady) -> Result<Self> {
+        if !builder.display_filter.is_empty() || !builder.capture_filter.is_empty() {
+            return Err(RTSharkError::invalid_argument(
+                "display_filter and capture_filter cannot be used by a dissector: each packet must be printed",
+            ));
+        }
+
+        // packets are dissected as soon as they are received
+        let builder = RTSharkBuilderReady {
+            input_path: vec!["-"],
+            input_globs: vec![],
+            input_reader: None,
+            live_capture: true,
+            ..builder.with_frame_number()
+        };
+        let tshark_params = builder.spawn_args()?;
+        let mut tshark_child = builder
+            .tshark_command(&tshark_params)
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(tshark_spawn_error)?;
+
+        let stdin = BufWriter::new(tshark_child.stdin.take().unwrap());
+        let rtshark = RTShark::new(
+            tshark_child,
+            builder.output_format,
+            builder.metadata_blacklist.clone(),
+            builder.graceful_drop,
+            builder.recover_xml_errors,
+        );
+
+        let mut dissector = Dissector {
+            stdin,
+            rtshark,
+            link_types: vec![],
+            frames: 0,
+            #[cfg(target_family = "unix")]
+            timeout: None,
+        };
+        write_section_header(&mut dissector.stdin)?;
+
+        Ok(dissector)
+    }
+
+    /// Dissect a packet captured at the given time. The link type tells the first protocol of
+    /// the packet, as a LINKTYPE_ value of pcap files (1 for Ethernet, 101 for raw IP ...).
+    ///
+    /// The returned packet has the next frame number, starting at 1.
+    ///
+    /// If a timeout is set with [Dissector::set_timeout] and TShark does not print the dissection
+    /// in time, an [RTSharkError::Io] error of kind [std::io::ErrorKind::TimedOut] is returned.
+    /// The dissector can still be used: the late dissection is skipped by the next call.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let builder = rtshark::RTSharkBuilder::builder().input_path("-");
+    /// # let Ok(mut dissector) = builder.spawn_dissector() else { return };
+    /// // LINKTYPE_ETHERNET
+    /// const ETHERNET: u16 = 1;
+    ///
+    /// let frame = [0u8; 60];
+    /// match dissector.dissect(ETHERNET, std::time::SystemTime::now(), &frame) {
+    ///     Ok(packet) => println!("Got a packet with {} layers", packet.iter().count()),
+    ///     Err(e) => eprintln!("Error dissecting frame: {e}"),
+    /// }
+    /// ```
+    pub fn dissect(
+        &mut self,
+        link_type: u16,
+        timestamp: SystemTime,
+        data: &[u8],
+    ) -> Result<Packet> {
+        let interface = match self.link_types.iter().position(|t| *t == link_type) {
+            Some(interface) => interface,
+            None => {
+                write_interface_description(&mut self.stdin, link_type)?;
+                self.link_types.push(link_type);
+                self.link_types.len() - 1
+            }
+        };
+
+        // timestamps before 1970 are not supported by pcapng
+        let micros = timestamp
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_micros() as u64);
+        write_enhanced_packet(&mut self.stdin, interface as u32, micros, data)?;
+        self.stdin.flush()?;
+        self.frames += 1;
+
+        #[cfg(target_family = "unix")]
+        let deadline = self.timeout.and_then(|t| Instant::now().checked_add(t));
+
+        loop {
+            #[cfg(target_family = "unix")]
+            let packet = self.read_until(deadline)?;
+            #[cfg(not(target_family = "unix"))]
+            let packet = self.rtshark.read()?;
+
+            let Some(packet) = packet else {
+                return Err(RTSharkError::TsharkExited {
+                    status: self.rtshark.process.status(),
+                    stderr: self.rtshark.stderr_lines().join("\n"),
+                });
+            };
+
+            match packet.frame_number() {
+                // dissection of a previous packet, whose read failed
+                Some(number) if number < self.frames => continue,
+                Some(number) if number > self.frames => {
+                    return Err(RTSharkError::invalid_data(format!(
+                        "TShark printed packet {number} instead of packet {}",
+                        self.frames
+                    )))
+                }
+                Some(_) => return Ok(packet),
+                None => {
+                    return Err(RTSharkError::invalid_data(
+                        "TShark printed a packet without frame number",
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Set the maximum time [Dissector::dissect] waits for TShark to print a dissection, or
+    /// wait as long as needed with None (the default). This API is only available on unix.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let builder = rtshark::RTSharkBuilder::builder().input_path("-");
+    /// # let Ok(mut dissector) = builder.spawn_dissector() else { return };
+    /// dissector.set_timeout(Some(std::time::Duration::from_secs(1)));
+    ///
+    /// match dissector.dissect(1, std::time::SystemTime::now(), &[0u8; 60]) {
+    ///     Ok(packet) => println!("Got a packet with {} layers", packet.iter().count()),
+    ///     Err(rtshark::RTSharkError::Io(e)) if e.kind() == std::io::ErrorKind::TimedOut => {
+    ///         eprintln!("TShark is too slow")
+    ///     }
+    ///     Err(e) => eprintln!("Error dissecting frame: {e}"),
+    /// }
+    /// ```
+    #[cfg(target_family = "unix")]
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// Read the next packet printed by TShark, failing with a TimedOut error after the deadline.
+    #[cfg(target_family = "unix")]
+    fn read_until(&mut self, deadline: Option<Instant>) -> Result<Option<Packet>> {
+        let Some(deadline) = deadline else {
+            return self.rtshark.read();
+        };
+
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        match self.rtshark.read_timeout(timeout)? {
+            ReadOutcome::Packet(packet) => Ok(Some(packet)),
+            ReadOutcome::Eof => Ok(None),
+            ReadOutcome::Timeout => Err(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                "TShark did not print the dissection before the timeout",
+            )
+            .into()),
+        }
+    }
+
+    /// Get the warnings printed by TShark so far, see [RTShark::warnings].
+    pub fn warnings(&self) -> Vec<String> {
+        self.rtshark.warnings()
+    }
+}
+
+/// Write the pcapng section header, in little endian.
+fn write_section_header(out: &mut impl Write) -> std::io::Result<()> {
+    let length = 28u32;
+    out.write_all(&SECTION_HEADER_BLOCK.to_le_bytes())?;
+    out.write_all(&length.to_le_bytes())?;
+    // byte-order magic
+    out.write_all(&0x1A2B_3C4Du32.to_le_bytes())?;
+    // version 1.0
+    out.write_all(&1u16.to_le_bytes())?;
+    out.write_all(&0u16.to_le_bytes())?;
+    // unknown section length
+    out.write_all(&(-1i64).to_le_bytes())?;
+    out.write_all(&length.to_le_bytes())
+}
+
+/// Declare a pcapng interface with a link type, and timestamps in microseconds.
+fn write_interface_description(out: &mut impl Write, link_type: u16) -> std::io::Result<()> {
+    let length = 20u32;
+    out.write_all(&INTERFACE_DESCRIPTION_BLOCK.to_le_bytes())?;
+    out.write_all(&length.to_le_bytes())?;
+    out.write_all(&link_type.to_le_bytes())?;
+    // reserved
+    out.write_all(&0u16.to_le_bytes())?;
+    // no snapshot length limit
+    out.write_all(&0u32.to_le_bytes())?;
+    out.write_all(&length.to_le_bytes())
+}
+
+/// Write a packet captured on a pcapng interface.
+fn write_enhanced_packet(
+    out: &mut impl Write,
+    interface: u32,
+    micros: u64,
+    data: &[u8],
+) -> std::io::Result<()> {
+    let data_length = u32::try_from(data.len())
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidInput, "packet too large"))?;
+    // data is padded to 32 bits
+    let padding = (4 - data.len() % 4) % 4;
+    let length = 32 + data_length + padding as u32;
+
+    out.write_all(&ENHANCED_PACKET_BLOCK.to_le_bytes())?;
+    out.write_all(&length.to_le_bytes())?;
+    out.write_all(&interface.to_le_bytes())?;
+    out.write_all(&((micros >> 32) as u32).to_le_bytes())?;
+    out.write_all(&(micros as u32).to_le_bytes())?;
+    // captured and original lengths
+    out.write_all(&data_length.to_le_bytes())?;
+    out.write_all(&data_length.to_le_bytes())?;
+    out.write_all(data)?;
+    out.write_all(&[0u8; 3][..padding])?;
+    out.write_all(&length.to_le_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pcapng_blocks() {
+        let mut out = vec![];
+        write_section_header(&mut out).unwrap();
+        assert_eq!(out.len(), 28);
+        assert_eq!(out[..4], [0x0A, 0x0D, 0x0D, 0x0A]);
+        assert_eq!(out[8..12], [0x4D, 0x3C, 0x2B, 0x1A]);
+
+        let mut out = vec![];
+        write_interface_description(&mut out, 101).unwrap();
+        assert_eq!(out.len(), 20);
+        assert_eq!(out[8..10], [101, 0]);
+
+        let mut out = vec![];
+        write_enhanced_packet(&mut out, 1, 0x1_0000_0002, &[0xAA; 5]).unwrap();
+        assert_eq!(out.len(), 40);
+        assert_eq!(out[4..8], [40, 0, 0, 0]);
+        // interface id, then timestamp high and low parts
+        assert_eq!(out[8..20], [1, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0]);
+        assert_eq!(out[28..36], [0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0, 0, 0]);
+        assert_eq!(out[36..], [40, 0, 0, 0]);
+    }
+
+    /// TShark reading the section header, the interface description, then packets of
+    /// 4 bytes (36 bytes blocks), and printing a PDML packet for each of them.
+    /// The first packet is printed after the given delay, in seconds.
+    #[cfg(target_family = "unix")]
+    fn fake_tshark(first_delay: &str) -> String {
+        format!(
+            r#"#!/bin/sh
+head -c 48 > /dev/null
+printf '<pdml>\n'
+n=1
+while [ "$(head -c 36 | wc -c)" -eq 36 ]; do
+    [ $n -eq 1 ] && sleep {first_delay}
+    printf '<packet>\n<proto name="geninfo"><field name="num" show="%d"/></proto>\n</packet>\n' $n
+    n=$((n + 1))
+done
+printf '</pdml>\n'
+"#
+        )
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_dissector() {
+        use crate::tests::{env_path, write_script};
+        use crate::RTSharkBuilder;
+
+        let tmp_dir = tempdir::TempDir::new("test_dissector").unwrap();
+        write_script(tmp_dir.path(), "tshark", &fake_tshark("0"));
+
+        let env_path = env_path(tmp_dir.path());
+        let builder = RTSharkBuilder::builder()
+            .input_path("-")
+            .env_path(&env_path);
+        let mut dissector = builder.spawn_dissector().unwrap();
+
+        for number in 1..=3 {
+            let packet = dissector
+                .dissect(101, SystemTime::now(), &[0x45, 0, 0, 0])
+                .unwrap();
+            assert_eq!(packet.frame_number(), Some(number));
+        }
+
+        assert!(matches!(
+            builder.display_filter("udp").spawn_dissector(),
+            Err(RTSharkError::InvalidArgument { .. })
+        ));
+        assert!(matches!(
+            builder.capture_filter("udp").spawn_dissector(),
+            Err(RTSharkError::InvalidArgument { .. })
+        ));
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_dissector_timeout() {
+        use crate::tests::{env_path, write_script};
+        use crate::RTSharkBuilder;
+        use std::time::{Duration, Instant};
+
+        // TShark dissecting the first packet slowly
+        let tmp_dir = tempdir::TempDir::new("test_dissector_timeout").unwrap();
+        write_script(tmp_dir.path(), "tshark", &fake_tshark("0.5"));
+
+        let env_path = env_path(tmp_dir.path());
+        let builder = RTSharkBuilder::builder()
+            .input_path("-")
+            .env_path(&env_path);
+        let mut dissector = builder.spawn_dissector().unwrap();
+        dissector.set_timeout(Some(Duration::from_millis(100)));
+
+        let start = Instant::now();
+        let result = dissector.dissect(101, SystemTime::now(), &[0x45, 0, 0, 0]);
+        assert!(
+            matches!(result, Err(RTSharkError::Io(e)) if e.kind() == std::io::ErrorKind::TimedOut)
+        );
+        assert!(start.elapsed() >= Duration::from_millis(100));
+
+        // the late dissection of the first packet is skipped
+        dissector.set_timeout(None);
+        let packet = dissector
+            .dissect(101, SystemTime::now(), &[0x45, 0, 0, 0])
+            .unwrap();
+        assert_eq!(packet.frame_number(), Some(2));
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_dissector_frame_number() {
+        use crate::tests::{env_path, write_script};
+        use crate::{OutputFormat, RTSharkBuilder};
+
+        // TShark printing packets without geninfo
+        let tmp_dir = tempdir::TempDir::new("test_dissector_frame_number").unwrap();
+        write_script(
+            tmp_dir.path(),
+            "tshark",
+            r#"#!/bin/sh
+head -c 48 > /dev/null
+printf '<pdml>\n'
+while [ "$(head -c 36 | wc -c)" -eq 36 ]; do
+    printf '<packet>\n<proto name="frame"></proto>\n</packet>\n'
+done
+printf '</pdml>\n'
+"#,
+        );
+
+        let env_path = env_path(tmp_dir.path());
+        let builder = RTSharkBuilder::builder()
+            .input_path("-")
+            .env_path(&env_path);
+        let mut dissector = builder.spawn_dissector().unwrap();
+        assert!(matches!(
+            dissector.dissect(101, SystemTime::now(), &[0x45, 0, 0, 0]),
+            Err(RTSharkError::InvalidData { .. })
+        ));
+
+        // frame numbers of JSON packets are kept by the whitelist
+        let builder = builder
+            .output_format(OutputFormat::Json)
+            .metadata_whitelist("ip.src");
+        assert_eq!(
+            builder.with_frame_number().metadata_whitelist.unwrap(),
+            ["ip.src", "frame.number"]
+        );
+        let builder = builder.metadata_whitelist("frame.number");
+        assert_eq!(
+            builder.with_frame_number().metadata_whitelist.unwrap(),
+            ["ip.src", "frame.number"]
+        );
+    }
+
+    #[test]
+    fn test_dissector_tshark() {
+        use crate::RTSharkBuilder;
+
+        let builder = RTSharkBuilder::builder().input_path("-");
+        let mut dissector = builder.spawn_dissector().unwrap();
+
+        // each packet is dissected before the next one is written
+        let udp_packet = [
+            // IPv4 header, 127.0.0.1 -> 127.0.0.1, UDP
+            0x45, 0x00, 0x00, 0x1d, 0x00, 0x00, 0x00, 0x00, 0x40, 0x11, 0x7c, 0xce, 0x7f, 0x00,
+            0x00, 0x01, 0x7f, 0x00, 0x00, 0x01, //
+            // UDP header, port 1234 -> 5678, then 1 byte of payload
+            0x04, 0xd2, 0x16, 0x2e, 0x00, 0x09, 0x00, 0x00, 0x61,
+        ];
+        for number in 1..=2 {
+            let packet = dissector
+                .dissect(101, SystemTime::now(), &udp_packet)
+                .unwrap();
+            assert_eq!(packet.frame_number(), Some(number));
+
+            let udp = packet.layer_name("udp").unwrap();
+            assert_eq!(udp.metadata("udp.dstport").unwrap().value(), "5678");
+        }
+
+        // LINKTYPE_ETHERNET, declared by a new pcapng interface
+        let mut ethernet_frame = vec![0xff; 6];
+        ethernet_frame.extend([0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x08, 0x00]);
+        ethernet_frame.extend(udp_packet);
+        let packet = dissector
+            .dissect(1, SystemTime::now(), &ethernet_frame)
+            .unwrap();
+        assert_eq!(packet.frame_number(), Some(3));
+        assert!(packet.layer_name("eth").is_some());
+        assert!(packet.layer_name("udp").is_some());
+    }
+}
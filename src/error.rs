@@ -0,0 +1,295 @@
+//! Errors returned by rtshark.
+
+use crate::diagnostic::{Diagnostic, DiagnosticKind};
+use crate::value::ValueError;
+use std::process::ExitStatus;
+
+/// Result type of rtshark APIs.
+pub type Result<T> = std::result::Result<T, RTSharkError>;
+
+/// Error returned by rtshark, to know the cause of a failure without parsing error messages.
+///
+/// It can be converted to a [std::io::Error] with an appropriate [std::io::ErrorKind].
+///
+/// # Example
+///
+/// ```
+/// let builder = rtshark::RTSharkBuilder::builder()
+///     .input_path("/tmp/my.pcap")
+///     .display_filter("ip.srcc == 10.0.0.1");
+///
+/// match builder.spawn().and_then(|mut rtshark| rtshark.read()) {
+///     Err(rtshark::RTSharkError::TsharkNotFound(e)) => eprintln!("Please install TShark: {e}"),
+///     Err(rtshark::RTSharkError::InputNotFound { path }) => eprintln!("No such file: {path}"),
+///     Err(rtshark::RTSharkError::InvalidDisplayFilter { message }) => eprintln!("Fix the filter: {message}"),
+///     Err(e) => eprintln!("Error running tshark: {e}"),
+///     Ok(_) => (),
+/// }
+/// ```
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum RTSharkError {
+    /// TShark executable (or another Wireshark tool like editcap) cannot be found in PATH.
+    TsharkNotFound(std::io::Error),
+    /// Input file cannot be found.
+    InputNotFound {
+        /// Path of the input file
+        path: String,
+    },
+    /// Display filter rejected by TShark.
+    InvalidDisplayFilter {
+        /// TShark error message
+        message: String,
+    },
+    /// Capture filter rejected by TShark.
+    InvalidCaptureFilter {
+        /// TShark error message
+        message: String,
+    },
+    /// TShark is not allowed to capture on an interface or to read a file.
+    PermissionDenied {
+        /// TShark error message
+        message: String,
+    },
+    /// Invalid builder parameters (example: spawn_fields() without metadata whitelist).
+    InvalidArgument {
+        /// Error message
+        message: String,
+    },
+    /// TShark XML output (PDML or PSML) cannot be parsed.
+    XmlParse {
+        /// Position of the error in TShark output, in bytes
+        offset: u64,
+        /// Parser error message
+        message: String,
+    },
+    /// Packet skipped because it cannot be parsed, with XML error recovery
+    /// (see [crate::RTSharkBuilderReady::recover_xml_errors]). Next packets can still be read.
+    SkippedPacket(SkippedPacket),
+    /// Unexpected content in TShark output (example: invalid timestamp or non utf8 value).
+    InvalidData {
+        /// Error message
+        message: String,
+    },
+    /// TShark exited with an error which is not described by another variant.
+    TsharkExited {
+        /// TShark exit status, if known
+        status: Option<ExitStatus>,
+        /// Lines printed by TShark on stderr
+        stderr: String,
+    },
+    /// Metadata value cannot be converted to the requested type.
+    Value(ValueError),
+    /// I/O error while communicating with TShark.
+    Io(std::io::Error),
+}
+
+impl RTSharkError {
+    /// Check if no more packets can be read after this error.
+    ///
+    /// Invalid data in one packet (like a non utf8 value) is not fatal: next packets can still be read.
+    /// Other errors (TShark failure, I/O error, XML syntax error ...) are fatal.
+    pub fn is_fatal(&self) -> bool {
+        !matches!(
+            self,
+            RTSharkError::SkippedPacket(_)
+                | RTSharkError::InvalidData { .. }
+                | RTSharkError::Value(_)
+        )
+    }
+
+    /// Build an [RTSharkError::InvalidData] error.
+    pub(crate) fn invalid_data(message: impl Into<String>) -> Self {
+        RTSharkError::InvalidData {
+            message: message.into(),
+        }
+    }
+
+    /// Build an [RTSharkError::SkippedPacket] error.
+    pub(crate) fn skipped_packet(reason: impl Into<String>, byte_offset: u64) -> Self {
+        RTSharkError::SkippedPacket(SkippedPacket {
+            reason: reason.into(),
+            byte_offset,
+        })
+    }
+
+    /// Build an [RTSharkError::InvalidArgument] error.
+    pub(crate) fn invalid_argument(message: impl Into<String>) -> Self {
+        RTSharkError::InvalidArgument {
+            message: message.into(),
+        }
+    }
+
+    /// Build an error from the fatal message printed by TShark on stderr, and all stderr lines.
+    pub(crate) fn from_stderr(
+        status: Option<ExitStatus>,
+        message: &str,
+        stderr: &[String],
+    ) -> Self {
+        let diagnostic = Diagnostic::parse(message);
+        let message = diagnostic.message().to_owned();
+
+        if let Some(path) = missing_file(&message) {
+            return RTSharkError::InputNotFound { path };
+        }
+
+        match diagnostic.kind() {
+            DiagnosticKind::CaptureFilter => RTSharkError::InvalidCaptureFilter { message },
+            DiagnosticKind::DisplayFilter => RTSharkError::InvalidDisplayFilter { message },
+            DiagnosticKind::PermissionDenied => RTSharkError::PermissionDenied { message },
+            _ => RTSharkError::TsharkExited {
+                status,
+                stderr: stderr.join("\n"),
+            },
+        }
+    }
+}
+
+/// A packet skipped by XML error recovery, see [RTSharkError::SkippedPacket].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SkippedPacket {
+    reason: String,
+    byte_offset: u64,
+}
+
+impl SkippedPacket {
+    /// Why the packet cannot be parsed.
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+
+    /// Position of the packet in TShark output, in bytes.
+    pub fn byte_offset(&self) -> u64 {
+        self.byte_offset
+    }
+}
+
+/// Get the path in TShark message: The file "/tmp/my.pcap" doesn't exist.
+fn missing_file(message: &str) -> Option<String> {
+    let (_, rest) = message.split_once("The file \"")?;
+    let (path, rest) = rest.split_once('"')?;
+    rest.contains("doesn't exist").then(|| path.to_owned())
+}
+
+impl std::fmt::Display for RTSharkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RTSharkError::TsharkNotFound(e) => write!(f, "Unable to find tshark: {e}"),
+            RTSharkError::InputNotFound { path } => write!(f, "Unable to find {path}"),
+            RTSharkError::InvalidDisplayFilter { message } => {
+                write!(f, "Invalid display filter: {message}")
+            }
+            RTSharkError::InvalidCaptureFilter { message } => {
+                write!(f, "Invalid capture filter: {message}")
+            }
+            RTSharkError::PermissionDenied { message } => write!(f, "Permission denied: {message}"),
+            RTSharkError::InvalidArgument { message } => write!(f, "{message}"),
+            RTSharkError::XmlParse { offset, message } => write!(
+                f,
+                "xml parsing error: {message} at tshark output offset {offset}"
+            ),
+            RTSharkError::SkippedPacket(skipped) => write!(
+                f,
+                "packet skipped at tshark output offset {}: {}",
+                skipped.byte_offset, skipped.reason
+            ),
+            RTSharkError::InvalidData { message } => write!(f, "{message}"),
+            RTSharkError::TsharkExited { status, stderr } => match status {
+                Some(status) => write!(f, "TShark failed ({status}): {stderr}"),
+                None => write!(f, "TShark failed: {stderr}"),
+            },
+            RTSharkError::Value(e) => write!(f, "{e}"),
+            RTSharkError::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for RTSharkError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RTSharkError::TsharkNotFound(e) | RTSharkError::Io(e) => Some(e),
+            RTSharkError::Value(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for RTSharkError {
+    fn from(e: std::io::Error) -> Self {
+        RTSharkError::Io(e)
+    }
+}
+
+impl From<ValueError> for RTSharkError {
+    fn from(e: ValueError) -> Self {
+        RTSharkError::Value(e)
+    }
+}
+
+impl From<RTSharkError> for std::io::Error {
+    fn from(e: RTSharkError) -> Self {
+        let kind = match e {
+            RTSharkError::Io(e) => return e,
+            RTSharkError::TsharkNotFound(_) | RTSharkError::InputNotFound { .. } => {
+                std::io::ErrorKind::NotFound
+            }
+            RTSharkError::PermissionDenied { .. } => std::io::ErrorKind::PermissionDenied,
+            RTSharkError::InvalidDisplayFilter { .. }
+            | RTSharkError::InvalidCaptureFilter { .. }
+            | RTSharkError::InvalidArgument { .. }
+            | RTSharkError::TsharkExited { .. } => std::io::ErrorKind::InvalidInput,
+            RTSharkError::XmlParse { .. }
+            | RTSharkError::SkippedPacket(_)
+            | RTSharkError::InvalidData { .. }
+            | RTSharkError::Value(_) => std::io::ErrorKind::InvalidData,
+        };
+        std::io::Error::new(kind, e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_stderr() {
+        let err = RTSharkError::from_stderr(
+            None,
+            "tshark: \"ip.srcc\" is neither a field nor a protocol name.\n",
+            &[],
+        );
+        assert!(matches!(err, RTSharkError::InvalidDisplayFilter { message }
+            if message == "tshark: \"ip.srcc\" is neither a field nor a protocol name."));
+
+        let err = RTSharkError::from_stderr(
+            None,
+            "tshark: Invalid capture filter \"tcp port\" for interface 'lo'.",
+            &[],
+        );
+        assert!(matches!(err, RTSharkError::InvalidCaptureFilter { .. }));
+
+        let err = RTSharkError::from_stderr(
+            None,
+            "tshark: You don't have permission to capture on that device.",
+            &[],
+        );
+        assert!(matches!(err, RTSharkError::PermissionDenied { .. }));
+
+        let err =
+            RTSharkError::from_stderr(None, "tshark: The file \"/tmp/a.pcap\" doesn't exist.", &[]);
+        assert!(matches!(err, RTSharkError::InputNotFound { path } if path == "/tmp/a.pcap"));
+
+        let stderr = vec!["Capturing on 'lo'".to_owned(), "tshark: fatal".to_owned()];
+        let err = RTSharkError::from_stderr(None, "tshark: fatal", &stderr);
+        assert!(
+            matches!(&err, RTSharkError::TsharkExited { status: None, stderr }
+            if stderr == "Capturing on 'lo'\ntshark: fatal")
+        );
+
+        assert!(err.is_fatal());
+        assert!(!RTSharkError::invalid_data("invalid timestamp").is_fatal());
+
+        let err = std::io::Error::from(err);
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+}
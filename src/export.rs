@@ -0,0 +1,410 @@
+//! Objects extracted by TShark "--export-objects" option, see [crate::RTSharkBuilderReady::export_objects].
+
+use crate::fields::AGGREGATOR;
+use crate::Result;
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+
+/// Protocol of exported objects.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportProtocol {
+    /// HTTP bodies
+    Http,
+    /// Files transferred with SMB
+    Smb,
+    /// Emails (Internet Message Format)
+    Imf,
+    /// Files transferred with TFTP
+    Tftp,
+    /// DICOM files
+    Dicom,
+}
+
+impl ExportProtocol {
+    /// Name of the protocol in TShark "--export-objects" option.
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            ExportProtocol::Http => "http",
+            ExportProtocol::Smb => "smb",
+            ExportProtocol::Imf => "imf",
+            ExportProtocol::Tftp => "tftp",
+            ExportProtocol::Dicom => "dicom",
+        }
+    }
+}
+
+/// A file written by TShark while exporting objects.
+///
+/// Frame number, hostname and content type are only found for HTTP objects, on a best-effort basis:
+/// they are guessed from the HTTP responses whose URI ends with the file name, taken in frame order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExportedObject {
+    frame_number: Option<u64>,
+    hostname: Option<String>,
+    content_type: Option<String>,
+    filename: OsString,
+    path: PathBuf,
+    size: u64,
+}
+
+impl ExportedObject {
+    /// Number of the frame containing the end of the object (HTTP only, best-effort).
+    pub fn frame_number(&self) -> Option<u64> {
+        self.frame_number
+    }
+
+    /// Host serving the object (HTTP only, best-effort).
+    pub fn hostname(&self) -> Option<&str> {
+        self.hostname.as_deref()
+    }
+
+    /// Content type of the object (example: "text/html"), for HTTP only, best-effort.
+    pub fn content_type(&self) -> Option<&str> {
+        self.content_type.as_deref()
+    }
+
+    /// Name of the written file, chosen by TShark. A "(N)" suffix is added if a file with the
+    /// same name already existed in the directory.
+    pub fn filename(&self) -> &OsStr {
+        &self.filename
+    }
+
+    /// Path of the written file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Size of the written file, in bytes.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+/// HTTP response found in TShark fields output, to describe exported objects.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct HttpResponse {
+    frame_number: u64,
+    uri: String,
+    content_type: Option<String>,
+}
+
+/// TShark fields printed while exporting HTTP objects, to describe them.
+pub(crate) const HTTP_FIELDS: [&str; 3] =
+    ["frame.number", "http.response_for.uri", "http.content_type"];
+
+/// Private directory created in the destination directory, where TShark exports the objects of
+/// one run. It is removed with its remaining content when dropped.
+pub(crate) struct ExportDir {
+    path: PathBuf,
+}
+
+impl ExportDir {
+    /// Create a new directory, not used by another export.
+    pub(crate) fn create(dir: &Path) -> Result<Self> {
+        let mut attempt = 0;
+        loop {
+            let path = dir.join(format!(".rtshark-export-{}-{attempt}", std::process::id()));
+            match std::fs::create_dir(&path) {
+                Ok(()) => return Ok(ExportDir { path }),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => attempt += 1,
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// List the files written by TShark.
+    fn files(&self) -> Result<Vec<OsString>> {
+        let files = std::fs::read_dir(&self.path)?
+            .map(|entry| entry.map(|e| e.file_name()))
+            .collect::<std::io::Result<_>>()?;
+        Ok(files)
+    }
+}
+
+impl Drop for ExportDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Move an exported file to the destination directory, without replacing an existing file:
+/// like TShark, a "(N)" suffix is then added to its name.
+fn move_file(from: &Path, dir: &Path, filename: &OsStr) -> Result<PathBuf> {
+    let mut to = dir.join(filename);
+
+    for n in 1.. {
+        // a hard link fails if the destination exists, unlike a rename
+        match std::fs::hard_link(from, &to) {
+            Ok(()) => {
+                std::fs::remove_file(from)?;
+                return Ok(to);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => (),
+            // hard links are not supported by this file system
+            Err(_) if !to.exists() => {
+                std::fs::rename(from, &to)?;
+                return Ok(to);
+            }
+            Err(e) => return Err(e.into()),
+        }
+
+        let name = Path::new(filename);
+        let mut numbered = name.file_stem().unwrap_or(filename).to_os_string();
+        numbered.push(format!("({n})"));
+        if let Some(extension) = name.extension() {
+            numbered.push(".");
+            numbered.push(extension);
+        }
+        to = dir.join(numbered);
+    }
+
+    unreachable!("no free file name")
+}
+
+/// Parse HTTP responses from fields output (frame.number, http.response_for.uri, http.content_type),
+/// with occurrences separated by [AGGREGATOR].
+pub(crate) fn parse_http_responses(output: &str) -> Vec<HttpResponse> {
+    let mut responses = vec![];
+
+    for line in output.lines() {
+        let mut columns = line.split('\t');
+        let (Some(frame), Some(uris), content_types) =
+            (columns.next(), columns.next(), columns.next())
+        else {
+            continue;
+        };
+        let Ok(frame_number) = frame.trim().parse() else {
+            continue;
+        };
+
+        // several responses may be in the same frame
+        let mut content_types = content_types.unwrap_or("").split(AGGREGATOR);
+        for uri in uris.split(AGGREGATOR).filter(|u| !u.is_empty()) {
+            responses.push(HttpResponse {
+                frame_number,
+                uri: uri.to_owned(),
+                content_type: content_types
+                    .next()
+                    .filter(|c| !c.is_empty())
+                    .map(str::to_owned),
+            });
+        }
+    }
+
+    responses
+}
+
+/// Replace characters that TShark does not keep in exported file names.
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '\\' | '/' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c => c,
+        })
+        .collect()
+}
+
+/// Remove the "(N)" suffix added by TShark to a file name to avoid duplicates ("index(1).html" becomes "index.html").
+fn strip_duplicate_suffix(filename: &str) -> String {
+    let (stem, extension) = match filename.rfind('.') {
+        Some(dot) if dot > 0 => filename.split_at(dot),
+        _ => (filename, ""),
+    };
+
+    match stem.strip_suffix(')').and_then(|s| s.rsplit_once('(')) {
+        Some((base, n)) if !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()) => {
+            format!("{}{}", base, extension)
+        }
+        _ => filename.to_owned(),
+    }
+}
+
+/// Duplicate suffix of a file name, to sort files in the order they were written.
+fn duplicate_index(filename: &str) -> u64 {
+    let stem = filename.rsplit_once('.').map_or(filename, |(s, _)| s);
+    stem.strip_suffix(')')
+        .and_then(|s| s.rsplit_once('('))
+        .and_then(|(_, n)| n.parse().ok())
+        .unwrap_or(0)
+}
+
+/// Get the host of an absolute URI ("http://example.com:8080/a" gives "example.com:8080").
+fn uri_host(uri: &str) -> Option<&str> {
+    let (_, rest) = uri.split_once("://")?;
+    let host = rest.split(['/', '?', '#']).next()?;
+    (!host.is_empty()).then_some(host)
+}
+
+/// Get the name given by TShark to an object exported from an URI: its last path component.
+fn uri_filename(uri: &str) -> String {
+    let path = match uri.split_once("://") {
+        Some((_, rest)) => rest.find('/').map_or("", |i| &rest[i..]),
+        None => uri,
+    };
+    sanitize(path.rsplit('/').next().unwrap_or(""))
+}
+
+/// Move the files written by TShark to the destination directory, and list them.
+/// HTTP responses are matched to files by name, in frame order.
+pub(crate) fn exported_objects(
+    export_dir: &ExportDir,
+    dir: &Path,
+    responses: &[HttpResponse],
+) -> Result<Vec<ExportedObject>> {
+    let mut files = export_dir.files()?;
+    // "name", "name(1)", "name(2)" are written in this order
+    files.sort_by_cached_key(|f| {
+        let name = f.to_string_lossy();
+        (strip_duplicate_suffix(&name), duplicate_index(&name))
+    });
+
+    let mut used = vec![false; responses.len()];
+    let mut objects = vec![];
+
+    for exported in &files {
+        let from = export_dir.path().join(exported);
+        let size = std::fs::metadata(&from)?.len();
+
+        // names which are not UTF-8 cannot come from an URI
+        let base = exported.to_str().map(strip_duplicate_suffix);
+        let response = responses
+            .iter()
+            .enumerate()
+            .find(|(i, r)| !used[*i] && base.as_ref() == Some(&uri_filename(&r.uri)));
+
+        // files numbered by TShark are numbered again in the destination directory
+        let name = match &base {
+            Some(base) if exported != base.as_str() && files.iter().any(|f| f == base.as_str()) => {
+                OsStr::new(base)
+            }
+            _ => exported,
+        };
+        let path = move_file(&from, dir, name)?;
+        let mut object = ExportedObject {
+            frame_number: None,
+            hostname: None,
+            content_type: None,
+            filename: path.file_name().unwrap_or_default().to_os_string(),
+            path,
+            size,
+        };
+
+        if let Some((i, response)) = response {
+            used[i] = true;
+            object.frame_number = Some(response.frame_number);
+            object.hostname = uri_host(&response.uri).map(str::to_owned);
+            object.content_type.clone_from(&response.content_type);
+        }
+
+        objects.push(object);
+    }
+
+    objects.sort_by_key(|o| o.frame_number.unwrap_or(u64::MAX));
+
+    Ok(objects)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_file_names() {
+        assert_eq!(strip_duplicate_suffix("index(1).html"), "index.html");
+        assert_eq!(strip_duplicate_suffix("index(12)"), "index");
+        assert_eq!(strip_duplicate_suffix("index().html"), "index().html");
+        assert_eq!(strip_duplicate_suffix("index.html"), "index.html");
+        assert_eq!(duplicate_index("index(12).html"), 12);
+        assert_eq!(duplicate_index("index.html"), 0);
+
+        assert_eq!(uri_filename("http://example.com/a/b.png"), "b.png");
+        assert_eq!(uri_filename("http://example.com/q?x=1"), "q_x=1");
+        assert_eq!(uri_filename("/style.css"), "style.css");
+        assert_eq!(
+            uri_host("http://example.com:8080/a"),
+            Some("example.com:8080")
+        );
+        assert_eq!(uri_host("/a"), None);
+    }
+
+    #[test]
+    fn test_exported_objects() {
+        let output = "1\t\t\n\
+                      2\thttp://example.com/index.html\ttext/html\n\
+                      4\thttp://example.com/index.html\u{1f}http://example.com/a,b/logo.png\ttext/html; charset=a,b\u{1f}image/png\n";
+        let responses = parse_http_responses(output);
+        assert_eq!(responses.len(), 3);
+        assert_eq!(responses[1].uri, "http://example.com/index.html");
+        assert_eq!(
+            responses[1].content_type.as_deref(),
+            Some("text/html; charset=a,b")
+        );
+        assert_eq!(responses[2].uri, "http://example.com/a,b/logo.png");
+
+        let tmp_dir = tempdir::TempDir::new("test_export").unwrap();
+        // an existing file is not replaced
+        std::fs::write(tmp_dir.path().join("logo.png"), "old").unwrap();
+
+        let export_dir = ExportDir::create(tmp_dir.path()).unwrap();
+        for (name, content) in [
+            ("index(1).html", "second"),
+            ("index.html", "first"),
+            ("logo.png", "png"),
+            ("unknown", ""),
+        ] {
+            let mut file = std::fs::File::create(export_dir.path().join(name)).unwrap();
+            file.write_all(content.as_bytes()).unwrap();
+        }
+
+        let objects = exported_objects(&export_dir, tmp_dir.path(), &responses).unwrap();
+        assert_eq!(objects.len(), 4);
+
+        assert_eq!(objects[0].filename(), "index.html");
+        assert_eq!(objects[0].frame_number(), Some(2));
+        assert_eq!(objects[0].hostname(), Some("example.com"));
+        assert_eq!(objects[0].content_type(), Some("text/html"));
+        assert_eq!(objects[0].size(), 5);
+        assert_eq!(objects[0].path(), tmp_dir.path().join("index.html"));
+
+        assert_eq!(objects[1].filename(), "index(1).html");
+        assert_eq!(objects[1].frame_number(), Some(4));
+        assert_eq!(objects[2].filename(), "logo(1).png");
+        assert_eq!(objects[2].content_type(), Some("image/png"));
+        assert_eq!(std::fs::read(objects[2].path()).unwrap(), b"png");
+        assert_eq!(
+            std::fs::read(tmp_dir.path().join("logo.png")).unwrap(),
+            b"old"
+        );
+
+        assert_eq!(objects[3].filename(), "unknown");
+        assert_eq!(objects[3].frame_number(), None);
+        assert_eq!(objects[3].hostname(), None);
+    }
+
+    // file names must be valid UTF-8 on macOS
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_exported_objects_not_utf8() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let tmp_dir = tempdir::TempDir::new("test_export").unwrap();
+        let export_dir = ExportDir::create(tmp_dir.path()).unwrap();
+        let name = OsStr::from_bytes(b"caf\xe9.txt");
+        std::fs::write(export_dir.path().join(name), "menu").unwrap();
+
+        let objects = exported_objects(&export_dir, tmp_dir.path(), &[]).unwrap();
+        assert_eq!(objects.len(), 1);
+        assert_eq!(objects[0].filename(), name);
+        assert_eq!(objects[0].path(), tmp_dir.path().join(name));
+
+        // the export directory is removed
+        let path = export_dir.path().to_owned();
+        drop(export_dir);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,393 @@
+//! Reader for TShark tabular output (-T fields), see [crate::RTSharkBuilderReady::spawn_fields].
+
+use crate::input::InputError;
+use crate::reader::ReaderCore;
+use crate::stderr::StderrReader;
+use crate::{Metadata, RTSharkError, RTSharkHandle, Result, TerminationReport};
+use std::io::{BufRead, BufReader};
+use std::process::{Child, ChildStdout};
+use std::sync::Arc;
+
+/// Character used by TShark to separate occurrences of a field in a column.
+/// The "unit separator" control character is not expected in displayed values.
+pub(crate) const AGGREGATOR: char = '\u{1f}';
+
+/// TShark option to use [AGGREGATOR] between occurrences.
+pub(crate) const AGGREGATOR_OPTION: &str = "aggregator=\u{1f}";
+
+/// One line of TShark tabular output: the values of the whitelisted metadata for one packet.
+///
+/// Columns are in the order given to metadata_whitelist(). Each column holds all occurrences
+/// of the metadata in the packet, in packet order, and is empty when the metadata is absent.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldsRow {
+    /// metadata names, shared by all rows
+    columns: Arc<[String]>,
+    /// occurrences of each metadata
+    values: Vec<Vec<String>>,
+}
+
+impl FieldsRow {
+    /// Get the metadata names of this row, in column order.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let builder = rtshark::RTSharkBuilder::builder().input_path("/tmp/my.pcap").metadata_whitelist("ip.src");
+    /// # let Ok(mut reader) = builder.spawn_fields() else { return };
+    /// while let Ok(Some(row)) = reader.read() {
+    ///     assert_eq!(row.columns(), ["ip.src"]);
+    /// }
+    /// ```
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// Get all occurrences of a metadata in this row, or None if this metadata is not a column.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let builder = rtshark::RTSharkBuilder::builder().input_path("/tmp/my.pcap").metadata_whitelist("ip.src");
+    /// # let Ok(mut reader) = builder.spawn_fields() else { return };
+    /// while let Ok(Some(row)) = reader.read() {
+    ///     // ip in ip tunnels give several ip.src values
+    ///     for src in row.values("ip.src").unwrap() {
+    ///         println!("ip.src: {src}");
+    ///     }
+    /// }
+    /// ```
+    pub fn values(&self, name: &str) -> Option<&[String]> {
+        let index = self.columns.iter().position(|c| c == name)?;
+        self.get(index)
+    }
+
+    /// Get the first occurrence of a metadata in this row, if any.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let builder = rtshark::RTSharkBuilder::builder().input_path("/tmp/my.pcap").metadata_whitelist("ip.src");
+    /// # let Ok(mut reader) = builder.spawn_fields() else { return };
+    /// while let Ok(Some(row)) = reader.read() {
+    ///     if let Some(src) = row.value("ip.src") {
+    ///         println!("ip.src: {src}");
+    ///     }
+    /// }
+    /// ```
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.values(name)?.first().map(String::as_str)
+    }
+
+    /// Get all occurrences of the metadata at a column index.
+    pub fn get(&self, index: usize) -> Option<&[String]> {
+        self.values.get(index).map(Vec::as_slice)
+    }
+
+    /// Get all occurrences of a metadata as [Metadata] objects, to use typed accessors.
+    /// Only name and value are set on returned metadata.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let builder = rtshark::RTSharkBuilder::builder().input_path("/tmp/my.pcap").metadata_whitelist("tcp.dstport");
+    /// # let Ok(mut reader) = builder.spawn_fields() else { return };
+    /// while let Ok(Some(row)) = reader.read() {
+    ///     for port in row.metadata("tcp.dstport") {
+    ///         let port: u64 = port.as_u64().unwrap();
+    ///     }
+    /// }
+    /// ```
+    pub fn metadata(&self, name: &str) -> Vec<Metadata> {
+        self.values(name)
+            .unwrap_or_default()
+            .iter()
+            .map(|v| Metadata::new(name.to_owned(), v.clone(), None, None, None))
+            .collect()
+    }
+
+    /// Iterate over columns, as (metadata name, occurrences) pairs.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &[String])> {
+        self.columns
+            .iter()
+            .map(String::as_str)
+            .zip(self.values.iter().map(Vec::as_slice))
+    }
+}
+
+/// FieldsReader structure represents a TShark process started with tabular output (-T fields).
+/// It is created by [crate::RTSharkBuilderReady::spawn_fields].
+pub struct FieldsReader {
+    /// TShark process and stderr
+    core: ReaderCore,
+    /// TShark piped output
+    stdout: BufReader<ChildStdout>,
+    /// metadata names, in column order
+    columns: Arc<[String]>,
+    /// line buffer, reused between reads
+    line: String,
+}
+
+impl FieldsReader {
+    /// create a new FieldsReader instance from a successful builder call.
+    pub(crate) fn new(
+        process: Child,
+        stdout: BufReader<ChildStdout>,
+        stderr: StderrReader,
+        columns: Vec<String>,
+        graceful_drop: Option<std::time::Duration>,
+        input_error: Option<InputError>,
+    ) -> Self {
+        FieldsReader {
+            core: ReaderCore::new(process, stderr, graceful_drop, input_error),
+            stdout,
+            columns: columns.into(),
+            line: String::new(),
+        }
+    }
+
+    /// Get the metadata names, in column order.
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// Read the values of the next packet.
+    /// Reading rows can be done until 'None' is returned, like with [crate::RTShark::read].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let builder = rtshark::RTSharkBuilder::builder().input_path("/tmp/my.pcap").metadata_whitelist("ip.src");
+    /// let mut reader = match builder.spawn_fields() {
+    ///     Err(err) => { eprintln!("Error running tshark: {err}"); return; }
+    ///     Ok(reader) => reader,
+    /// };
+    ///
+    /// loop {
+    ///     match reader.read() {
+    ///         Ok(Some(row)) => println!("{:?}", row.values("ip.src")),
+    ///         Ok(None) => break,
+    ///         Err(e) => { eprintln!("Got decoding error: {e}"); break; }
+    ///     }
+    /// }
+    /// ```
+    pub fn read(&mut self) -> Result<Option<FieldsRow>> {
+        self.line.clear();
+        let size = self.stdout.read_line(&mut self.line)?;
+
+        if size == 0 {
+            // Got EOF
+            self.core.check_eof()?;
+            return Ok(None);
+        }
+
+        let values = parse_line(&self.line, self.columns.len())?;
+        self.core.delivered();
+
+        Ok(Some(FieldsRow {
+            columns: self.columns.clone(),
+            values,
+        }))
+    }
+
+    /// Wait for TShark termination and get how it terminated, like [crate::RTShark::wait].
+    /// The number of rows read is reported as the number of packets delivered.
+    pub fn wait(&mut self) -> Result<TerminationReport> {
+        self.core.wait(&mut self.stdout)
+    }
+
+    /// Get the non-fatal messages printed by TShark on stderr so far, like [crate::RTShark::warnings].
+    pub fn warnings(&self) -> Vec<String> {
+        self.core.warnings()
+    }
+
+    /// Kill the running TShark process associated to this reader.
+    pub fn kill(&mut self) {
+        self.core.kill()
+    }
+
+    /// Ask TShark to stop gracefully, and kill it if it is still running after the timeout,
+    /// like [crate::RTShark::stop].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let builder = rtshark::RTSharkBuilder::builder().input_path("/tmp/my.pcap").metadata_whitelist("ip.src").spawn_fields();
+    /// # let Ok(mut reader) = builder else { return };
+    /// reader.stop(std::time::Duration::from_secs(5)).unwrap();
+    ///
+    /// // read remaining rows
+    /// while let Ok(Some(_)) = reader.read() {}
+    /// ```
+    pub fn stop(&mut self, timeout: std::time::Duration) -> Result<()> {
+        self.core.stop(timeout)
+    }
+
+    /// Get a handle to kill or stop TShark from another thread, like [crate::RTShark::handle].
+    pub fn handle(&self) -> RTSharkHandle {
+        self.core.handle()
+    }
+
+    /// Returns tshark process id if tshark is running.
+    pub fn pid(&self) -> Option<u32> {
+        self.core.pid()
+    }
+}
+
+impl Drop for FieldsReader {
+    fn drop(&mut self) {
+        self.core.terminate(&mut self.stdout);
+    }
+}
+
+/// Split a line of tabular output in columns of occurrences.
+fn parse_line(line: &str, columns: usize) -> Result<Vec<Vec<String>>> {
+    let line = line.trim_end_matches(['\n', '\r']);
+
+    let values: Vec<Vec<String>> = line.split('\t').map(parse_column).collect();
+
+    if values.len() != columns {
+        return Err(RTSharkError::invalid_data(format!(
+            "Expected {} columns in TShark fields output, got {}: {}",
+            columns,
+            values.len(),
+            line
+        )));
+    }
+
+    Ok(values)
+}
+
+/// Split a column in occurrences, removing TShark quotes and escapes.
+fn parse_column(column: &str) -> Vec<String> {
+    let column = column
+        .strip_prefix('"')
+        .and_then(|c| c.strip_suffix('"'))
+        .unwrap_or(column);
+
+    if column.is_empty() {
+        return vec![];
+    }
+
+    column.split(AGGREGATOR).map(unescape).collect()
+}
+
+/// Revert TShark escaping of quotes ("") and control characters (\t, \n, \r, \\).
+fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('"', Some('"')) => {
+                chars.next();
+                out.push('"');
+            }
+            ('\\', Some(&next)) => {
+                let unescaped = match next {
+                    't' => '\t',
+                    'n' => '\n',
+                    'r' => '\r',
+                    '\\' => '\\',
+                    _ => {
+                        out.push(c);
+                        continue;
+                    }
+                };
+                chars.next();
+                out.push(unescaped);
+            }
+            _ => out.push(c),
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_line() {
+        let line = "\"10.0.0.1\u{1f}192.168.0.1\"\t\"443\"\t\"\"\n";
+        let values = parse_line(line, 3).unwrap();
+
+        assert_eq!(values[0], vec!["10.0.0.1", "192.168.0.1"]);
+        assert_eq!(values[1], vec!["443"]);
+        assert!(values[2].is_empty());
+
+        let row = FieldsRow {
+            columns: vec![
+                "ip.src".to_owned(),
+                "tcp.dstport".to_owned(),
+                "udp.dstport".to_owned(),
+            ]
+            .into(),
+            values,
+        };
+        assert_eq!(row.value("ip.src"), Some("10.0.0.1"));
+        assert_eq!(row.values("udp.dstport"), Some(&[][..]));
+        assert!(row.values("ip.dst").is_none());
+        assert_eq!(row.metadata("tcp.dstport")[0].as_u64().unwrap(), 443);
+        assert_eq!(
+            row.iter().map(|(name, _)| name).collect::<Vec<_>>(),
+            vec!["ip.src", "tcp.dstport", "udp.dstport"]
+        );
+    }
+
+    #[test]
+    fn test_parse_line_escaped() {
+        let line = "\"GET \"\"/\"\" HTTP/1.1\\r\\n\"\t\"a\\\\b\\x\"\r\n";
+        let values = parse_line(line, 2).unwrap();
+
+        assert_eq!(values[0], vec!["GET \"/\" HTTP/1.1\r\n"]);
+        assert_eq!(values[1], vec!["a\\b\\x"]);
+
+        assert!(parse_line(line, 3).is_err());
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_fields_stop() {
+        use std::os::unix::process::CommandExt;
+        use std::process::{Command, Stdio};
+
+        // prints a last row when asked to stop
+        let script = r#"
+            trap 'printf "\"2\"\n"; exit 0' INT
+            printf '"1"\n'
+            while true; do sleep 0.1; done
+        "#;
+        let mut child = Command::new("sh")
+            .args(["-c", script])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        let stderr = StderrReader::new(child.stderr.take().unwrap());
+        let mut reader = FieldsReader::new(
+            child,
+            stdout,
+            stderr,
+            vec!["frame.number".to_owned()],
+            None,
+            None,
+        );
+
+        let row = reader.read().unwrap().unwrap();
+        assert_eq!(row.value("frame.number"), Some("1"));
+
+        reader.stop(std::time::Duration::from_secs(5)).unwrap();
+        let row = reader.read().unwrap().unwrap();
+        assert_eq!(row.value("frame.number"), Some("2"));
+        assert!(reader.read().unwrap().is_none());
+
+        let report = reader.wait().unwrap();
+        assert!(report.success());
+        assert_eq!(report.packets_delivered(), 2);
+        assert!(!reader.handle().is_running());
+    }
+}
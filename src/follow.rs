@@ -0,0 +1,423 @@
+//! Reassembled streams from TShark "-z follow" statistics, see [crate::RTSharkBuilderReady::follow_stream].
+
+use crate::value::parse_hex;
+use crate::{parse_timestamp_micros, RTSharkError, Result};
+
+/// Protocol of a followed stream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FollowProtocol {
+    /// TCP payload, by tcp.stream index
+    Tcp,
+    /// UDP payload, by udp.stream index
+    Udp,
+    /// Decrypted TLS payload, by tcp.stream index (requires TLS keys)
+    Tls,
+    /// HTTP payload, with decompressed and dechunked bodies, by tcp.stream index
+    Http,
+}
+
+impl FollowProtocol {
+    /// Name of the protocol in TShark "-z follow" option.
+    pub(crate) fn tap_name(&self) -> &'static str {
+        match self {
+            FollowProtocol::Tcp => "tcp",
+            FollowProtocol::Udp => "udp",
+            FollowProtocol::Tls => "tls",
+            FollowProtocol::Http => "http",
+        }
+    }
+}
+
+/// Output mode requested to TShark for a followed stream.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FollowMode {
+    /// Hexadecimal output: only the payload and its direction are available.
+    #[default]
+    Raw,
+    /// YAML output (TShark 3.6 and above): the frame number and timestamp of each chunk are also available.
+    Yaml,
+}
+
+impl FollowMode {
+    /// Name of the mode in TShark "-z follow" option.
+    pub(crate) fn tap_name(&self) -> &'static str {
+        match self {
+            FollowMode::Raw => "raw",
+            FollowMode::Yaml => "yaml",
+        }
+    }
+}
+
+/// Direction of a chunk of a followed stream.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreamDirection {
+    /// Sent by the node which initiated the stream (TShark node 0)
+    ClientToServer,
+    /// Sent by the other node (TShark node 1)
+    ServerToClient,
+}
+
+/// Payload sent in one direction of a followed stream, in stream order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StreamChunk {
+    direction: StreamDirection,
+    data: Vec<u8>,
+    frame_number: Option<u64>,
+    timestamp_micros: Option<i64>,
+}
+
+impl StreamChunk {
+    /// Direction of this chunk.
+    pub fn direction(&self) -> StreamDirection {
+        self.direction
+    }
+
+    /// Payload of this chunk.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Number of the frame containing this chunk, with [FollowMode::Yaml] only.
+    pub fn frame_number(&self) -> Option<u64> {
+        self.frame_number
+    }
+
+    /// Capture time of this chunk, in microseconds since epoch, with [FollowMode::Yaml] only.
+    pub fn timestamp_micros(&self) -> Option<i64> {
+        self.timestamp_micros
+    }
+}
+
+/// A stream reassembled by TShark: its two endpoints and the ordered list of payload chunks.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FollowedStream {
+    client: String,
+    server: String,
+    chunks: Vec<StreamChunk>,
+}
+
+impl FollowedStream {
+    /// Address and port of the node which initiated the stream (example: "10.0.0.1:51234").
+    pub fn client(&self) -> &str {
+        &self.client
+    }
+
+    /// Address and port of the other node.
+    pub fn server(&self) -> &str {
+        &self.server
+    }
+
+    /// Payload chunks, in stream order. Consecutive chunks may have the same direction.
+    pub fn chunks(&self) -> &[StreamChunk] {
+        &self.chunks
+    }
+
+    /// Concatenation of all chunks sent in one direction.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rtshark::{FollowMode, FollowProtocol, StreamDirection};
+    ///
+    /// let builder = rtshark::RTSharkBuilder::builder()
+    ///     .input_path("/tmp/my.pcap");
+    ///
+    /// if let Ok(stream) = builder.follow_stream(FollowProtocol::Http, 0, FollowMode::Raw) {
+    ///     let request = stream.data(StreamDirection::ClientToServer);
+    ///     println!("{}", String::from_utf8_lossy(&request));
+    /// }
+    /// ```
+    pub fn data(&self, direction: StreamDirection) -> Vec<u8> {
+        self.chunks
+            .iter()
+            .filter(|c| c.direction == direction)
+            .flat_map(|c| c.data.iter().copied())
+            .collect()
+    }
+}
+
+/// Build an error for an unexpected line of follow output.
+fn invalid_line(line: &str) -> RTSharkError {
+    RTSharkError::invalid_data(format!("Unexpected follow stream line: {}", line.trim()))
+}
+
+/// Parse "-z follow,<proto>,raw,<index>" output.
+pub(crate) fn parse_follow_raw(output: &str) -> Result<FollowedStream> {
+    // ===================================================================
+    // Follow: tcp,raw
+    // Filter: tcp.stream eq 0
+    // Node 0: 10.0.0.1:51234
+    // Node 1: 10.0.0.2:80
+    // 474554202f20485454502f312e310d0a0d0a
+    // 	485454502f312e3120323030204f4b0d0a0d0a
+    // ===================================================================
+    let mut stream = FollowedStream::default();
+
+    for line in output.lines() {
+        if let Some(node) = line.strip_prefix("Node 0: ") {
+            stream.client = node.trim().to_owned();
+            continue;
+        }
+        if let Some(node) = line.strip_prefix("Node 1: ") {
+            stream.server = node.trim().to_owned();
+            continue;
+        }
+
+        // data sent by node 1 is indented
+        let (direction, hex) = match line.strip_prefix('\t') {
+            Some(hex) => (StreamDirection::ServerToClient, hex),
+            None => (StreamDirection::ClientToServer, line),
+        };
+        let hex = hex.trim();
+        if hex.is_empty() || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            // titles and separators
+            continue;
+        }
+
+        let data = parse_hex(hex).ok_or_else(|| invalid_line(line))?;
+        stream.chunks.push(StreamChunk {
+            direction,
+            data,
+            frame_number: None,
+            timestamp_micros: None,
+        });
+    }
+
+    Ok(stream)
+}
+
+/// Parse "-z follow,<proto>,yaml,<index>" output.
+pub(crate) fn parse_follow_yaml(output: &str) -> Result<FollowedStream> {
+    // peers:
+    //   - peer: 0
+    //     host: 10.0.0.1
+    //     port: 51234
+    //   - peer: 1
+    //     host: 10.0.0.2
+    //     port: 80
+    // packets:
+    //   - packet: 4
+    //     peer: 0
+    //     index: 0
+    //     timestamp: 1700000000.123456789
+    //     data: !!binary |
+    //       R0VUIC8gSFRUUC8xLjENCg0K
+    let mut stream = FollowedStream::default();
+
+    // peers, as (peer, host, port)
+    let mut peers: Vec<(String, String, String)> = vec![];
+    // packets, with their peer and base64 data
+    let mut packets: Vec<(String, StreamChunk, String)> = vec![];
+    let mut in_packets = false;
+    let mut in_data = false;
+
+    for line in output.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            continue;
+        }
+        if !line.starts_with(' ') {
+            in_data = false;
+            in_packets = line.starts_with("packets:");
+            continue;
+        }
+
+        let item = trimmed.strip_prefix("- ");
+        let (key, value) = item.unwrap_or(trimmed).split_once(':').unwrap_or(("", ""));
+        let value = value.trim();
+
+        if in_data && key.is_empty() {
+            if let Some((_, _, data)) = packets.last_mut() {
+                data.push_str(trimmed);
+            }
+            continue;
+        }
+        in_data = false;
+
+        if !in_packets {
+            if item.is_some() {
+                peers.push((value.to_owned(), String::new(), String::new()));
+            } else if let Some((_, host, port)) = peers.last_mut() {
+                match key {
+                    "host" => *host = value.to_owned(),
+                    "port" => *port = value.to_owned(),
+                    _ => (),
+                }
+            }
+            continue;
+        }
+
+        if item.is_some() {
+            let chunk = StreamChunk {
+                direction: StreamDirection::ClientToServer,
+                data: vec![],
+                frame_number: value.parse().ok(),
+                timestamp_micros: None,
+            };
+            packets.push((String::new(), chunk, String::new()));
+        } else if let Some((peer, chunk, data)) = packets.last_mut() {
+            match key {
+                "peer" => *peer = value.to_owned(),
+                "timestamp" => chunk.timestamp_micros = parse_timestamp_micros(value),
+                "data" => match value.strip_prefix("!!binary |") {
+                    Some(inline) => {
+                        in_data = true;
+                        data.push_str(inline.trim());
+                    }
+                    None => data.push_str(value),
+                },
+                _ => (),
+            }
+        }
+    }
+
+    for (peer, host, port) in peers {
+        let node = if port.is_empty() {
+            host
+        } else {
+            format!("{}:{}", host, port)
+        };
+        match peer.as_str() {
+            "0" => stream.client = node,
+            "1" => stream.server = node,
+            _ => (),
+        }
+    }
+
+    for (peer, mut chunk, data) in packets {
+        chunk.direction = match peer.as_str() {
+            "1" => StreamDirection::ServerToClient,
+            _ => StreamDirection::ClientToServer,
+        };
+        chunk.data = decode_base64(&data).ok_or_else(|| invalid_line(&data))?;
+        stream.chunks.push(chunk);
+    }
+
+    Ok(stream)
+}
+
+/// Decode a standard base64 string, with padding.
+fn decode_base64(data: &str) -> Option<Vec<u8>> {
+    fn sextet(c: u8) -> Option<u32> {
+        match c {
+            b'A'..=b'Z' => Some((c - b'A') as u32),
+            b'a'..=b'z' => Some((c - b'a' + 26) as u32),
+            b'0'..=b'9' => Some((c - b'0' + 52) as u32),
+            b'+' => Some(62),
+            b'/' => Some(63),
+            _ => None,
+        }
+    }
+
+    let data = data.trim_end_matches('=').as_bytes();
+    let mut out = Vec::with_capacity(data.len() * 3 / 4);
+
+    for group in data.chunks(4) {
+        let mut bits = 0u32;
+        for &c in group {
+            bits = (bits << 6) | sextet(c)?;
+        }
+        match group.len() {
+            4 => out.extend_from_slice(&bits.to_be_bytes()[1..]),
+            3 => out.extend_from_slice(&(bits << 6).to_be_bytes()[1..3]),
+            2 => out.push((bits >> 4) as u8),
+            _ => return None,
+        }
+    }
+
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_follow_raw() {
+        let output = "
+===================================================================
+Follow: tcp,raw
+Filter: tcp.stream eq 0
+Node 0: 10.0.0.1:51234
+Node 1: 10.0.0.2:80
+474554202f
+\t4f4b
+\t0d0a
+===================================================================
+";
+        let stream = parse_follow_raw(output).unwrap();
+        assert_eq!(stream.client(), "10.0.0.1:51234");
+        assert_eq!(stream.server(), "10.0.0.2:80");
+        assert_eq!(stream.chunks().len(), 3);
+        assert_eq!(
+            stream.chunks()[0].direction(),
+            StreamDirection::ClientToServer
+        );
+        assert_eq!(stream.chunks()[0].data(), b"GET /");
+        assert_eq!(stream.chunks()[0].frame_number(), None);
+        assert_eq!(
+            stream.chunks()[1].direction(),
+            StreamDirection::ServerToClient
+        );
+        assert_eq!(stream.data(StreamDirection::ServerToClient), b"OK\r\n");
+
+        assert!(parse_follow_raw("\t4f4").is_err());
+    }
+
+    #[test]
+    fn test_parse_follow_yaml() {
+        let output = "
+===================================================================
+Follow: tcp,yaml
+Filter: tcp.stream eq 0
+peers:
+  - peer: 0
+    host: 10.0.0.1
+    port: 51234
+  - peer: 1
+    host: 10.0.0.2
+    port: 80
+packets:
+  - packet: 4
+    peer: 0
+    index: 0
+    timestamp: 1700000000.000001000
+    data: !!binary |
+      R0VUIC8=
+  - packet: 6
+    peer: 1
+    index: 0
+    timestamp: 1700000000.5
+    data: !!binary |
+      T0sN
+      Cg==
+===================================================================
+";
+        let stream = parse_follow_yaml(output).unwrap();
+        assert_eq!(stream.client(), "10.0.0.1:51234");
+        assert_eq!(stream.server(), "10.0.0.2:80");
+        assert_eq!(stream.chunks().len(), 2);
+
+        let request = &stream.chunks()[0];
+        assert_eq!(request.direction(), StreamDirection::ClientToServer);
+        assert_eq!(request.data(), b"GET /");
+        assert_eq!(request.frame_number(), Some(4));
+        assert_eq!(request.timestamp_micros(), Some(1_700_000_000_000_001));
+
+        let response = &stream.chunks()[1];
+        assert_eq!(response.direction(), StreamDirection::ServerToClient);
+        assert_eq!(response.data(), b"OK\r\n");
+        assert_eq!(response.frame_number(), Some(6));
+    }
+
+    #[test]
+    fn test_decode_base64() {
+        assert_eq!(decode_base64("").unwrap(), b"");
+        assert_eq!(decode_base64("Zg==").unwrap(), b"f");
+        assert_eq!(decode_base64("Zm8=").unwrap(), b"fo");
+        assert_eq!(decode_base64("Zm9v").unwrap(), b"foo");
+        assert_eq!(decode_base64("Zm9vYmE=").unwrap(), b"fooba");
+        assert!(decode_base64("Z").is_none());
+        assert!(decode_base64("Zm9*").is_none());
+    }
+}
@@ -0,0 +1,626 @@
+//! Several capture files read as one stream, see [crate::RTSharkBuilderReady::input_path],
+//! [crate::RTSharkBuilderReady::input_glob] and [crate::RTSharkBuilderReady::merge_inputs].
+//!
+//! Files are read one after the other by a new TShark process for each file, or merged
+//! chronologically by mergecap and piped to a single TShark process.
+//!
+//! A capture can also be read from any reader, piped to TShark, see
+//! [crate::RTSharkBuilder::input_reader].
+
+use crate::{
+    tshark_command, tshark_spawn_error, OutputFormat, PacketParser, PdmlChunk, RTShark,
+    RTSharkBuilderReady, RTSharkError, Result,
+};
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+
+/// Reader given to [crate::RTSharkBuilder::input_reader], shared by builder clones
+/// and taken by the first spawned TShark.
+#[derive(Clone)]
+pub(crate) struct InputReader {
+    reader: Arc<Mutex<Option<Box<dyn Read + Send>>>>,
+    /// error of the copy to TShark standard input
+    error: InputError,
+}
+
+impl InputReader {
+    pub(crate) fn new(reader: impl Read + Send + 'static) -> Self {
+        InputReader {
+            reader: Arc::new(Mutex::new(Some(Box::new(reader)))),
+            error: InputError::default(),
+        }
+    }
+
+    /// Take the reader, for the TShark process about to be spawned.
+    pub(crate) fn take(&self) -> Result<Box<dyn Read + Send>> {
+        let reader = self.reader.lock().unwrap_or_else(|e| e.into_inner()).take();
+        reader.ok_or_else(|| {
+            RTSharkError::invalid_argument("input_reader can only be read by one TShark process")
+        })
+    }
+
+    /// Get the error slot of the copy to TShark standard input.
+    pub(crate) fn error(&self) -> InputError {
+        self.error.clone()
+    }
+}
+
+/// Error returned by the reader while it is copied to TShark standard input. TShark only
+/// sees a short capture, so the error is reported by the TShark reader at EOF.
+#[derive(Clone, Default)]
+pub(crate) struct InputError(Arc<Mutex<Option<std::io::Error>>>);
+
+impl InputError {
+    fn set(&self, e: std::io::Error) {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner()) = Some(e);
+    }
+
+    /// Return the copy error once, if any.
+    pub(crate) fn check(&self) -> Result<()> {
+        match self.0.lock().unwrap_or_else(|e| e.into_inner()).take() {
+            Some(e) => Err(RTSharkError::Io(e)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Copy a reader to TShark standard input in a background thread. A copy error is stored in
+/// the error slot before stdin is closed, so that it is known when TShark output ends.
+pub(crate) fn write_input(
+    mut reader: Box<dyn Read + Send>,
+    mut stdin: impl Write + Send + 'static,
+    error: InputError,
+) -> Result<()> {
+    std::thread::Builder::new()
+        .name("rtshark-input".to_owned())
+        .spawn(move || {
+            match std::io::copy(&mut reader, &mut stdin) {
+                // TShark exited before reading the whole capture
+                Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => (),
+                Err(e) => error.set(e),
+                Ok(_) => (),
+            }
+            drop(stdin);
+        })?;
+
+    Ok(())
+}
+
+/// Next input files, each read by a new TShark process once the previous one has exited.
+pub(crate) struct InputChain {
+    /// TShark command line parameters
+    params: Vec<String>,
+    /// index of the input file in params
+    input_index: usize,
+    /// custom environment path containing TShark application
+    env_path: String,
+    /// kill TShark and its dumpcap child when this process dies (linux only)
+    kill_on_parent_death: bool,
+    /// output format used by TShark to report packets
+    output_format: OutputFormat,
+    /// files not read yet
+    files: VecDeque<String>,
+    /// file read by the running TShark process
+    pub(crate) current: Arc<str>,
+}
+
+/// Check that an input file exists.
+pub(crate) fn check_input_file(path: &str) -> Result<()> {
+    std::fs::metadata(path).map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => RTSharkError::InputNotFound {
+            path: path.to_owned(),
+        },
+        _ => e.into(),
+    })?;
+
+    Ok(())
+}
+
+/// Start reading several input files, one after the other or merged by mergecap.
+pub(crate) fn spawn_files(builder: &RTSharkBuilderReady) -> Result<RTShark> {
+    let files = input_files(builder)?;
+    if builder.merge_inputs {
+        return spawn_merged(builder, &files);
+    }
+    // each TShark process would overwrite the output file of the previous one
+    if !builder.output_path.is_empty() {
+        return Err(RTSharkError::invalid_argument(
+            "output_path cannot be used to read several input files one after the other, use merge_inputs",
+        ));
+    }
+
+    let mut files = VecDeque::from(files);
+    let Some(first) = files.pop_front() else {
+        return Err(RTSharkError::invalid_argument("no input file"));
+    };
+
+    let single = RTSharkBuilderReady {
+        input_path: vec![&first],
+        input_globs: vec![],
+        ..builder.clone()
+    };
+    let params = single.spawn_args()?;
+    let tshark_child = single.spawn_tshark(&params)?;
+
+    let mut rtshark = RTShark::new(
+        tshark_child,
+        builder.output_format,
+        builder.metadata_blacklist.clone(),
+        builder.graceful_drop,
+        builder.recover_xml_errors,
+    );
+    rtshark.inputs = Some(InputChain {
+        input_index: params.iter().position(|p| *p == "-r").unwrap_or_default() + 1,
+        params: params.iter().map(|p| p.to_string()).collect(),
+        env_path: builder.env_path.to_owned(),
+        kill_on_parent_death: builder.kill_on_parent_death,
+        output_format: builder.output_format,
+        files,
+        current: Arc::from(first.as_str()),
+    });
+
+    Ok(rtshark)
+}
+
+/// Merge input files with mergecap, piped to TShark standard input.
+fn spawn_merged(builder: &RTSharkBuilderReady, files: &[String]) -> Result<RTShark> {
+    let single = RTSharkBuilderReady {
+        input_path: vec!["-"],
+        input_globs: vec![],
+        ..builder.clone()
+    };
+    let params = single.spawn_args()?;
+
+    let mut command = Command::new("mergecap");
+    command
+        // "-" writes the merged capture to stdout
+        .args(["-w", "-"])
+        // file names starting with "-" are not options
+        .arg("--")
+        .args(files)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    if !builder.env_path.is_empty() {
+        command.env("PATH", builder.env_path);
+    }
+
+    let mut mergecap = command.spawn().map_err(tshark_spawn_error)?;
+    let merged = mergecap.stdout.take().unwrap();
+
+    let tshark_child = match single.tshark_command(&params).stdin(merged).spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            let _ = mergecap.kill();
+            let _ = mergecap.wait();
+            return Err(tshark_spawn_error(e));
+        }
+    };
+
+    let mut rtshark = RTShark::new(
+        tshark_child,
+        builder.output_format,
+        builder.metadata_blacklist.clone(),
+        builder.graceful_drop,
+        builder.recover_xml_errors,
+    );
+    // mergecap errors are reported like TShark ones
+    rtshark.stderr.merge(mergecap.stderr.take().unwrap());
+    rtshark.mergecap = Some(mergecap);
+
+    Ok(rtshark)
+}
+
+/// Input files, followed by the files matching each glob pattern. They must all exist.
+fn input_files(builder: &RTSharkBuilderReady) -> Result<Vec<String>> {
+    let mut files: Vec<String> = builder.input_path.iter().map(|p| p.to_string()).collect();
+    for file in &files {
+        check_input_file(file)?;
+    }
+
+    for pattern in &builder.input_globs {
+        files.extend(expand_glob(pattern)?);
+    }
+
+    Ok(files)
+}
+
+/// Get the files matching a glob pattern, sorted by name.
+/// Wildcards are only supported in the file name, not in directory names.
+fn expand_glob(pattern: &str) -> Result<Vec<String>> {
+    let not_found = || RTSharkError::InputNotFound {
+        path: pattern.to_owned(),
+    };
+
+    let path = Path::new(pattern);
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(not_found)?;
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    let entries = std::fs::read_dir(dir).map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => not_found(),
+        _ => e.into(),
+    })?;
+
+    let mut files = vec![];
+    for entry in entries {
+        let file_name = entry?.file_name();
+        let Some(file_name) = file_name.to_str() else {
+            continue;
+        };
+        // like shells, hidden files only match a pattern starting with a dot
+        if file_name.starts_with('.') && !name.starts_with('.') {
+            continue;
+        }
+        if glob_match(name, file_name) {
+            files.push(
+                path.with_file_name(file_name)
+                    .to_string_lossy()
+                    .into_owned(),
+            );
+        }
+    }
+
+    if files.is_empty() {
+        return Err(not_found());
+    }
+    files.sort();
+
+    Ok(files)
+}
+
+/// Check if a name matches a glob pattern: "*" matches any characters and "?" matches one character.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    let (mut p, mut n) = (0, 0);
+    // pattern position after the last "*", and name position it matches up to
+    let mut star = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p + 1, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            // backtrack: the last "*" matches one more character
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p;
+                    n = star_n + 1;
+                    star = Some((star_p, n));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+impl RTShark {
+    /// Start TShark on the next input file, once the previous one has closed its output.
+    /// Returns false if there is no next file, or if TShark was stopped or killed.
+    pub(crate) fn next_input(&mut self) -> Result<bool> {
+        let Some(inputs) = &mut self.inputs else {
+            return Ok(false);
+        };
+        if self.process.stopped() {
+            return Ok(false);
+        }
+        let Some(path) = inputs.files.pop_front() else {
+            return Ok(false);
+        };
+
+        self.process.wait()?;
+
+        inputs.params[inputs.input_index] = path.clone();
+        let mut tshark_child = tshark_command(
+            &inputs.params,
+            &inputs.env_path,
+            inputs.kill_on_parent_death,
+        )
+        .spawn()
+        .map_err(tshark_spawn_error)?;
+        let stdout = tshark_child.stdout.take().unwrap();
+        let stderr = tshark_child.stderr.take().unwrap();
+
+        if let Err(mut tshark_child) = self.process.replace(tshark_child) {
+            // stopped or killed in the meantime, from another thread
+            let _ = tshark_child.kill();
+            let _ = tshark_child.wait();
+            return Ok(false);
+        }
+
+        self.parser = PacketParser::new(inputs.output_format, stdout);
+        self.stderr.attach(stderr);
+        self.pdml_chunk = PdmlChunk::default();
+        inputs.current = Arc::from(path);
+
+        Ok(true)
+    }
+
+    /// Check the exit status of mergecap, once TShark has read all its output.
+    pub(crate) fn check_mergecap(&mut self) -> Result<()> {
+        let Some(mut mergecap) = self.mergecap.take() else {
+            return Ok(());
+        };
+
+        let status = mergecap.wait()?;
+        // TShark may stop reading before the end, like with -c
+        #[cfg(target_family = "unix")]
+        if std::os::unix::process::ExitStatusExt::signal(&status) == Some(libc::SIGPIPE) {
+            return Ok(());
+        }
+        if status.success() || self.process.stopped() {
+            return Ok(());
+        }
+
+        let lines = self.stderr.wait_lines();
+        match self.stderr.take_error() {
+            Some(line) => Err(RTSharkError::from_stderr(Some(status), &line, &lines)),
+            None => Err(RTSharkError::TsharkExited {
+                status: Some(status),
+                stderr: lines.join("\n"),
+            }),
+        }
+    }
+}
+
+#[cfg(all(test, target_family = "unix"))]
+mod tests {
+    use super::*;
+    use crate::tests::{env_path, pdml, write_script};
+    use crate::RTSharkBuilder;
+
+    /// TShark printing the "capture file" given to -r, which is already PDML.
+    const FAKE_TSHARK: &str = r#"#!/bin/sh
+while [ $# -gt 0 ]; do
+    case "$1" in
+        -r) file="$2"; shift;;
+    esac
+    shift
+done
+cat "$file"
+"#;
+
+    /// mergecap concatenating its input files, given after "--"
+    const FAKE_MERGECAP: &str = r#"#!/bin/sh
+[ "$3" = "--" ] || exit 1
+shift 3
+cat "$@"
+"#;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("ring_*.pcapng", "ring_00001_20240101.pcapng"));
+        assert!(glob_match("ring_?.pcap", "ring_1.pcap"));
+        assert!(glob_match("*", "my.pcap"));
+        assert!(glob_match("*a*b", "xaxxab"));
+        assert!(!glob_match("ring_?.pcap", "ring_10.pcap"));
+        assert!(!glob_match("ring_*.pcapng", "ring_1.pcap"));
+        assert!(!glob_match("*a*b", "xaxxa"));
+    }
+
+    #[test]
+    fn test_expand_glob() {
+        let tmp_dir = tempdir::TempDir::new("test_input_glob").unwrap();
+        for name in ["ring_2.pcap", "ring_1.pcap", "other.pcap", ".ring_3.pcap"] {
+            std::fs::write(tmp_dir.path().join(name), "").unwrap();
+        }
+
+        let pattern = tmp_dir.path().join("ring_*.pcap");
+        let files = expand_glob(pattern.to_str().unwrap()).unwrap();
+        let expected = ["ring_1.pcap", "ring_2.pcap"]
+            .map(|name| tmp_dir.path().join(name).to_str().unwrap().to_owned());
+        assert_eq!(files, expected);
+
+        let pattern = tmp_dir.path().join("missing_*.pcap");
+        assert!(matches!(
+            expand_glob(pattern.to_str().unwrap()),
+            Err(RTSharkError::InputNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn test_input_files_sequential() {
+        let tmp_dir = tempdir::TempDir::new("test_input_files").unwrap();
+        write_script(tmp_dir.path(), "tshark", FAKE_TSHARK);
+        let ring_1 = tmp_dir.path().join("ring_1.pcap");
+        let ring_2 = tmp_dir.path().join("ring_2.pcap");
+        let other = tmp_dir.path().join("other.pcap");
+        std::fs::write(&ring_1, pdml(&[1, 2])).unwrap();
+        std::fs::write(&ring_2, pdml(&[1])).unwrap();
+        std::fs::write(&other, pdml(&[1, 2, 3])).unwrap();
+
+        let env_path = env_path(tmp_dir.path());
+        let glob = tmp_dir.path().join("ring_*.pcap");
+        let builder = RTSharkBuilder::builder()
+            .input_path(other.to_str().unwrap())
+            .input_glob(glob.to_str().unwrap())
+            .env_path(&env_path);
+
+        let packets: Vec<(String, u64)> = builder
+            .spawn()
+            .unwrap()
+            .map(|packet| {
+                let packet = packet.unwrap();
+                let file = Path::new(packet.source_file().unwrap()).file_name();
+                let file = file.unwrap().to_str().unwrap().to_owned();
+                (file, packet.frame_number().unwrap())
+            })
+            .collect();
+
+        let expected = [
+            ("other.pcap", 1),
+            ("other.pcap", 2),
+            ("other.pcap", 3),
+            ("ring_1.pcap", 1),
+            ("ring_1.pcap", 2),
+            ("ring_2.pcap", 1),
+        ]
+        .map(|(file, number)| (file.to_owned(), number));
+        assert_eq!(packets, expected);
+    }
+
+    #[test]
+    fn test_input_files_output_path() {
+        let tmp_dir = tempdir::TempDir::new("test_input_files").unwrap();
+        write_script(tmp_dir.path(), "tshark", FAKE_TSHARK);
+        write_script(tmp_dir.path(), "mergecap", FAKE_MERGECAP);
+        std::fs::write(tmp_dir.path().join("ring_1.pcap"), pdml(&[1])).unwrap();
+        std::fs::write(tmp_dir.path().join("ring_2.pcap"), pdml(&[1])).unwrap();
+
+        let env_path = env_path(tmp_dir.path());
+        let glob = tmp_dir.path().join("ring_*.pcap");
+        let output = tmp_dir.path().join("output.pcap");
+        let builder = RTSharkBuilder::builder()
+            .input_glob(glob.to_str().unwrap())
+            .env_path(&env_path)
+            .output_path(output.to_str().unwrap());
+
+        // each TShark process would overwrite the output file
+        assert!(matches!(
+            builder.spawn(),
+            Err(RTSharkError::InvalidArgument { .. })
+        ));
+        // a single TShark process reads the merged files
+        assert!(builder.merge_inputs().spawn().is_ok());
+    }
+
+    #[test]
+    fn test_input_files_merged() {
+        let tmp_dir = tempdir::TempDir::new("test_input_files").unwrap();
+        write_script(tmp_dir.path(), "tshark", FAKE_TSHARK);
+        write_script(tmp_dir.path(), "mergecap", FAKE_MERGECAP);
+        std::fs::write(tmp_dir.path().join("ring_1.pcap"), pdml(&[1, 2])).unwrap();
+
+        let env_path = env_path(tmp_dir.path());
+        let glob = tmp_dir.path().join("ring_*.pcap");
+        let builder = RTSharkBuilder::builder()
+            .input_glob(glob.to_str().unwrap())
+            .env_path(&env_path)
+            .merge_inputs();
+
+        let packets: Vec<_> = builder.spawn().unwrap().map(|p| p.unwrap()).collect();
+        assert_eq!(packets.len(), 2);
+        assert!(packets.iter().all(|p| p.source_file().is_none()));
+    }
+
+    #[test]
+    fn test_input_files_merge_error() {
+        const FAILING_MERGECAP: &str = r#"#!/bin/sh
+echo "mergecap: The file \"$4\" isn't a capture file in a format mergecap understands." >&2
+exit 2
+"#;
+        let tmp_dir = tempdir::TempDir::new("test_input_files").unwrap();
+        write_script(tmp_dir.path(), "tshark", FAKE_TSHARK);
+        write_script(tmp_dir.path(), "mergecap", FAILING_MERGECAP);
+        std::fs::write(tmp_dir.path().join("ring_1.pcap"), "").unwrap();
+
+        let env_path = env_path(tmp_dir.path());
+        let glob = tmp_dir.path().join("ring_*.pcap");
+        let builder = RTSharkBuilder::builder()
+            .input_glob(glob.to_str().unwrap())
+            .env_path(&env_path)
+            .merge_inputs();
+
+        let mut rtshark = builder.spawn().unwrap();
+        assert!(matches!(
+            rtshark.read(),
+            Err(RTSharkError::TsharkExited { status, stderr })
+                if status.and_then(|s| s.code()) == Some(2) && stderr.starts_with("mergecap: The file")
+        ));
+        assert!(rtshark.read().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_input_reader() {
+        let tmp_dir = tempdir::TempDir::new("test_input_reader").unwrap();
+        write_script(tmp_dir.path(), "tshark", FAKE_TSHARK);
+
+        let env_path = env_path(tmp_dir.path());
+        let capture = std::io::Cursor::new(pdml(&[1, 2, 3]).into_bytes());
+        let builder = RTSharkBuilder::builder()
+            .input_reader(capture)
+            .env_path(&env_path);
+
+        let numbers: Vec<u64> = builder
+            .spawn()
+            .unwrap()
+            .map(|packet| packet.unwrap().frame_number().unwrap())
+            .collect();
+        assert_eq!(numbers, [1, 2, 3]);
+
+        // the reader is consumed
+        assert!(matches!(
+            builder.spawn(),
+            Err(RTSharkError::InvalidArgument { .. })
+        ));
+    }
+
+    #[test]
+    fn test_input_files_not_found() {
+        let builder = RTSharkBuilder::builder()
+            .input_path("/tmp/rtshark_missing_1.pcap")
+            .input_path("/tmp/rtshark_missing_2.pcap");
+        assert!(matches!(
+            builder.spawn(),
+            Err(RTSharkError::InputNotFound { .. })
+        ));
+
+        // only spawn() reads several files
+        let builder = RTSharkBuilder::builder().input_glob("/tmp/rtshark_*.pcap");
+        assert!(matches!(
+            builder.spawn_fields(),
+            Err(RTSharkError::InvalidArgument { .. })
+        ));
+    }
+
+    #[test]
+    fn test_input_reader_error() {
+        /// Reader failing like an interrupted download.
+        struct FailingReader;
+
+        impl Read for FailingReader {
+            fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::new(
+                    std::io::ErrorKind::ConnectionReset,
+                    "download failed",
+                ))
+            }
+        }
+
+        let tmp_dir = tempdir::TempDir::new("test_input_reader").unwrap();
+        write_script(tmp_dir.path(), "tshark", FAKE_TSHARK);
+
+        let env_path = env_path(tmp_dir.path());
+        let capture = std::io::Cursor::new(pdml(&[1, 2]).into_bytes()).chain(FailingReader);
+        let builder = RTSharkBuilder::builder()
+            .input_reader(capture)
+            .env_path(&env_path);
+        let mut rtshark = builder.spawn().unwrap();
+
+        assert_eq!(rtshark.read().unwrap().unwrap().frame_number(), Some(1));
+        assert_eq!(rtshark.read().unwrap().unwrap().frame_number(), Some(2));
+        // the reader error is returned at EOF, once
+        assert!(matches!(
+            rtshark.read(),
+            Err(RTSharkError::Io(e)) if e.kind() == std::io::ErrorKind::ConnectionReset
+        ));
+        assert!(rtshark.read().unwrap().is_none());
+    }
+}
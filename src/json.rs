@@ -0,0 +1,456 @@
+//! Parser for TShark JSON outputs (-T json and -T ek), mapped to the same [Packet] model as PDML.
+
+use crate::{ignored_protocols, parse_timestamp_micros, Metadata, Packet, RTSharkError, Result};
+use serde_json::{Map, Value};
+use std::io::BufRead;
+
+/// Streaming reader extracting top level JSON objects from TShark output.
+///
+/// With "-T json", TShark prints one array containing one object per packet.
+/// With "-T ek", TShark prints one object per line, each packet preceded by an "index" object.
+/// In both cases, packets are top level objects (or objects in the top level array), so
+/// this reader only tracks nesting and strings to find where each object ends.
+pub(crate) struct JsonReader<R: BufRead> {
+    /// TShark output
+    reader: R,
+    /// "-T ek" instead of "-T json"
+    ek: bool,
+    /// Bytes of the object being read
+    object: Vec<u8>,
+    /// Nesting level in the current object, 0 when outside an object
+    depth: usize,
+    /// Inside a JSON string
+    in_string: bool,
+    /// Previous byte was a backslash in a JSON string
+    escaped: bool,
+    /// Number of bytes consumed from TShark output
+    offset: u64,
+}
+
+impl<R: BufRead> JsonReader<R> {
+    pub(crate) fn new(reader: R, ek: bool) -> Self {
+        JsonReader {
+            reader,
+            ek,
+            object: vec![],
+            depth: 0,
+            in_string: false,
+            escaped: false,
+            offset: 0,
+        }
+    }
+
+    /// Get the underlying reader.
+    pub(crate) fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// Read the next top level object. Returns None at EOF.
+    fn read_object(&mut self) -> Result<Option<Vec<u8>>> {
+        loop {
+            let available = self.reader.fill_buf()?;
+            if available.is_empty() {
+                return Ok(None);
+            }
+
+            let mut complete = None;
+            for (i, &b) in available.iter().enumerate() {
+                if self.depth == 0 {
+                    // Outside objects: skip array delimiters, separators and spaces
+                    if b == b'{' {
+                        self.object.clear();
+                        self.object.push(b);
+                        self.depth = 1;
+                    }
+                    continue;
+                }
+
+                self.object.push(b);
+                if self.in_string {
+                    if self.escaped {
+                        self.escaped = false;
+                    } else if b == b'\\' {
+                        self.escaped = true;
+                    } else if b == b'"' {
+                        self.in_string = false;
+                    }
+                    continue;
+                }
+
+                match b {
+                    b'"' => self.in_string = true,
+                    b'{' | b'[' => self.depth += 1,
+                    b'}' | b']' => {
+                        self.depth -= 1;
+                        if self.depth == 0 {
+                            complete = Some(i + 1);
+                            break;
+                        }
+                    }
+                    _ => (),
+                }
+            }
+
+            let consumed = complete.unwrap_or(available.len());
+            self.reader.consume(consumed);
+            self.offset += consumed as u64;
+
+            if complete.is_some() {
+                return Ok(Some(std::mem::take(&mut self.object)));
+            }
+        }
+    }
+
+    /// Read the next packet. Returns None at EOF.
+    pub(crate) fn read_packet(&mut self, filters: &[String]) -> Result<Option<Packet>> {
+        loop {
+            let object = match self.read_object()? {
+                Some(object) => object,
+                None => return Ok(None),
+            };
+
+            let value: Value = serde_json::from_slice(&object).map_err(|e| {
+                RTSharkError::invalid_data(format!(
+                    "json parsing error: {} at tshark output offset {}",
+                    e, self.offset
+                ))
+            })?;
+
+            let layers = if self.ek {
+                value.get("layers")
+            } else {
+                value.get("_source").and_then(|s| s.get("layers"))
+            };
+
+            // EK "index" objects do not contain any layer
+            let Some(Value::Object(layers)) = layers else {
+                continue;
+            };
+
+            let mut packet = build_packet(layers, self.ek, filters);
+
+            // EK format provides the capture time in milliseconds, as a fallback
+            if packet.timestamp_micros.is_none() && self.ek {
+                packet.timestamp_micros = value
+                    .get("timestamp")
+                    .and_then(json_value_to_string)
+                    .and_then(|ms| ms.parse::<i64>().ok())
+                    .map(|ms| ms * 1000);
+            }
+
+            return Ok(Some(packet));
+        }
+    }
+}
+
+/// Convert a JSON scalar to the string displayed by TShark.
+fn json_value_to_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// Get a metadata name from a JSON key.
+///
+/// EK format replaces dots by underscores and prefixes keys with the layer name
+/// (example: "ip_ip_src" in layer "ip"). Only the dot after the protocol name can be restored
+/// (example: "tcp_tcp_seq_raw" becomes "tcp.seq_raw" but "tcp_tcp_flags_syn" becomes "tcp.flags_syn").
+fn field_name(key: &str, layer: &str, ek: bool) -> String {
+    if !ek {
+        return key.to_owned();
+    }
+
+    let key = key
+        .strip_prefix(layer)
+        .and_then(|k| k.strip_prefix('_'))
+        .unwrap_or(key);
+    key.replacen('_', ".", 1)
+}
+
+/// Check if a metadata should be skipped, like with PDML.
+fn skip_metadata(name: &str, filters: &[String]) -> bool {
+    name.is_empty() || name.starts_with("_ws.") || filters.iter().any(|f| f == name)
+}
+
+/// Build the tree of metadata from a JSON object, in order.
+fn build_metadata(
+    fields: &Map<String, Value>,
+    layer: &str,
+    ek: bool,
+    filters: &[String],
+    out: &mut Vec<Metadata>,
+) {
+    for (key, value) in fields {
+        // Children of a field are in an object named "<field>_tree", just after the field.
+        if let Some(parent) = key.strip_suffix("_tree") {
+            let parent = field_name(parent, layer, ek);
+            let trees: Vec<&Map<String, Value>> = match value {
+                Value::Object(tree) => vec![tree],
+                Value::Array(trees) => trees.iter().filter_map(|t| t.as_object()).collect(),
+                _ => vec![],
+            };
+
+            // With several occurrences of a field, each occurrence has its own tree
+            let parents: Vec<usize> = out
+                .iter()
+                .enumerate()
+                .filter(|(_, m)| m.name() == parent)
+                .map(|(i, _)| i)
+                .collect();
+
+            for (i, tree) in trees.into_iter().enumerate() {
+                let mut children = vec![];
+                build_metadata(tree, layer, ek, filters, &mut children);
+                match parents.get(i) {
+                    Some(&p) => out[p].children.extend(children),
+                    // parent is filtered out: keep children at its level
+                    None => out.extend(children),
+                }
+            }
+            continue;
+        }
+
+        let name = field_name(key, layer, ek);
+        let values = match value {
+            Value::Array(values) => values.iter().collect(),
+            value => vec![value],
+        };
+
+        for value in values {
+            match value {
+                // Text labels and "_ws.expert" contain fields without being a field
+                Value::Object(fields) => build_metadata(fields, layer, ek, filters, out),
+                value => {
+                    if let Some(value) = json_value_to_string(value) {
+                        if !skip_metadata(&name, filters) {
+                            out.push(Metadata::new(name.clone(), value, None, None, None));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Build a packet from the "layers" object of a packet.
+fn build_packet(layers: &Map<String, Value>, ek: bool, filters: &[String]) -> Packet {
+    let mut packet = Packet::new();
+
+    for (name, value) in layers {
+        let layer_fields: Vec<&Map<String, Value>> = match value {
+            Value::Object(fields) => vec![fields],
+            // Same protocol several times (IP tunnels ...)
+            Value::Array(items) if items.iter().all(|i| i.is_object()) => {
+                items.iter().filter_map(|i| i.as_object()).collect()
+            }
+            // In "whitelist" mode (-e option), layers contain fields directly
+            _ => {
+                let mut fields = Map::new();
+                fields.insert(name.clone(), value.clone());
+                let mut metadata = vec![];
+                build_metadata(&fields, "", ek, filters, &mut metadata);
+                for m in metadata {
+                    if let Some(proto) = m.name().split('.').next() {
+                        packet.push_if_not_exist(proto.to_owned());
+                    }
+                    if let Some(layer) = packet.last_layer_mut() {
+                        layer.add(m);
+                    }
+                }
+                continue;
+            }
+        };
+
+        for fields in layer_fields {
+            let mut metadata = vec![];
+            build_metadata(fields, name, ek, filters, &mut metadata);
+
+            if ignored_protocols(name) {
+                // Fields of "fake-field-wrapper" belong to the previous layer, if names match.
+                for m in metadata {
+                    let proto_from_name = m.name().split('.').next().unwrap_or("");
+                    if let Some(layer) = packet
+                        .last_layer_mut()
+                        .filter(|layer| layer.name == proto_from_name)
+                    {
+                        layer.add(m);
+                    }
+                }
+                continue;
+            }
+
+            packet.push(name.clone());
+            if let Some(layer) = packet.last_layer_mut() {
+                layer.metadata = metadata;
+            }
+        }
+    }
+
+    packet.timestamp_micros = packet
+        .layer_name("frame")
+        .and_then(|frame| frame.metadata("frame.time_epoch"))
+        .and_then(|epoch| parse_timestamp_micros(epoch.value()));
+    packet.frame_number = packet
+        .layer_name("frame")
+        .and_then(|frame| frame.metadata("frame.number"))
+        .and_then(|number| number.value().parse().ok());
+
+    packet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const JSON_TCP: &str = r#"
+[
+  {
+    "_index": "packets-2021-03-05",
+    "_type": "doc",
+    "_score": null,
+    "_source": {
+      "layers": {
+        "frame": {
+          "frame.time_epoch": "1652011560.275852000",
+          "frame.number": "1"
+        },
+        "ip": {
+          "ip.src": "1.1.1.1",
+          "ip.dst": "1.1.1.2"
+        },
+        "tcp": {
+          "tcp.srcport": "52796",
+          "tcp.flags": "0x0018",
+          "tcp.flags_tree": {
+            "tcp.flags.res": "0",
+            "tcp.flags.push": "1",
+            "_ws.expert": {
+              "tcp.flags.str": "{AP}",
+              "_ws.expert.message": "Some } expert message"
+            }
+          },
+          "Timestamps": {
+            "tcp.time_relative": "0.000000000"
+          }
+        }
+      }
+    }
+  },
+  {
+    "_index": "packets-2021-03-05",
+    "_type": "doc",
+    "_score": null,
+    "_source": {
+      "layers": {
+        "frame": {
+          "frame.number": "2"
+        },
+        "ip": [
+          { "ip.src": "10.215.215.9" },
+          { "ip.src": "10.10.215.9" }
+        ],
+        "udp": {
+          "udp.port": ["53", "5353"]
+        }
+      }
+    }
+  }
+]
+"#;
+
+    #[test]
+    fn test_json_parse_packets() {
+        let mut reader = JsonReader::new(JSON_TCP.as_bytes(), false);
+
+        let pkt = reader.read_packet(&[]).unwrap().unwrap();
+        assert_eq!(pkt.timestamp_micros(), Some(1652011560275852));
+        assert_eq!(pkt.layer_count(), 3);
+
+        let tcp = pkt.layer_name("tcp").unwrap();
+        let roots: Vec<&str> = tcp.tree().iter().map(|m| m.name()).collect();
+        assert_eq!(roots, ["tcp.srcport", "tcp.flags", "tcp.time_relative"]);
+
+        let flags = tcp.metadata("tcp.flags").unwrap();
+        let children: Vec<&str> = flags.children().iter().map(|m| m.name()).collect();
+        assert_eq!(
+            children,
+            ["tcp.flags.res", "tcp.flags.push", "tcp.flags.str"]
+        );
+        assert_eq!(tcp.metadata("tcp.flags.str").unwrap().value(), "{AP}");
+
+        let pkt = reader.read_packet(&[]).unwrap().unwrap();
+        assert_eq!(pkt.layer_count(), 4);
+        assert_eq!(pkt.layer_index(1).unwrap().name(), "ip");
+        assert_eq!(pkt.layer_index(2).unwrap().name(), "ip");
+        let udp = pkt.layer_name("udp").unwrap();
+        let ports: Vec<&str> = udp.iter().map(|m| m.value()).collect();
+        assert_eq!(ports, ["53", "5353"]);
+
+        assert!(reader.read_packet(&[]).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_json_filter_metadata() {
+        let mut reader = JsonReader::new(JSON_TCP.as_bytes(), false);
+
+        let pkt = reader
+            .read_packet(&["ip.src".to_string(), "tcp.flags".to_string()])
+            .unwrap()
+            .unwrap();
+
+        let ip = pkt.layer_name("ip").unwrap();
+        assert!(ip.metadata("ip.src").is_none());
+        assert!(ip.metadata("ip.dst").is_some());
+
+        // children of a filtered out field are kept at its level
+        let tcp = pkt.layer_name("tcp").unwrap();
+        assert!(tcp.metadata("tcp.flags").is_none());
+        assert!(tcp.tree().iter().any(|m| m.name() == "tcp.flags.push"));
+    }
+
+    #[test]
+    fn test_json_whitelist() {
+        let json = r#"[{"_source": {"layers": {"ip.src": ["127.0.0.1"], "udp.dstport": ["53"]}}}]"#;
+        let mut reader = JsonReader::new(json.as_bytes(), false);
+
+        let pkt = reader.read_packet(&[]).unwrap().unwrap();
+        let ip = pkt.layer_name("ip").unwrap();
+        assert_eq!(ip.metadata("ip.src").unwrap().value(), "127.0.0.1");
+        let udp = pkt.layer_name("udp").unwrap();
+        assert_eq!(udp.metadata("udp.dstport").unwrap().value(), "53");
+    }
+
+    #[test]
+    fn test_ek_parse_packets() {
+        let ek = r#"{"index":{"_index":"packets-2022-05-08","_type":"doc"}}
+{"timestamp":"1652011560275","layers":{"frame":{"frame_frame_number":"1"},"ip":{"ip_ip_src":"127.0.0.1","ip_ip_ttl":64},"tcp":{"tcp_tcp_seq_raw":"1963007432"}}}
+{"index":{"_index":"packets-2022-05-08","_type":"doc"}}
+{"timestamp":"1652011560276","layers":{"frame":{"frame_frame_number":"2","frame_frame_time_epoch":"1652011560.276000000"}}}
+"#;
+        let mut reader = JsonReader::new(ek.as_bytes(), true);
+
+        let pkt = reader.read_packet(&[]).unwrap().unwrap();
+        assert_eq!(pkt.timestamp_micros(), Some(1652011560275000));
+        let ip = pkt.layer_name("ip").unwrap();
+        assert_eq!(ip.metadata("ip.src").unwrap().value(), "127.0.0.1");
+        assert_eq!(ip.metadata("ip.ttl").unwrap().value(), "64");
+        let tcp = pkt.layer_name("tcp").unwrap();
+        assert!(tcp.metadata("tcp.seq_raw").is_some());
+
+        let pkt = reader.read_packet(&[]).unwrap().unwrap();
+        assert_eq!(pkt.timestamp_micros(), Some(1652011560276000));
+        assert_eq!(pkt.frame_number(), Some(2));
+
+        assert!(reader.read_packet(&[]).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_json_parse_error() {
+        let json = r#"[{"_source": {"layers": {"ip": {"ip.src": }}}}]"#;
+        let mut reader = JsonReader::new(json.as_bytes(), false);
+        assert!(reader.read_packet(&[]).is_err());
+    }
+}
@@ -25,11 +25,13 @@
 //!     Ok(rtshark) => rtshark,
 //! };
 //!
-//! // read packets until the end of the PCAP file
-//! while let Some(packet) = rtshark.read().unwrap_or_else(|e| {
-//!     eprintln!("Error parsing TShark output: {e}");
-//!     None
-//! }) {
+//! // read packets until the end of the PCAP file, or a fatal error
+//! for packet in rtshark {
+//!     let packet = match packet {
+//!         Ok(packet) => packet,
+//!         Err(e) => { eprintln!("Error parsing TShark output: {e}"); continue; }
+//!     };
+//!
 //!     for layer in packet {
 //!         println!("Layer: {}", layer.name());
 //!         for metadata in layer {
@@ -39,12 +41,54 @@
 //! }
 //! ```
 
+use pipe::PipeReader;
+use process::TsharkProcess;
 use quick_xml::events::{BytesStart, Event};
 use semver::Version;
-use std::io::{BufRead, BufReader, Error, ErrorKind, Result};
-#[cfg(target_family = "unix")]
-use std::os::unix::process::ExitStatusExt;
-use std::process::{Child, ChildStderr, ChildStdout, Command, Stdio};
+use std::io::{BufRead, BufReader};
+use std::iter::FusedIterator;
+use std::process::{Child, Command, Stdio};
+use stderr::StderrReader;
+
+#[cfg(feature = "tokio")]
+mod async_rtshark;
+mod catalog;
+mod channel;
+mod diagnostic;
+mod dissector;
+mod error;
+mod export;
+mod fields;
+mod follow;
+mod input;
+mod json;
+mod parallel;
+mod pipe;
+mod process;
+mod psml;
+mod reader;
+mod stats;
+mod stderr;
+mod value;
+
+#[cfg(feature = "tokio")]
+pub use async_rtshark::AsyncRTShark;
+pub use catalog::{FieldCatalog, FieldEntry, FieldType, FieldValue, ProtocolEntry};
+pub use channel::{BackpressurePolicy, PacketReceiver};
+pub use diagnostic::{Diagnostic, DiagnosticKind, DiagnosticSeverity};
+pub use dissector::Dissector;
+pub use error::{RTSharkError, Result, SkippedPacket};
+pub use export::{ExportProtocol, ExportedObject};
+pub use fields::{FieldsReader, FieldsRow};
+pub use follow::{FollowMode, FollowProtocol, FollowedStream, StreamChunk, StreamDirection};
+pub use parallel::{ParallelOptions, ParallelReader, SplitMode};
+pub use process::{RTSharkHandle, TerminationReport};
+pub use psml::{PacketSummary, SummaryReader};
+pub use stats::{
+    Conversation, ConversationType, Endpoint, ExpertInfo, ExpertSeverity, IoInterval,
+    ProtocolStats, StatsTree, StatsTreeItem,
+};
+pub use value::ValueError;
 
 /// A metadata belongs to one [Layer]. It describes one particular information about a [Packet] (example: IP source address).
 #[derive(Default, Clone, Debug, PartialEq)]
@@ -63,6 +107,8 @@ pub struct Metadata {
     size: Option<u32>,
     /// Offset of this data in the packet, in bytes
     position: Option<u32>,
+    /// Child metadata nested under this one by TShark (example: flag bits of "tcp.flags")
+    children: Vec<Metadata>,
 }
 
 /// This is one metadata from a given layer of the packet returned by TShark application.
@@ -82,6 +128,7 @@ impl Metadata {
             display,
             size,
             position,
+            children: vec![],
         }
     }
 
@@ -162,6 +209,78 @@ impl Metadata {
     pub fn position(&self) -> Option<u32> {
         self.position
     }
+
+    /// Adds a child metadata under this metadata. This function is useless for most applications.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut flags = rtshark::Metadata::new("tcp.flags".to_string(), "0x0018".to_string(), None, None, None);
+    /// let push = rtshark::Metadata::new("tcp.flags.push".to_string(), "1".to_string(), None, None, None);
+    /// flags.add_child(push);
+    /// assert_eq!(flags.children().len(), 1)
+    /// ```
+    pub fn add_child(&mut self, metadata: Metadata) {
+        self.children.push(metadata);
+    }
+
+    /// Child metadata nested under this metadata by TShark, in PDML order.
+    /// For instance, "tcp.flags.syn" is a child of "tcp.flags".
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut flags = rtshark::Metadata::new("tcp.flags".to_string(), "0x0018".to_string(), None, None, None);
+    /// let push = rtshark::Metadata::new("tcp.flags.push".to_string(), "1".to_string(), None, None, None);
+    /// flags.add_child(push);
+    /// assert_eq!(flags.children()[0].name(), "tcp.flags.push")
+    /// ```
+    pub fn children(&self) -> &[Metadata] {
+        &self.children
+    }
+}
+
+/// Depth-first iterator on a tree of [Metadata], returning parents before their children.
+struct MetadataIter<'a> {
+    stack: Vec<std::slice::Iter<'a, Metadata>>,
+}
+
+impl<'a> MetadataIter<'a> {
+    fn new(roots: &'a [Metadata]) -> Self {
+        MetadataIter {
+            stack: vec![roots.iter()],
+        }
+    }
+}
+
+impl<'a> Iterator for MetadataIter<'a> {
+    type Item = &'a Metadata;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let iter = self.stack.last_mut()?;
+            match iter.next() {
+                Some(metadata) => {
+                    if !metadata.children.is_empty() {
+                        self.stack.push(metadata.children.iter());
+                    }
+                    return Some(metadata);
+                }
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
+}
+
+/// Move a tree of [Metadata] in a flat list, parents before their children.
+fn flatten_metadata(tree: Vec<Metadata>, flat: &mut Vec<Metadata>) {
+    for mut metadata in tree {
+        let children = std::mem::take(&mut metadata.children);
+        flat.push(metadata);
+        flatten_metadata(children, flat);
+    }
 }
 
 /// A layer is a protocol in the protocol stack of a packet (example: IP layer). It may contain multiple [Metadata].
@@ -171,7 +290,7 @@ pub struct Layer {
     name: String,
     /// Number of this layer for this packet in the stack of layers. Starts at 0 with "frame" virtual layer.
     index: usize,
-    /// List of metadata associated to this layer
+    /// List of top level metadata associated to this layer, each one may contain child metadata
     metadata: Vec<Metadata>,
 }
 
@@ -214,7 +333,7 @@ impl Layer {
         self.index
     }
 
-    /// Adds a metadata in the list of metadata for this layer. This function is useless for most applications.
+    /// Adds a top level metadata in the list of metadata for this layer. This function is useless for most applications.
     ///
     /// # Example
     ///
@@ -227,7 +346,7 @@ impl Layer {
         self.metadata.push(metadata);
     }
 
-    /// Get a metadata by its name.
+    /// Get a metadata by its name. Child metadata are searched too.
     ///
     /// # Example
     ///
@@ -239,13 +358,16 @@ impl Layer {
     /// assert_eq!(ip_src.display(), Some("Source: 127.0.0.1"))
     /// ```
     pub fn metadata(&self, name: &str) -> Option<&Metadata> {
-        self.metadata.iter().find(|m| m.name().eq(name))
+        self.iter().find(|m| m.name().eq(name))
     }
 
     /// Get an iterator on the list of [Metadata] for this [Layer].
     /// This iterator does not take ownership of returned [Metadata].
     /// This is the opposite of the "into"-iterator which returns owned objects.
     ///
+    /// The metadata tree is flattened: each metadata is returned before its children.
+    /// Use [Layer::tree] to keep the hierarchy.
+    ///
     /// # Example
     ///
     /// ```
@@ -256,7 +378,24 @@ impl Layer {
     /// assert_eq!(metadata.display(), Some("Source: 127.0.0.1"))
     /// ```
     pub fn iter(&self) -> impl Iterator<Item = &Metadata> {
-        self.metadata.iter()
+        MetadataIter::new(&self.metadata)
+    }
+
+    /// Get the top level [Metadata] of this [Layer], as nested by TShark.
+    /// Child metadata are available using [Metadata::children].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let mut tcp_layer = rtshark::Layer::new("tcp".to_string(), 2);
+    /// let mut flags = rtshark::Metadata::new("tcp.flags".to_string(), "0x0002".to_string(), None, None, None);
+    /// flags.add_child(rtshark::Metadata::new("tcp.flags.syn".to_string(), "1".to_string(), None, None, None));
+    /// tcp_layer.add(flags);
+    /// let flags = &tcp_layer.tree()[0];
+    /// assert_eq!(flags.children()[0].name(), "tcp.flags.syn")
+    /// ```
+    pub fn tree(&self) -> &[Metadata] {
+        &self.metadata
     }
 }
 
@@ -268,6 +407,8 @@ impl IntoIterator for Layer {
     /// This iterator takes ownership of returned [Metadata].
     /// This is the opposite of an iterator by reference.
     ///
+    /// Like [Layer::iter], the metadata tree is flattened. Returned [Metadata] have no children.
+    ///
     /// # Example 1
     ///
     /// ```
@@ -288,7 +429,9 @@ impl IntoIterator for Layer {
     /// assert_eq!(metadata.display(), Some("Source: 127.0.0.1"))
     /// ```
     fn into_iter(self) -> Self::IntoIter {
-        self.metadata.into_iter()
+        let mut flat = vec![];
+        flatten_metadata(self.metadata, &mut flat);
+        flat.into_iter()
     }
 }
 
@@ -300,6 +443,10 @@ pub struct Packet {
     /// Packet capture timestamp --- the number of non-leap-microseconds since
     /// January 1, 1970 UTC
     timestamp_micros: Option<i64>,
+    /// Packet number in the capture, starting at 1
+    frame_number: Option<u64>,
+    /// Capture file of the packet, when several files are read one after the other
+    source_file: Option<std::sync::Arc<str>>,
 }
 
 impl Packet {
@@ -319,6 +466,18 @@ impl Packet {
         self.timestamp_micros
     }
 
+    /// Returns this packet's number in the capture (frame.number), starting at 1.
+    pub fn frame_number(&self) -> Option<u64> {
+        self.frame_number
+    }
+
+    /// Returns the capture file this packet was read from, when several input files are read
+    /// one after the other (see [RTSharkBuilderReady::input_path]). Its frame number is relative
+    /// to this file.
+    pub fn source_file(&self) -> Option<&str> {
+        self.source_file.as_deref()
+    }
+
     /// Push a new layer at the end of the layer stack. This function is useless for most applications.
     /// # Examples
     ///
@@ -515,6 +674,57 @@ impl<'a> RTSharkBuilder {
             enabled_protocols: vec![],
             output_path: "",
             decode_as: vec![],
+            output_format: OutputFormat::default(),
+            graceful_drop: None,
+            kill_on_parent_death: false,
+            recover_xml_errors: false,
+            input_globs: vec![],
+            merge_inputs: false,
+            input_reader: None,
+        }
+    }
+
+    /// Capture read from any reader instead of a file: TShark reads it on its standard input
+    /// ("-r -"), fed by a background thread. The capture must be in pcapng or pcap format.
+    ///
+    /// This avoids temporary files for captures held in memory, downloaded or decompressed on
+    /// the fly. The reader is consumed by the first spawn: a builder cannot be spawned twice.
+    /// If the reader fails, TShark sees a short capture: the reader error is returned as an
+    /// [RTSharkError::Io] once TShark output ends.
+    ///
+    /// ## Example: Prepare an instance of TShark to read a capture held in memory
+    ///
+    /// ```
+    /// let capture: Vec<u8> = vec![];
+    /// let builder = rtshark::RTSharkBuilder::builder()
+    ///     .input_reader(std::io::Cursor::new(capture));
+    /// ```
+    pub fn input_reader(
+        &mut self,
+        reader: impl std::io::Read + Send + 'static,
+    ) -> RTSharkBuilderReady<'a> {
+        RTSharkBuilderReady {
+            input_reader: Some(input::InputReader::new(reader)),
+            ..self.input_path("-")
+        }
+    }
+
+    /// Capture files to read, given by a glob pattern: "*" matches any characters and "?"
+    /// matches one character in the file name (not in directory names).
+    /// Matching files are read one after the other, sorted by name, like the files of a ring
+    /// buffer. See [RTSharkBuilderReady::input_path] to read several files.
+    ///
+    /// ## Example: Prepare an instance of TShark to read all files of a ring buffer
+    ///
+    /// ```
+    /// let builder = rtshark::RTSharkBuilder::builder()
+    ///     .input_glob("/tmp/ring_*.pcapng");
+    /// ```
+    pub fn input_glob(&mut self, pattern: &'a str) -> RTSharkBuilderReady<'a> {
+        RTSharkBuilderReady {
+            input_path: vec![],
+            input_globs: vec![pattern],
+            ..self.input_path("")
         }
     }
 
@@ -528,24 +738,42 @@ impl<'a> RTSharkBuilder {
     /// }
     /// ```
     pub fn version(&self) -> Result<RTSharkVersion> {
-        let output = Command::new("tshark").args(["--version"]).output()?;
+        let output = Command::new("tshark")
+            .args(["--version"])
+            .output()
+            .map_err(tshark_spawn_error)?;
         let message = std::str::from_utf8(&output.stdout)
-            .map_err(|e| {
-                std::io::Error::new(
-                    std::io::ErrorKind::InvalidData,
-                    format!("Version message not utf8: {}", e),
-                )
-            })?
+            .map_err(|e| RTSharkError::invalid_data(format!("Version message not utf8: {}", e)))?
             .to_owned();
         let version = message
             .split_whitespace()
             .find_map(|s| Version::parse(s).ok())
-            .ok_or(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
+            .ok_or(RTSharkError::invalid_data(
                 "Unable to parse version from command output",
             ))?;
         Ok(RTSharkVersion { version, message })
     }
+
+    /// Retrieve the catalog of protocols and fields known by the TShark executable,
+    /// using "tshark -G fields" and "tshark -G protocols" reports.
+    ///
+    /// This catalog can be used to validate metadata names before spawning TShark,
+    /// see [RTSharkBuilderReady::validate_metadata], or to get the typed value of a [Metadata].
+    ///
+    /// ## Example:
+    /// ```
+    /// let builder = rtshark::RTSharkBuilder::builder();
+    /// if let Ok(catalog) = builder.field_catalog() {
+    ///     if let Some(field) = catalog.field("ip.src") {
+    ///         println!("{}: {}", field.name(), field.ftype());
+    ///     }
+    /// }
+    /// ```
+    pub fn field_catalog(&self) -> Result<FieldCatalog> {
+        let fields = catalog::glossary("fields")?;
+        let protocols = catalog::glossary("protocols")?;
+        Ok(FieldCatalog::parse(&fields, &protocols))
+    }
 }
 
 /// Version information for the TShark executable
@@ -586,6 +814,22 @@ impl RTSharkVersion {
     }
 }
 
+/// Output format used by TShark to report packets details, see [RTSharkBuilderReady::output_format].
+/// All formats are mapped to the same [Packet], [Layer] and [Metadata] types.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Packet Details Markup Language (-T pdml), an XML-based format. This is the default format.
+    #[default]
+    Pdml,
+    /// JSON format (-T json). Metadata size, position and raw value are not available.
+    Json,
+    /// Elasticsearch bulk format (-T ek), one JSON object per line.
+    /// Metadata size, position and raw value are not available, and the metadata hierarchy is flattened.
+    /// Dots in metadata names are replaced by underscores by TShark: only the dot after the protocol name is restored
+    /// (example: "tcp_tcp_seq_raw" becomes "tcp.seq_raw").
+    Ek,
+}
+
 /// RTSharkBuilderReady is an object used to run to create a [RTShark] instance.
 /// It is possible to use it to add more optional parameters before starting a TShark application.
 #[derive(Clone)]
@@ -614,13 +858,30 @@ pub struct RTSharkBuilderReady<'a> {
     output_path: &'a str,
     /// decode_as : let TShark to decode as this expression
     decode_as: Vec<&'a str>,
+    /// output format used by TShark to report packets
+    output_format: OutputFormat,
+    /// stop TShark gracefully when RTShark is dropped, with this timeout
+    graceful_drop: Option<std::time::Duration>,
+    /// kill TShark process group when this process dies (linux only)
+    kill_on_parent_death: bool,
+    /// skip packets which cannot be parsed, instead of stopping at the first XML error
+    recover_xml_errors: bool,
+    /// glob patterns of input files, expanded when TShark is spawned
+    input_globs: Vec<&'a str>,
+    /// merge input files chronologically with mergecap, instead of reading them one after the other
+    merge_inputs: bool,
+    /// capture piped to TShark standard input
+    input_reader: Option<input::InputReader>,
 }
 
 impl<'a> RTSharkBuilderReady<'a> {
-    /// Adds another input for tshark. It works only with live capture to read packets from
-    /// multiple interfaces.
-    /// Adding multiple pcap files will fail, since tshark will only read the last instance of "-r"
-    /// option.
+    /// Adds another input for tshark. With live capture, packets are read from multiple interfaces.
+    ///
+    /// Multiple pcap files are only supported by spawn(): one TShark process reads each file, one
+    /// after the other, and [Packet::source_file] tells which file a packet comes from.
+    /// Use merge_inputs() to read them in chronological order instead.
+    /// output_path() can only be used with merge_inputs(), since each TShark process would
+    /// overwrite the output file.
     ///
     /// ## Example: Prepare an instance of TShark to read from multiple network interfaces
     ///
@@ -630,6 +891,14 @@ impl<'a> RTSharkBuilderReady<'a> {
     ///     .input_path("eth1")
     ///     .live_capture();
     /// ```
+    ///
+    /// ## Example: Prepare an instance of TShark to read two capture files
+    ///
+    /// ```
+    /// let builder = rtshark::RTSharkBuilder::builder()
+    ///     .input_path("/tmp/my.pcap")
+    ///     .input_path("/tmp/my_other.pcap");
+    /// ```
     #[must_use]
     pub fn input_path(&self, path: &'a str) -> Self {
         let mut new = self.clone();
@@ -637,6 +906,44 @@ impl<'a> RTSharkBuilderReady<'a> {
         new
     }
 
+    /// Adds capture files given by a glob pattern, see [RTSharkBuilder::input_glob].
+    /// Matching files are read after the other input files, sorted by name.
+    ///
+    /// ## Example: Prepare an instance of TShark to read the files of two ring buffers
+    ///
+    /// ```
+    /// let builder = rtshark::RTSharkBuilder::builder()
+    ///     .input_glob("/tmp/eth0_*.pcapng")
+    ///     .input_glob("/tmp/eth1_*.pcapng");
+    /// ```
+    #[must_use]
+    pub fn input_glob(&self, pattern: &'a str) -> Self {
+        let mut new = self.clone();
+        new.input_globs.push(pattern);
+        new
+    }
+
+    /// Merge multiple input files in chronological order with mergecap, instead of reading them
+    /// one after the other. A single TShark process reads the merged packets, so frame numbers
+    /// run across all files and [Packet::source_file] is not set.
+    ///
+    /// mergecap is searched like TShark, in PATH or in env_path().
+    ///
+    /// ### Example: Prepare an instance of TShark to merge the captures of two interfaces
+    ///
+    /// ```
+    /// let builder = rtshark::RTSharkBuilder::builder()
+    ///     .input_glob("/tmp/eth0_*.pcapng")
+    ///     .input_glob("/tmp/eth1_*.pcapng")
+    ///     .merge_inputs();
+    /// ```
+    #[must_use]
+    pub fn merge_inputs(&self) -> Self {
+        let mut new = self.clone();
+        new.merge_inputs = true;
+        new
+    }
+
     /// Enables -i option of TShark.
     ///
     /// This option must be set to use network interface or pipe for live packet capture. See input_path() option of [RTSharkBuilder] for more details.
@@ -878,652 +1185,2192 @@ impl<'a> RTSharkBuilderReady<'a> {
         new
     }
 
-    /// Starts a new TShark process given the provided parameters, mapped to a new [RTShark] instance.
-    /// This function may fail if tshark binary is not in PATH or if there are some issues with input_path parameter : not found or no read permission...
-    /// In other cases (output_path not writable, invalid syntax for pcap_filter or display_filter),
-    /// TShark process will start but will stop a few moments later, leading to a EOF on rtshark.read function.
-    /// # Example
+    /// Select the output format used by TShark to report packets details (-T option).
+    /// PDML is used by default.
+    ///
+    /// All formats are mapped to the same [Packet] type, so that the fastest or the most
+    /// faithful format can be chosen for each workload. See [OutputFormat] for limitations.
+    ///
+    /// ### Example: Prepare an instance of TShark using JSON output
     ///
     /// ```
     /// let builder = rtshark::RTSharkBuilder::builder()
-    ///     .input_path("/tmp/my.pcap");
-    /// let tshark: std::io::Result<rtshark::RTShark> = builder.spawn();
+    ///     .input_path("/tmp/my.pcap")
+    ///     .output_format(rtshark::OutputFormat::Json);
     /// ```
-    pub fn spawn(&self) -> Result<RTShark> {
-        let mut tshark_params = self.prepare_args()?;
+    #[must_use]
+    pub fn output_format(&self, format: OutputFormat) -> Self {
+        let mut new = self.clone();
+        new.output_format = format;
+        new
+    }
 
-        tshark_params.extend(&[
-            // Packet Details Markup Language, an XML-based format for the details of a decoded packet.
-            // This information is equivalent to the packet details printed with the -V option.
-            "-Tpdml", // -l activate unbuffered mode, useful to print packets as they come
-            "-l",
-        ]);
+    /// Stop TShark gracefully when the [RTShark] instance is dropped, like with [RTShark::stop],
+    /// instead of killing it. Drop then waits for TShark termination, at most for this timeout.
+    /// It also applies to the [FieldsReader] and [SummaryReader] instances.
+    ///
+    /// This lets TShark flush the file given to output_path().
+    ///
+    /// ### Example: Prepare an instance of TShark writing a complete pcap file when dropped
+    ///
+    /// ```
+    /// let builder = rtshark::RTSharkBuilder::builder()
+    ///     .input_path("eth0")
+    ///     .live_capture()
+    ///     .output_path("/tmp/capture.pcap")
+    ///     .graceful_drop(std::time::Duration::from_secs(5));
+    /// ```
+    #[must_use]
+    pub fn graceful_drop(&self, timeout: std::time::Duration) -> Self {
+        let mut new = self.clone();
+        new.graceful_drop = Some(timeout);
+        new
+    }
 
-        let mut tshark_child = self.spawn_tshark(&tshark_params)?;
+    /// Kill TShark and its dumpcap child if this process dies without dropping [RTShark],
+    /// for instance after a crash. This is only available on Linux, and ignored on other platforms.
+    ///
+    /// A small watchdog shell is started in TShark process group: it kills the whole group once
+    /// this process has died, and exits with TShark (PR_SET_PDEATHSIG).
+    ///
+    /// ### Example: Prepare an instance of TShark which never outlives this process
+    ///
+    /// ```
+    /// let builder = rtshark::RTSharkBuilder::builder()
+    ///     .input_path("eth0")
+    ///     .live_capture()
+    ///     .kill_on_parent_death();
+    /// ```
+    #[must_use]
+    pub fn kill_on_parent_death(&self) -> Self {
+        let mut new = self.clone();
+        new.kill_on_parent_death = true;
+        new
+    }
 
-        let buf_reader = BufReader::new(tshark_child.stdout.take().unwrap());
-        let stderr = BufReader::new(tshark_child.stderr.take().unwrap());
+    /// Skip packets which cannot be parsed instead of stopping at the first XML error, so that
+    /// unexpected output for one malformed packet does not stop reading a long capture.
+    ///
+    /// read() returns an [RTSharkError::SkippedPacket] error for each skipped packet, with its
+    /// position in TShark output, then next packets are read as usual. This is only useful with
+    /// the default PDML output: with JSON outputs, an invalid packet never stops reading.
+    ///
+    /// ### Example: Prepare an instance of TShark skipping invalid packets
+    ///
+    /// ```
+    /// let builder = rtshark::RTSharkBuilder::builder()
+    ///     .input_path("/tmp/my.pcap")
+    ///     .recover_xml_errors();
+    /// ```
+    #[must_use]
+    pub fn recover_xml_errors(&self) -> Self {
+        let mut new = self.clone();
+        new.recover_xml_errors = true;
+        new
+    }
 
-        let reader = quick_xml::Reader::from_reader(buf_reader);
+    /// Check that all metadata names given to metadata_whitelist() and metadata_blacklist()
+    /// are known by TShark, using a catalog retrieved by [RTSharkBuilder::field_catalog].
+    ///
+    /// TShark does not complain about unknown names in the blacklist, and a typo in the whitelist
+    /// leads to packets without metadata. This function returns an error listing all unknown names.
+    ///
+    /// ### Example: Validate the whitelist before starting TShark
+    ///
+    /// ```
+    /// let builder = rtshark::RTSharkBuilder::builder()
+    ///     .input_path("/tmp/my.pcap")
+    ///     .metadata_whitelist("ip.src");
+    /// if let Ok(catalog) = rtshark::RTSharkBuilder::builder().field_catalog() {
+    ///     builder.validate_metadata(&catalog).expect("unknown metadata name");
+    /// }
+    /// ```
+    pub fn validate_metadata(&self, catalog: &FieldCatalog) -> Result<()> {
+        let unknown: Vec<&str> = self
+            .metadata_whitelist
+            .iter()
+            .flatten()
+            .chain(self.metadata_blacklist.iter())
+            .map(|name| name.as_str())
+            .filter(|name| !catalog.contains(name))
+            .collect();
+
+        if !unknown.is_empty() {
+            return Err(RTSharkError::invalid_argument(format!(
+                "Unknown metadata names: {}",
+                unknown.join(", ")
+            )));
+        }
 
-        Ok(RTShark::new(
-            tshark_child,
-            reader,
-            stderr,
-            self.metadata_blacklist.clone(),
-        ))
+        Ok(())
     }
 
-    /// Starts a new TShark process given the provided parameters and runs it to completion. In
-    /// contrast to [`RTSharkBuilderReady::spawn` ]no programmatic access to individual packets is
-    /// provided.
+    /// Starts a new TShark process given the provided parameters, mapped to a new [RTShark] instance.
     /// This function may fail if tshark binary is not in PATH or if there are some issues with input_path parameter : not found or no read permission...
     /// In other cases (output_path not writable, invalid syntax for pcap_filter or display_filter),
-    /// TShark process will fail and the stderr will be reported.
+    /// TShark process will start but will stop a few moments later, leading to a EOF on rtshark.read function.
     /// # Example
     ///
     /// ```
     /// let builder = rtshark::RTSharkBuilder::builder()
     ///     .input_path("/tmp/my.pcap");
-    /// let _: Result<(), std::io::Error> = builder.batch();
+    /// let tshark: rtshark::Result<rtshark::RTShark> = builder.spawn();
     /// ```
-    pub fn batch(&self) -> Result<()> {
-        let tshark_params = self.prepare_args()?;
+    pub fn spawn(&self) -> Result<RTShark> {
+        if !self.live_capture && (self.input_path.len() > 1 || !self.input_globs.is_empty()) {
+            return input::spawn_files(self);
+        }
 
-        let mut tshark_child = self.spawn_tshark(&tshark_params)?;
+        let tshark_params = self.spawn_args()?;
+        let tshark_child = self.spawn_tshark(&tshark_params)?;
 
-        if !tshark_child.wait()?.success() {
-            let mut stderr = BufReader::new(tshark_child.stderr.take().unwrap());
-            // if process stops, there may be due to an error, we can get it in stderr
-            let mut line = String::new();
-            let size = stderr.read_line(&mut line)?;
-            // if len is != 0 there is an error message
-            if size != 0 {
-                return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, line));
-            }
-        }
+        let mut rtshark = RTShark::new(
+            tshark_child,
+            self.output_format,
+            self.metadata_blacklist.clone(),
+            self.graceful_drop,
+            self.recover_xml_errors,
+        );
+        rtshark.input_error = self.input_error();
 
-        Ok(())
+        Ok(rtshark)
     }
 
-    fn spawn_tshark(&self, tshark_params: &[&str]) -> Result<Child> {
-        // piping from TShark, not to load the entire output in ram...
-        // spawn may fail if TShark is not found in path
+    /// Prepare TShark command line parameters used by spawn().
+    fn spawn_args(&self) -> Result<Vec<&str>> {
+        let mut tshark_params = self.prepare_args()?;
 
-        let tshark_child = if self.env_path.is_empty() {
-            Command::new("tshark")
-                .args(tshark_params)
-                .stdout(Stdio::piped())
-                .stderr(Stdio::piped())
-                .spawn()
-        } else {
-            Command::new("tshark")
-                .args(tshark_params)
-                .stdout(Stdio::piped())
-                .stderr(Stdio::piped())
-                .env("PATH", self.env_path)
-                .spawn()
-        };
+        match self.output_format {
+            // Packet Details Markup Language, an XML-based format for the details of a decoded packet.
+            // This information is equivalent to the packet details printed with the -V option.
+            OutputFormat::Pdml => tshark_params.push("-Tpdml"),
+            // Merge duplicated keys in arrays, so that no metadata is lost by the JSON parser.
+            OutputFormat::Json => tshark_params.extend(&["-Tjson", "--no-duplicate-keys"]),
+            OutputFormat::Ek => tshark_params.push("-Tek"),
+        }
+        // -l activate unbuffered mode, useful to print packets as they come
+        tshark_params.push("-l");
 
-        tshark_child.map_err(|e| match e.kind() {
-            std::io::ErrorKind::NotFound => {
-                std::io::Error::new(e.kind(), format!("Unable to find tshark: {}", e))
-            }
-            _ => e,
-        })
+        Ok(tshark_params)
     }
 
-    /// Prepare tshark command line parameters.
-    fn prepare_args(&self) -> Result<Vec<&str>> {
-        let mut tshark_params = if self.live_capture {
-            let mut input = vec![];
-            self.input_path
-                .iter()
-                .for_each(|i| input.extend(&["-i", i]));
-            input
-        } else {
-            if self.input_path.len() > 1 {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::InvalidInput,
-                    "tshark supports only one input pcap file",
-                ));
+    /// Starts a new TShark process printing only the metadata given to metadata_whitelist(), as a table (-T fields).
+    ///
+    /// This is much faster than [RTSharkBuilderReady::spawn] when only a few metadata are needed:
+    /// TShark does not build the whole packet details and rtshark does not parse XML.
+    /// Each row holds one column per whitelisted metadata, in the same order. Each column holds all occurrences
+    /// of the metadata in the packet. The metadata blacklist and output format are ignored.
+    ///
+    /// This function fails if no metadata whitelist has been given.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let builder = rtshark::RTSharkBuilder::builder()
+    ///     .input_path("/tmp/my.pcap")
+    ///     .metadata_whitelist("ip.src")
+    ///     .metadata_whitelist("tcp.dstport");
+    ///
+    /// let mut reader = match builder.spawn_fields() {
+    ///     Err(err) => { eprintln!("Error running tshark: {err}"); return; }
+    ///     Ok(reader) => reader,
+    /// };
+    ///
+    /// while let Some(row) = reader.read().unwrap_or_else(|e| {
+    ///     eprintln!("Error parsing TShark output: {e}");
+    ///     None
+    /// }) {
+    ///     println!("{:?} -> {:?}", row.values("ip.src"), row.values("tcp.dstport"));
+    /// }
+    /// ```
+    pub fn spawn_fields(&self) -> Result<FieldsReader> {
+        let columns = match &self.metadata_whitelist {
+            Some(wl) if !wl.is_empty() => wl.clone(),
+            _ => {
+                return Err(RTSharkError::invalid_argument(
+                    "metadata_whitelist is required to spawn TShark with fields output",
+                ))
             }
-
-            // test if input file exists
-            let input_path = self.input_path[0];
-            std::fs::metadata(input_path).map_err(|e| match e.kind() {
-                std::io::ErrorKind::NotFound => {
-                    std::io::Error::new(e.kind(), format!("Unable to find {}: {}", input_path, e))
-                }
-                _ => e,
-            })?;
-
-            vec!["-r", input_path]
         };
 
+        let mut tshark_params = self.prepare_args()?;
+
         tshark_params.extend(&[
-            // Disable network object name resolution (such as hostname, TCP and UDP port names)
-            "-n",
-            // When capturing packets, TShark writes to the standard error an initial line listing the interfaces from which packets are being captured and,
-            // if packet information isn’t being displayed to the terminal, writes a continuous count of packets captured to the standard output.
-            // If the -Q option is specified, neither the initial line, nor the packet information, nor any packet counts will be displayed.
-            "-Q",
+            "-Tfields",
+            "-E",
+            "header=n",
+            "-E",
+            "separator=/t",
+            // keep all occurrences of a field, separated with a character not expected in values
+            "-E",
+            "occurrence=a",
+            "-E",
+            fields::AGGREGATOR_OPTION,
+            "-E",
+            "quote=d",
+            "-l",
         ]);
 
-        if !self.output_path.is_empty() {
-            tshark_params.extend(&["-w", self.output_path]);
-        }
+        let mut tshark_child = self.spawn_tshark(&tshark_params)?;
 
-        if self.live_capture && !self.capture_filter.is_empty() {
-            tshark_params.extend(&["-f", self.capture_filter]);
-        }
-
-        if !self.display_filter.is_empty() {
-            tshark_params.extend(&["-Y", self.display_filter]);
-        }
-
-        if !self.decode_as.is_empty() {
-            for elm in self.decode_as.iter() {
-                tshark_params.extend(&["-d", elm]);
-            }
-        }
-
-        for option in &self.options {
-            tshark_params.extend(&["-o", option]);
-        }
-
-        if let Some(wl) = &self.metadata_whitelist {
-            for whitelist_elem in wl {
-                tshark_params.extend(&["-e", whitelist_elem]);
-            }
-        }
-
-        for protocol in &self.disabled_protocols {
-            tshark_params.extend(&["--disable-protocol", protocol]);
-        }
-
-        for protocol in &self.enabled_protocols {
-            tshark_params.extend(&["--enable-protocol", protocol]);
-        }
-
-        Ok(tshark_params)
-    }
-}
-
-/// RTShark structure represents a TShark process.
-/// It allows controlling the TShark process and reading from application's output.
-/// It is created by [RTSharkBuilder].
-pub struct RTShark {
-    /// Contains the TShark process handle, when TShark is running
-    process: Option<Child>,
-    /// xml parser on TShark piped output
-    parser: quick_xml::Reader<BufReader<ChildStdout>>,
-    /// stderr
-    stderr: BufReader<ChildStderr>,
-    /// optional metadata blacklist, to prevent storing useless metadata in output packet structure
-    filters: Vec<String>,
-}
+        let stdout = BufReader::new(tshark_child.stdout.take().unwrap());
+        let stderr = StderrReader::new(tshark_child.stderr.take().unwrap());
 
-impl RTShark {
-    /// create a new RTShark instance from a successful builder call.
-    fn new(
-        process: Child,
-        parser: quick_xml::Reader<BufReader<ChildStdout>>,
-        stderr: BufReader<ChildStderr>,
-        filters: Vec<String>,
-    ) -> Self {
-        RTShark {
-            process: Some(process),
-            parser,
+        Ok(FieldsReader::new(
+            tshark_child,
+            stdout,
             stderr,
-            filters,
-        }
+            columns,
+            self.graceful_drop,
+            self.input_error(),
+        ))
     }
 
-    /// Read a packet from thsark output and map it to the [Packet] type.
-    /// Reading packet can be done until 'None' is returned.
-    /// Once 'None' is returned, no more packets can be read from this stream
-    /// and TShark instance can be dropped.
-    /// This could happen when TShark application dies or when this is the end of the PCAP file.
+    /// Dissects the input file with several TShark processes running in parallel, all using
+    /// the parameters of this builder (filters, whitelist, output format ...).
+    ///
+    /// The file is split according to [SplitMode]: in chunks of packets with editcap, or by TCP
+    /// streams. Packets are delivered in their original order (see [Packet::frame_number]),
+    /// unless [ParallelOptions::unordered] is used. frame.number is added to the metadata
+    /// whitelist, if any: packets are merged and renumbered with their frame number.
     ///
     /// # Example
     ///
     /// ```
-    /// # // Creates a builder with needed TShark parameters
-    /// # let builder = rtshark::RTSharkBuilder::builder()
-    /// #     .input_path("/tmp/my.pcap");
-    /// // Start a new TShark process
-    /// let mut rtshark = match builder.spawn() {
-    ///     Err(err) => { eprintln!("Error running tshark: {err}"); return; }
-    ///     Ok(rtshark) => rtshark
-    /// };
+    /// use rtshark::{ParallelOptions, SplitMode};
     ///
-    /// // read packets until the end of the PCAP file
-    /// loop {
-    ///     let packet = match rtshark.read() {
-    ///         Ok(p) => p,
-    ///         Err(e) => { eprintln!("Got decoding error: {e}"); continue; }
-    ///     };
+    /// let builder = rtshark::RTSharkBuilder::builder()
+    ///     .input_path("/tmp/my.pcap")
+    ///     .display_filter("udp");
     ///
-    ///     // end of stream
-    ///     if let None = packet {
-    ///         break;
-    ///     }
+    /// let options = ParallelOptions::new(SplitMode::Packets(100_000));
+    /// let reader = match builder.spawn_parallel(&options) {
+    ///     Err(err) => { eprintln!("Error running tshark: {err}"); return; }
+    ///     Ok(reader) => reader,
+    /// };
     ///
-    ///     println!("Got a packet");
+    /// for packet in reader.filter_map(|packet| packet.ok()) {
+    ///     println!("Got packet {:?}", packet.frame_number());
     /// }
     /// ```
-    pub fn read(&mut self) -> Result<Option<Packet>> {
-        let xml_reader = &mut self.parser;
-
-        let msg = parse_xml(xml_reader, &self.filters);
-        if let Ok(ref msg) = msg {
-            let done = match msg {
-                None => {
-                    // Got None == EOF
-                    match self.process {
-                        Some(ref mut process) => RTShark::try_wait_has_exited(process),
-                        _ => true,
-                    }
-                }
-                _ => false,
-            };
-
-            if done {
-                self.process = None;
-
-                // if process stops, there may be due to an error, we can get it in stderr
-                let mut line = String::new();
-                let size = self.stderr.read_line(&mut line)?;
-                // if len is != 0 there is an error message
-                if size != 0 {
-                    return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, line));
-                }
-            }
-        }
-
-        msg
+    pub fn spawn_parallel(&self, options: &ParallelOptions) -> Result<ParallelReader<'a>> {
+        ParallelReader::spawn(self, options)
     }
 
-    /// Kill the running TShark process associated to this rtshark instance.
-    /// Once TShark is killed, there is no way to start it again using this object.
-    /// Any new TShark instance has to be created using RTSharkBuilder.
+    /// Starts a new TShark process dissecting packets given as bytes, one by one, see [Dissector].
+    ///
+    /// TShark keeps running and captures packets from its standard input, like a pipe given to
+    /// live_capture(): the input path is ignored. Other parameters of this builder are used
+    /// (metadata whitelist and blacklist, decode_as, output format ...), except the display and
+    /// capture filters which are rejected, since each packet must be printed. Packets are matched
+    /// with their frame number: frame.number is added to the metadata whitelist, if any.
     ///
     /// # Example
     ///
     /// ```
-    /// // Creates a builder with needed TShark parameters
     /// let builder = rtshark::RTSharkBuilder::builder()
-    ///     .input_path("/tmp/my.pcap");
+    ///     .input_path("-")
+    ///     .decode_as("udp.port==9999,rtp");
     ///
-    /// // Start a new TShark process
-    /// let mut rtshark = match builder.spawn() {
+    /// let mut dissector = match builder.spawn_dissector() {
     ///     Err(err) => { eprintln!("Error running tshark: {err}"); return; }
-    ///     Ok(rtshark) => rtshark
+    ///     Ok(dissector) => dissector,
     /// };
     ///
-    /// // kill running TShark process
-    /// rtshark.kill();
+    /// // LINKTYPE_RAW: the packet starts with its IP header
+    /// let ip_packet = [0x45, 0x00, 0x00, 0x14, 0, 0, 0, 0, 64, 17, 0, 0, 127, 0, 0, 1, 127, 0, 0, 1];
+    /// if let Ok(packet) = dissector.dissect(101, std::time::SystemTime::now(), &ip_packet) {
+    ///     println!("Got packet {:?}", packet.frame_number());
+    /// }
     /// ```
-    pub fn kill(&mut self) {
-        if let Some(ref mut process) = self.process {
-            let done = match process.try_wait() {
-                Ok(maybe) => match maybe {
-                    None => false,
-                    Some(_exitcode) => true,
-                },
-                Err(e) => {
-                    eprintln!("Error while killing rtshark: wait: {e}");
-                    false
-                }
-            };
-
-            if !done {
-                match process.kill() {
-                    Ok(()) => (),
-                    Err(e) => eprintln!("Error while killing rtshark: kill: {e}"),
-                }
-                if let Err(e) = process.wait() {
-                    eprintln!("Error while killing rtshark: wait: {e}");
-                }
-            }
-
-            self.process = None;
-        }
+    pub fn spawn_dissector(&self) -> Result<Dissector> {
+        Dissector::spawn(self)
     }
 
-    /// Returns tshark process id if tshark is running.
+    /// Starts a new TShark process printing a one-line summary of each packet (-T psml),
+    /// like the packet list of Wireshark: number, time, source, destination, protocol, length and info.
+    ///
+    /// This is faster than [RTSharkBuilderReady::spawn] as the packet details are not printed.
+    /// The metadata whitelist, blacklist and output format are ignored.
+    ///
     /// # Example
     ///
     /// ```
-    /// // Creates a builder with needed tshark parameters
     /// let builder = rtshark::RTSharkBuilder::builder()
     ///     .input_path("/tmp/my.pcap");
     ///
-    /// // Start a new tshark process
-    /// let mut rtshark = match builder.spawn() {
+    /// let mut reader = match builder.spawn_summary() {
     ///     Err(err) => { eprintln!("Error running tshark: {err}"); return; }
-    ///     Ok(rtshark) => println!("tshark PID is {}", rtshark.pid().unwrap())
+    ///     Ok(reader) => reader,
     /// };
     ///
+    /// while let Ok(Some(summary)) = reader.read() {
+    ///     println!("{:?}", summary.column("Info"));
+    /// }
     /// ```
-    pub fn pid(&self) -> Option<u32> {
-        self.process.as_ref().map(|p| p.id())
-    }
+    pub fn spawn_summary(&self) -> Result<SummaryReader> {
+        // "-e" options are not valid with psml output
+        let mut tshark_params = self.prepare_args_without_whitelist()?;
 
-    /// Check if process is stopped, get the exit code and return true if stopped.
-    /// Why not doing a simple wait ?
-    fn try_wait_has_exited(child: &mut Child) -> bool {
-        let mut count = 3;
-        while count != 0 {
-            #[cfg(target_family = "unix")]
-            if let Ok(Some(s)) = child.try_wait() {
-                return s.code().is_some() || s.signal().is_some();
-            }
+        // -l activate unbuffered mode, useful to print packets as they come
+        tshark_params.extend(&["-Tpsml", "-l"]);
 
-            #[cfg(target_family = "windows")]
-            if let Ok(Some(s)) = child.try_wait() {
-                return s.code().is_some();
-            }
+        let mut tshark_child = self.spawn_tshark(&tshark_params)?;
 
-            std::thread::sleep(std::time::Duration::from_millis(100));
-            count -= 1;
-        }
+        let buf_reader = BufReader::new(tshark_child.stdout.take().unwrap());
+        let stderr = StderrReader::new(tshark_child.stderr.take().unwrap());
 
-        false
-    }
-}
+        let reader = quick_xml::Reader::from_reader(buf_reader);
 
-impl Drop for RTShark {
-    fn drop(&mut self) {
-        self.kill()
+        Ok(SummaryReader::new(
+            tshark_child,
+            reader,
+            stderr,
+            self.graceful_drop,
+            self.input_error(),
+        ))
     }
-}
 
-/// search for an attribute of a XML tag using its name and return a string.
-fn rtshark_attr_by_name(tag: &BytesStart, key: &[u8]) -> Result<String> {
-    let attrs = &mut tag.attributes();
-    for attr in attrs {
-        let attr = attr.map_err(|e| {
-            std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                format!("Error decoding xml attribute: {e:?}"),
-            )
-        })?;
-        if attr.key.as_ref() == key {
-            let value = std::str::from_utf8(&attr.value).map_err(|e| {
-                std::io::Error::new(
-                    std::io::ErrorKind::InvalidData,
-                    format!("Error decoding utf8 value: {e:?}"),
-                )
-            })?;
-            return Ok(value.to_owned());
-        }
-    }
+    /// Runs TShark to completion with a statistics tap (-q -z <tap>) and returns its text output.
+    /// Packets are not printed. The metadata whitelist, blacklist and output format are ignored.
+    ///
+    /// Typed results are provided for common taps by [RTSharkBuilderReady::conversations],
+    /// [RTSharkBuilderReady::endpoints], [RTSharkBuilderReady::protocol_hierarchy],
+    /// [RTSharkBuilderReady::io_statistics], [RTSharkBuilderReady::stats_tree] and
+    /// [RTSharkBuilderReady::expert_info].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let builder = rtshark::RTSharkBuilder::builder()
+    ///     .input_path("/tmp/my.pcap");
+    ///
+    /// match builder.statistics("smb,srt") {
+    ///     Ok(output) => println!("{output}"),
+    ///     Err(err) => eprintln!("Error running tshark: {err}"),
+    /// }
+    /// ```
+    pub fn statistics(&self, tap: &str) -> Result<String> {
+        let mut tshark_params = self.prepare_args_without_whitelist()?;
 
-    let line =
-        std::str::from_utf8(tag.attributes_raw()).unwrap_or("Unable to decode UTF8 XML buffer");
+        tshark_params.extend(&["-q", "-z", tap]);
 
-    Err(std::io::Error::new(
-        std::io::ErrorKind::InvalidInput,
-        format!(
-            "xml lookup error: no key '{}' in '{}'",
-            std::str::from_utf8(key).unwrap(),
-            line
-        ),
-    ))
-}
+        self.run_tshark_output(&tshark_params)
+    }
 
-/// search for an attribute of a XML tag using its name and return a u32.
-fn rtshark_attr_by_name_u32(tag: &BytesStart, key: &[u8]) -> Result<u32> {
-    match rtshark_attr_by_name(tag, key) {
-        Err(e) => Err(e),
-        Ok(v) => v.parse::<u32>().map_err(|e| {
-            std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                format!("Error decoding u32 value: {e:?}"),
-            )
-        }),
+    /// Get the conversations between endpoints (-z conv,<type>).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let builder = rtshark::RTSharkBuilder::builder()
+    ///     .input_path("/tmp/my.pcap");
+    ///
+    /// if let Ok(conversations) = builder.conversations(rtshark::ConversationType::Tcp) {
+    ///     for c in conversations {
+    ///         println!("{} <-> {}: {} bytes", c.address_a(), c.address_b(), c.bytes());
+    ///     }
+    /// }
+    /// ```
+    pub fn conversations(&self, conversation_type: ConversationType) -> Result<Vec<Conversation>> {
+        let output = self.statistics(&format!("conv,{}", conversation_type.tap_name()))?;
+        stats::parse_conversations(&output)
     }
-}
 
-/// Build a metadata using attributes available on this XML "field" tag.
-/// Sample XML line : <field name="frame.time" show="test time" pos="0" size="0" showname="test time display"/>
-fn rtshark_build_metadata(tag: &BytesStart, filters: &[String]) -> Result<Option<Metadata>> {
-    let name = rtshark_attr_by_name(tag, b"name")?;
+    /// Get the traffic statistics of each endpoint (-z endpoints,<type>).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let builder = rtshark::RTSharkBuilder::builder()
+    ///     .input_path("/tmp/my.pcap");
+    ///
+    /// if let Ok(endpoints) = builder.endpoints(rtshark::ConversationType::Ip) {
+    ///     for e in endpoints {
+    ///         println!("{}: {} packets", e.address(), e.packets());
+    ///     }
+    /// }
+    /// ```
+    pub fn endpoints(&self, endpoint_type: ConversationType) -> Result<Vec<Endpoint>> {
+        let output = self.statistics(&format!("endpoints,{}", endpoint_type.tap_name()))?;
+        stats::parse_endpoints(&output)
+    }
 
-    // skip "_ws.expert" info, not related to a packet metadata
-    if name.is_empty() || name.starts_with("_ws.") {
-        return Ok(None);
+    /// Get the protocol hierarchy (-z io,phs). Top level protocols are returned, with encapsulated protocols as children.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let builder = rtshark::RTSharkBuilder::builder()
+    ///     .input_path("/tmp/my.pcap");
+    ///
+    /// if let Ok(hierarchy) = builder.protocol_hierarchy() {
+    ///     for p in hierarchy {
+    ///         println!("{}: {} frames", p.protocol(), p.frames());
+    ///     }
+    /// }
+    /// ```
+    pub fn protocol_hierarchy(&self) -> Result<Vec<ProtocolStats>> {
+        let output = self.statistics("io,phs")?;
+        stats::parse_protocol_hierarchy(&output)
     }
 
-    // skip data
-    if filters.contains(&name) {
-        return Ok(None);
+    /// Get the number of frames and bytes per time interval (-z io,stat,<interval>).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let builder = rtshark::RTSharkBuilder::builder()
+    ///     .input_path("/tmp/my.pcap");
+    ///
+    /// if let Ok(intervals) = builder.io_statistics(std::time::Duration::from_secs(1)) {
+    ///     for i in intervals {
+    ///         println!("{:?}: {} frames", i.start(), i.frames());
+    ///     }
+    /// }
+    /// ```
+    pub fn io_statistics(&self, interval: std::time::Duration) -> Result<Vec<IoInterval>> {
+        let output = self.statistics(&format!("io,stat,{}", interval.as_secs_f64()))?;
+        stats::parse_io_statistics(&output)
     }
 
-    // Issue #1 : uses pyshark-like algorithm to display the best 'value' for this field
-    // https://github.com/KimiNewt/pyshark/blob/master/src/pyshark/packet/fields.py#L14
-    // try first "show", then "value", finally "showname"
-    let value = match rtshark_attr_by_name(tag, b"show") {
-        Ok(value) => Ok(value),
-        Err(err) if err.kind() == std::io::ErrorKind::InvalidInput => {
-            match rtshark_attr_by_name(tag, b"value") {
-                Ok(value) => Ok(value),
-                Err(err) if err.kind() == std::io::ErrorKind::InvalidInput => {
-                    if let Ok(value) = rtshark_attr_by_name(tag, b"showname") {
-                        Ok(value)
-                    } else {
-                        Err(err)
-                    }
-                }
-                Err(err) => Err(err),
-            }
-        }
-        Err(err) => Err(err),
-    }?;
+    /// Get a statistics tree (-z <name>,tree), like "http" or "dns".
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let builder = rtshark::RTSharkBuilder::builder()
+    ///     .input_path("/tmp/my.pcap");
+    ///
+    /// if let Ok(tree) = builder.stats_tree("dns") {
+    ///     for item in tree.items() {
+    ///         println!("{}: {}", item.topic(), item.count());
+    ///     }
+    /// }
+    /// ```
+    pub fn stats_tree(&self, name: &str) -> Result<StatsTree> {
+        let output = self.statistics(&format!("{},tree", name))?;
+        stats::parse_stats_tree(&output)
+    }
 
-    let mut metadata = Metadata::new(name, value, None, None, None);
+    /// Get the expert information, aggregated over packets (-z expert).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let builder = rtshark::RTSharkBuilder::builder()
+    ///     .input_path("/tmp/my.pcap");
+    ///
+    /// if let Ok(infos) = builder.expert_info() {
+    ///     for info in infos {
+    ///         println!("{:?} {}: {}", info.severity(), info.protocol(), info.summary());
+    ///     }
+    /// }
+    /// ```
+    pub fn expert_info(&self) -> Result<Vec<ExpertInfo>> {
+        let output = self.statistics("expert")?;
+        stats::parse_expert(&output)
+    }
 
-    if let Ok(position) = rtshark_attr_by_name_u32(tag, b"pos") {
-        metadata.position = Some(position);
-    }
-    if let Ok(size) = rtshark_attr_by_name_u32(tag, b"size") {
-        metadata.size = Some(size);
-    }
-    if let Ok(display) = rtshark_attr_by_name(tag, b"showname") {
-        metadata.display = Some(display);
+    /// Get the payload of a stream, reassembled by TShark (-z follow,<protocol>,<mode>,<index>).
+    ///
+    /// Retransmissions and out of order segments are handled by TShark. The stream index
+    /// is the value of "tcp.stream" (or "udp.stream") metadata of the stream packets.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rtshark::{FollowMode, FollowProtocol, StreamDirection};
+    ///
+    /// let builder = rtshark::RTSharkBuilder::builder()
+    ///     .input_path("/tmp/my.pcap");
+    ///
+    /// if let Ok(stream) = builder.follow_stream(FollowProtocol::Tcp, 0, FollowMode::Yaml) {
+    ///     for chunk in stream.chunks() {
+    ///         let from = match chunk.direction() {
+    ///             StreamDirection::ClientToServer => stream.client(),
+    ///             StreamDirection::ServerToClient => stream.server(),
+    ///         };
+    ///         println!("frame {:?}: {} bytes from {}", chunk.frame_number(), chunk.data().len(), from);
+    ///     }
+    /// }
+    /// ```
+    pub fn follow_stream(
+        &self,
+        protocol: FollowProtocol,
+        stream_index: u64,
+        mode: FollowMode,
+    ) -> Result<FollowedStream> {
+        let output = self.statistics(&format!(
+            "follow,{},{},{}",
+            protocol.tap_name(),
+            mode.tap_name(),
+            stream_index
+        ))?;
+
+        match mode {
+            FollowMode::Raw => follow::parse_follow_raw(&output),
+            FollowMode::Yaml => follow::parse_follow_yaml(&output),
+        }
     }
-    if let Ok(raw_value) = rtshark_attr_by_name(tag, b"value") {
-        if raw_value != metadata.value {
-            metadata.raw_value = Some(raw_value);
+
+    /// Extract objects (files, emails, HTTP bodies ...) transferred with a protocol to a directory
+    /// (--export-objects <protocol>,<dir>), and returns the list of written files.
+    ///
+    /// The directory is created if needed. File names are chosen by TShark, and existing files are not
+    /// replaced. Objects are first exported to a private directory, so that only the files of this
+    /// run are listed. Frame number, hostname and content type are found for HTTP objects only, on a
+    /// best-effort basis.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let builder = rtshark::RTSharkBuilder::builder()
+    ///     .input_path("/tmp/my.pcap");
+    ///
+    /// if let Ok(objects) = builder.export_objects(rtshark::ExportProtocol::Http, "/tmp/objects") {
+    ///     for o in objects {
+    ///         println!("{}: {} bytes of {:?}", o.path().display(), o.size(), o.content_type());
+    ///     }
+    /// }
+    /// ```
+    pub fn export_objects(
+        &self,
+        protocol: ExportProtocol,
+        dir: &str,
+    ) -> Result<Vec<ExportedObject>> {
+        let dir_path = std::path::Path::new(dir);
+        std::fs::create_dir_all(dir_path)?;
+        // objects are moved to dir once TShark has exported them
+        let export_dir = export::ExportDir::create(dir_path)?;
+
+        let mut tshark_params = self.prepare_args_without_whitelist()?;
+
+        // the export directory name is valid UTF-8, like dir
+        let export_option = format!(
+            "{},{}",
+            protocol.name(),
+            export_dir.path().to_string_lossy()
+        );
+        tshark_params.extend(&["--export-objects", &export_option]);
+
+        // describe HTTP objects with their response
+        if protocol == ExportProtocol::Http {
+            // URIs and content types may contain commas, the default aggregator
+            tshark_params.extend(&[
+                "-Tfields",
+                "-E",
+                "separator=/t",
+                "-E",
+                fields::AGGREGATOR_OPTION,
+            ]);
+            for field in export::HTTP_FIELDS {
+                tshark_params.extend(&["-e", field]);
+            }
+        } else {
+            tshark_params.push("-q");
         }
+
+        let output = self.run_tshark_output(&tshark_params)?;
+
+        let responses = match protocol {
+            ExportProtocol::Http => export::parse_http_responses(&output),
+            _ => vec![],
+        };
+
+        export::exported_objects(&export_dir, dir_path, &responses)
     }
-    Ok(Some(metadata))
-}
 
-/// Process specific metadata in geninfo to fill the packet structure
-fn geninfo_metadata(tag: &BytesStart, packet: &mut Packet) -> Result<()> {
-    use chrono::{LocalResult, TimeZone as _, Utc};
+    /// Starts a new TShark process given the provided parameters, read asynchronously with tokio.
+    /// Available with the "tokio" feature. Only PDML output is supported: an InvalidArgument error is
+    /// returned if another output format, or recover_xml_errors, is used.
+    ///
+    /// Must be called from a tokio runtime. TShark is killed when the returned [AsyncRTShark] is dropped.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # async fn run() {
+    /// let builder = rtshark::RTSharkBuilder::builder()
+    ///     .input_path("/tmp/my.pcap");
+    ///
+    /// let mut rtshark = match builder.spawn_async() {
+    ///     Err(err) => { eprintln!("Error running tshark: {err}"); return; }
+    ///     Ok(rtshark) => rtshark,
+    /// };
+    ///
+    /// while let Ok(Some(packet)) = rtshark.read().await {
+    ///     println!("Got a packet");
+    /// }
+    /// # }
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub fn spawn_async(&self) -> Result<AsyncRTShark> {
+        if self.output_format != OutputFormat::Pdml {
+            return Err(RTSharkError::invalid_argument(
+                "spawn_async only supports the PDML output format",
+            ));
+        }
+        if self.recover_xml_errors {
+            return Err(RTSharkError::invalid_argument(
+                "recover_xml_errors is not supported by spawn_async",
+            ));
+        }
 
-    let name = rtshark_attr_by_name(tag, b"name")?;
-    if name != "timestamp" {
-        return Ok(());
+        let mut tshark_params = self.prepare_args()?;
+
+        // -l activate unbuffered mode, useful to print packets as they come
+        tshark_params.extend(&["-Tpdml", "-l"]);
+
+        let reader = match &self.input_reader {
+            Some(input_reader) => Some(input_reader.take()?),
+            None => None,
+        };
+        let mut command = tokio::process::Command::from(self.tshark_command(&tshark_params));
+        if reader.is_some() {
+            command.stdin(Stdio::piped());
+        }
+        let mut tshark_child = command
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(tshark_spawn_error)?;
+
+        if let Some(reader) = reader {
+            // the reader is blocking: it is copied by a thread, not by the tokio runtime
+            let stdin = tshark_child.stdin.take().unwrap();
+            #[cfg(target_family = "unix")]
+            let stdin = std::fs::File::from(stdin.into_owned_fd()?);
+            #[cfg(target_family = "windows")]
+            let stdin = std::fs::File::from(stdin.into_owned_handle()?);
+            input::write_input(reader, stdin, self.input_error().unwrap_or_default())?;
+        }
+
+        let mut rtshark = AsyncRTShark::new(tshark_child, self.metadata_blacklist.clone());
+        rtshark.input_error = self.input_error();
+
+        Ok(rtshark)
     }
-    let value = rtshark_attr_by_name(tag, b"value")?;
 
-    let bad_timestamp = || {
-        std::io::Error::new(
-            std::io::ErrorKind::InvalidData,
-            format!("Error decoding timestamp: {value}"),
-        )
-    };
+    /// Starts a new TShark process given the provided parameters and runs it to completion. In
+    /// contrast to [`RTSharkBuilderReady::spawn` ]no programmatic access to individual packets is
+    /// provided.
+    /// This function may fail if tshark binary is not in PATH or if there are some issues with input_path parameter : not found or no read permission...
+    /// In other cases (output_path not writable, invalid syntax for pcap_filter or display_filter),
+    /// TShark process will fail and the stderr will be reported.
+    /// # Example
+    ///
+    /// ```
+    /// let builder = rtshark::RTSharkBuilder::builder()
+    ///     .input_path("/tmp/my.pcap");
+    /// let _: Result<(), rtshark::RTSharkError> = builder.batch();
+    /// ```
+    pub fn batch(&self) -> Result<()> {
+        let tshark_params = self.prepare_args()?;
 
-    let (secs, nsecs) = value.split_once('.').ok_or_else(bad_timestamp)?;
-    let secs = secs.parse().map_err(|_| bad_timestamp())?;
-    let nsecs = nsecs.parse().map_err(|_| bad_timestamp())?;
+        // packets printed by TShark are not used: they are discarded instead of kept in memory
+        let mut command = self.tshark_command(&tshark_params);
+        command.stdout(Stdio::null());
+        self.run_tshark(command)?;
 
-    let LocalResult::Single(dt) = Utc.timestamp_opt(secs, nsecs) else {
-        return Err(bad_timestamp());
-    };
-    packet.timestamp_micros.replace(dt.timestamp_micros());
+        Ok(())
+    }
 
-    Ok(())
-}
+    /// Run TShark to completion and return its standard output.
+    fn run_tshark_output(&self, tshark_params: &[&str]) -> Result<String> {
+        let output = self.run_tshark(self.tshark_command(tshark_params))?;
 
-/// list of protocols in tshark output but not in packet data
-fn ignored_protocols(name: &str) -> bool {
-    name.eq("geninfo") || name.eq("fake-field-wrapper")
-}
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
 
-/// Main parser function used to decode XML output from tshark
-fn parse_xml<B: BufRead>(
-    xml_reader: &mut quick_xml::Reader<B>,
-    filters: &[String],
-) -> Result<Option<Packet>> {
-    let mut buf = vec![];
-    let mut packet = Packet::new();
+    /// Run a TShark command to completion, reading its piped outputs while it runs, so that it
+    /// never blocks on a full pipe.
+    fn run_tshark(&self, command: Command) -> Result<std::process::Output> {
+        let tshark_child = self.spawn_tshark_command(command)?;
+        let output = tshark_child.wait_with_output()?;
+        if let Some(input_error) = self.input_error() {
+            input_error.check()?;
+        }
+        stderr::check_output(&output)?;
 
-    let mut protoname = None;
+        Ok(output)
+    }
 
-    // tshark pdml is something like : (default mode)
-    //
-    // <!-- You can find pdml2html.xsl in /usr/share/wireshark or at https://gitlab.com/wireshark/wireshark/-/raw/master/pdml2html.xsl. -->
-    // <pdml version="0" creator="wireshark/4.0.6" time="Sat Oct  7 09:51:54 2023" capture_file="src/test.pcap">
-    // <packet>
-    //   <proto name="geninfo" pos="0" showname="General information" size="28">
-    //     <field name="num" pos="0" show="1" showname="Number" value="1" size="28"/>
-    //   </proto>
-    //   <proto name="frame" pos="0" showname="General information" size="28">
-    //   ...
-    //
-    // or, if using "whitelist" with -e option
-    //
-    // <pdml version="0" creator="wireshark/4.0.6" time="Sat Oct  7 09:51:54 2023" capture_file="src/test.pcap">
-    // <packet>
-    //   <proto name="geninfo" pos="0" showname="General information" size="28">
-    //     <field name="num" pos="0" show="1" showname="Number" value="1" size="28"/>
-    // </proto>
-    // <field name="num" pos="0" show="1" showname="Number" value="1" size="28"/>
-    // ...
+    fn spawn_tshark(&self, tshark_params: &[&str]) -> Result<Child> {
+        // piping from TShark, not to load the entire output in ram...
+        self.spawn_tshark_command(self.tshark_command(tshark_params))
+    }
 
-    /// Create a new layer if required and add metadata to the given packet.
-    fn _add_metadata(packet: &mut Packet, metadata: Metadata) -> Result<()> {
-        // Create a new layer if the field's protocol does not exist yet as a layer.
-        if let Some(proto) = metadata.name().split('.').next() {
-            packet.push_if_not_exist(proto.to_owned());
+    /// Spawn a TShark command, writing input_reader to its standard input if any.
+    fn spawn_tshark_command(&self, mut command: Command) -> Result<Child> {
+        // spawn may fail if TShark is not found in path
+        let Some(input_reader) = &self.input_reader else {
+            return command.spawn().map_err(tshark_spawn_error);
+        };
+
+        let reader = input_reader.take()?;
+        let mut tshark_child = command
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(tshark_spawn_error)?;
+        let stdin = tshark_child.stdin.take().unwrap();
+        if let Err(e) = input::write_input(reader, stdin, input_reader.error()) {
+            let _ = tshark_child.kill();
+            return Err(e);
         }
 
-        if let Some(layer) = packet.last_layer_mut() {
-            layer.add(metadata);
-        } else {
-            return Err(Error::new(
-                ErrorKind::InvalidData,
-                "Cannot find protocol name to push a metadata",
-            ));
+        Ok(tshark_child)
+    }
+
+    /// Get the error slot of input_reader, reporting a failed read at EOF.
+    fn input_error(&self) -> Option<input::InputError> {
+        self.input_reader.as_ref().map(input::InputReader::error)
+    }
+
+    /// Prepare the TShark command, with piped outputs.
+    fn tshark_command(&self, tshark_params: &[&str]) -> Command {
+        tshark_command(tshark_params, self.env_path, self.kill_on_parent_death)
+    }
+
+    /// Prepare tshark command line parameters.
+    fn prepare_args(&self) -> Result<Vec<&str>> {
+        let mut tshark_params = self.prepare_args_without_whitelist()?;
+
+        if let Some(wl) = &self.metadata_whitelist {
+            for whitelist_elem in wl {
+                tshark_params.extend(&["-e", whitelist_elem]);
+            }
         }
 
-        Ok(())
+        Ok(tshark_params)
     }
 
-    loop {
-        match xml_reader.read_event_into(&mut buf) {
-            Ok(Event::Start(ref e)) => {
-                // Here we have "packet" and "proto" and sometimes "field" tokens. Only "proto" and "field" are interesting today.
-                if b"proto" == e.name().as_ref() {
-                    let proto = rtshark_attr_by_name(e, b"name")?;
-                    protoname = Some(proto.to_owned());
+    /// Add frame.number to the metadata whitelist, if any, so that packets keep their frame
+    /// number. JSON and EK packets take it from the frame layer, PDML packets from geninfo.
+    pub(crate) fn with_frame_number(&self) -> Self {
+        let mut new = self.clone();
+        if let (OutputFormat::Json | OutputFormat::Ek, Some(wl)) =
+            (new.output_format, &mut new.metadata_whitelist)
+        {
+            if !wl.iter().any(|name| name == "frame.number") {
+                wl.push("frame.number".to_owned());
+            }
+        }
+        new
+    }
 
-                    // If we face a new protocol, add it in the packet layers stack.
-                    if !ignored_protocols(proto.as_str()) {
-                        packet.push(proto);
-                    }
-                }
+    /// Prepare tshark command line parameters, except the metadata whitelist.
+    fn prepare_args_without_whitelist(&self) -> Result<Vec<&str>> {
+        let mut tshark_params = if self.live_capture {
+            let mut input = vec![];
+            self.input_path
+                .iter()
+                .for_each(|i| input.extend(&["-i", i]));
+            input
+        } else {
+            // several files are read by several TShark processes, see spawn()
+            if self.input_path.len() != 1 || !self.input_globs.is_empty() {
+                return Err(RTSharkError::invalid_argument(
+                    "tshark supports only one input pcap file",
+                ));
+            }
 
-                // There are cases where fields are mapped in fields. So check if there is any parent field and extract its metadata.
-                if b"field" == e.name().as_ref() {
-                    if let Some(metadata) = rtshark_build_metadata(e, filters)? {
-                        _add_metadata(&mut packet, metadata)?;
-                    }
-                }
+            // test if input file exists ("-" is the standard input)
+            let input_path = self.input_path[0];
+            if input_path != "-" {
+                input::check_input_file(input_path)?;
             }
-            Ok(Event::Empty(ref e)) => {
-                // Here we should not have anything else than "field" but do a test anyway.
-                if b"field" == e.name().as_ref() {
-                    // Here we have two cases : with or without encapsuling "proto"
-                    // We have a protocol if "whitelist" mode is disabled.
-                    // Protocol "geninfo" is always here.
-                    if let Some(name) = protoname.as_ref() {
-                        if name == "geninfo" {
-                            // Put geninfo metadata in packet's object (timestamp ...).
-                            geninfo_metadata(e, &mut packet)?;
-                        } else if let Some(metadata) = rtshark_build_metadata(e, filters)? {
-                            // Some dissectors place field items at the top level instead
-                            // of inside a protocol. In these cases, in the PDML output the
-                            // field items are placed inside a fake "<proto>" element named
-                            // "fake-field-wrapper" in order to maximize compliance.
-                            // See https://github.com/wireshark/wireshark/blob/master/doc/README.xml-output
-                            //
-                            // An example is "tcp.reassembled". We should try to add these
-                            // items to the correct layer so that they are accessible.
-                            if name == "fake-field-wrapper" {
-                                let proto_from_name =
-                                    metadata.name().split('.').next().unwrap_or("");
-                                let proto_layer = packet
-                                    .last_layer_mut()
-                                    .filter(|layer| layer.name == proto_from_name);
-                                if let Some(layer) = proto_layer {
-                                    layer.add(metadata);
-                                }
-                            } else {
-                                // We can unwrap because we must have a layer : it was pushed in Event::Start
-                                packet.last_layer_mut().unwrap().add(metadata);
-                            }
-                        }
-                    } else if let Some(metadata) = rtshark_build_metadata(e, filters)? {
-                        _add_metadata(&mut packet, metadata)?;
-                    }
-                }
+
+            vec!["-r", input_path]
+        };
+
+        tshark_params.extend(&[
+            // Disable network object name resolution (such as hostname, TCP and UDP port names)
+            "-n",
+            // When capturing packets, TShark writes to the standard error an initial line listing the interfaces from which packets are being captured and,
+            // if packet information isn’t being displayed to the terminal, writes a continuous count of packets captured to the standard output.
+            // If the -Q option is specified, neither the initial line, nor the packet information, nor any packet counts will be displayed.
+            "-Q",
+        ]);
+
+        if !self.output_path.is_empty() {
+            tshark_params.extend(&["-w", self.output_path]);
+        }
+
+        if self.live_capture && !self.capture_filter.is_empty() {
+            tshark_params.extend(&["-f", self.capture_filter]);
+        }
+
+        if !self.display_filter.is_empty() {
+            tshark_params.extend(&["-Y", self.display_filter]);
+        }
+
+        if !self.decode_as.is_empty() {
+            for elm in self.decode_as.iter() {
+                tshark_params.extend(&["-d", elm]);
             }
-            Ok(Event::End(ref e)) => match e.name().as_ref() {
-                b"packet" => return Ok(Some(packet)),
-                b"proto" => protoname = None,
-                _ => (),
-            },
+        }
+
+        for option in &self.options {
+            tshark_params.extend(&["-o", option]);
+        }
+
+        for protocol in &self.disabled_protocols {
+            tshark_params.extend(&["--disable-protocol", protocol]);
+        }
+
+        for protocol in &self.enabled_protocols {
+            tshark_params.extend(&["--enable-protocol", protocol]);
+        }
+
+        Ok(tshark_params)
+    }
+}
+
+/// Parser of TShark output, depending on the selected [OutputFormat].
+enum PacketParser {
+    /// xml parser for PDML output
+    Pdml(quick_xml::Reader<BufReader<PipeReader>>),
+    /// json parser for JSON and EK outputs
+    Json(json::JsonReader<BufReader<PipeReader>>),
+}
+
+impl PacketParser {
+    /// Create the parser of TShark output, for the given format.
+    fn new(output_format: OutputFormat, stdout: std::process::ChildStdout) -> Self {
+        let buf_reader = BufReader::new(PipeReader::new(stdout));
+
+        match output_format {
+            OutputFormat::Pdml => PacketParser::Pdml(quick_xml::Reader::from_reader(buf_reader)),
+            OutputFormat::Json => PacketParser::Json(json::JsonReader::new(buf_reader, false)),
+            OutputFormat::Ek => PacketParser::Json(json::JsonReader::new(buf_reader, true)),
+        }
+    }
+
+    /// Get TShark piped output.
+    fn stdout_mut(&mut self) -> &mut BufReader<PipeReader> {
+        match self {
+            PacketParser::Pdml(xml_reader) => xml_reader.get_mut(),
+            PacketParser::Json(json_reader) => json_reader.get_mut(),
+        }
+    }
+}
+
+/// PDML lines read one by one until the end of a packet, then parsed by a new XML parser.
+///
+/// Unlike the XML parser reading TShark output, it does not lose a partially received packet
+/// on timeout, and an XML error does not prevent parsing the next packets.
+#[derive(Default)]
+struct PdmlChunk {
+    /// lines of the current packet
+    lines: Vec<u8>,
+    /// start of the last line in lines
+    line_start: usize,
+    /// start of the "<packet>" line in lines
+    packet_start: Option<usize>,
+    /// position of lines in TShark output
+    offset: u64,
+    /// lines were read: the XML parser reading TShark output cannot be used anymore, as it has
+    /// not seen them
+    started: bool,
+}
+
+impl PdmlChunk {
+    /// Position of the current packet in TShark output, if its first line was read.
+    fn packet_offset(&self) -> Option<u64> {
+        self.packet_start.map(|start| self.offset + start as u64)
+    }
+
+    /// Drop the first bytes of lines.
+    fn consume(&mut self, len: usize) {
+        self.lines.drain(..len);
+        self.offset += len as u64;
+        self.line_start = self.line_start.saturating_sub(len);
+        self.packet_start = None;
+    }
+}
+
+/// Result of [RTShark::read_timeout].
+#[derive(Debug)]
+pub enum ReadOutcome {
+    /// A packet was read
+    Packet(Packet),
+    /// No complete packet was received before the timeout. A partially received packet is
+    /// kept, and returned by a next read.
+    Timeout,
+    /// End of stream: no more packets can be read
+    Eof,
+}
+
+/// RTShark structure represents a TShark process.
+/// It allows controlling the TShark process and reading from application's output.
+/// It is created by [RTSharkBuilder].
+pub struct RTShark {
+    /// TShark process
+    process: TsharkProcess,
+    /// parser on TShark piped output
+    parser: PacketParser,
+    /// stderr, read in background
+    stderr: StderrReader,
+    /// optional metadata blacklist, to prevent storing useless metadata in output packet structure
+    filters: Vec<String>,
+    /// PDML lines of a packet partially received by read_timeout(), or in XML error recovery mode
+    pdml_chunk: PdmlChunk,
+    /// skip packets which cannot be parsed, instead of stopping at the first XML error
+    recover_xml_errors: bool,
+    /// number of packets returned by read()
+    packets_delivered: u64,
+    /// end of stream or fatal error returned by the iterator
+    finished: bool,
+    /// stop TShark gracefully when dropped, with this timeout
+    graceful_drop: Option<std::time::Duration>,
+    /// next input files, read by next TShark processes
+    inputs: Option<input::InputChain>,
+    /// mergecap process merging input files, piped to TShark, until it is reaped at EOF
+    mergecap: Option<Child>,
+    /// error of the input_reader copied to TShark standard input
+    input_error: Option<input::InputError>,
+}
+
+impl RTShark {
+    /// create a new RTShark instance from a successful builder call.
+    fn new(
+        mut process: Child,
+        output_format: OutputFormat,
+        filters: Vec<String>,
+        graceful_drop: Option<std::time::Duration>,
+        recover_xml_errors: bool,
+    ) -> Self {
+        let parser = PacketParser::new(output_format, process.stdout.take().unwrap());
+        let stderr = StderrReader::new(process.stderr.take().unwrap());
+
+        RTShark {
+            process: TsharkProcess::new(process),
+            parser,
+            stderr,
+            filters,
+            pdml_chunk: PdmlChunk::default(),
+            recover_xml_errors,
+            packets_delivered: 0,
+            finished: false,
+            graceful_drop,
+            inputs: None,
+            mergecap: None,
+            input_error: None,
+        }
+    }
+
+    /// Read a packet from thsark output and map it to the [Packet] type.
+    /// Reading packet can be done until 'None' is returned.
+    /// Once 'None' is returned, no more packets can be read from this stream
+    /// and TShark instance can be dropped.
+    /// This could happen when TShark application dies or when this is the end of the PCAP file.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # // Creates a builder with needed TShark parameters
+    /// # let builder = rtshark::RTSharkBuilder::builder()
+    /// #     .input_path("/tmp/my.pcap");
+    /// // Start a new TShark process
+    /// let mut rtshark = match builder.spawn() {
+    ///     Err(err) => { eprintln!("Error running tshark: {err}"); return; }
+    ///     Ok(rtshark) => rtshark
+    /// };
+    ///
+    /// // read packets until the end of the PCAP file
+    /// loop {
+    ///     let packet = match rtshark.read() {
+    ///         Ok(p) => p,
+    ///         Err(e) if e.is_fatal() => { eprintln!("Error running tshark: {e}"); break; }
+    ///         Err(e) => { eprintln!("Got decoding error: {e}"); continue; }
+    ///     };
+    ///
+    ///     // end of stream
+    ///     if let None = packet {
+    ///         break;
+    ///     }
+    ///
+    ///     println!("Got a packet");
+    /// }
+    /// ```
+    pub fn read(&mut self) -> Result<Option<Packet>> {
+        loop {
+            let msg = match &mut self.parser {
+                // parse packets one by one to skip invalid ones, or continue after read_timeout()
+                PacketParser::Pdml(_) if self.recover_xml_errors || self.pdml_chunk.started => {
+                    self.read_pdml_chunk()
+                }
+                PacketParser::Pdml(xml_reader) => parse_xml(xml_reader, &self.filters),
+                PacketParser::Json(json_reader) => json_reader.read_packet(&self.filters),
+            };
+
+            match self.read_done(msg) {
+                // end of an input file: continue with the next one
+                Ok(None) if self.next_input()? => continue,
+                msg => return msg,
+            }
+        }
+    }
+
+    /// Read a packet like [RTShark::read], but give up if no complete packet is received before
+    /// the timeout, to run other tasks while capturing on a quiet interface.
+    ///
+    /// A packet partially received when the timeout fires is not lost: it is returned by a next
+    /// call to read_timeout() or read(). This API is only available on unix.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let builder = rtshark::RTSharkBuilder::builder().input_path("/tmp/my.pcap");
+    /// # let Ok(mut rtshark) = builder.spawn() else { return };
+    /// use rtshark::ReadOutcome;
+    /// use std::time::Duration;
+    ///
+    /// loop {
+    ///     match rtshark.read_timeout(Duration::from_millis(500)) {
+    ///         Ok(ReadOutcome::Packet(packet)) => println!("Got a packet"),
+    ///         Ok(ReadOutcome::Timeout) => println!("No packet, doing some housekeeping"),
+    ///         Ok(ReadOutcome::Eof) => break,
+    ///         Err(e) => { eprintln!("Got decoding error: {e}"); break; }
+    ///     }
+    /// }
+    /// ```
+    #[cfg(target_family = "unix")]
+    pub fn read_timeout(&mut self, timeout: std::time::Duration) -> Result<ReadOutcome> {
+        let deadline = std::time::Instant::now().checked_add(timeout);
+
+        loop {
+            self.parser.stdout_mut().get_mut().set_deadline(deadline);
+            let msg = match &mut self.parser {
+                // quick_xml cannot resume parsing after an error: keep PDML lines until the end of the packet
+                PacketParser::Pdml(_) => self.read_pdml_chunk(),
+                PacketParser::Json(json_reader) => json_reader.read_packet(&self.filters),
+            };
+            self.parser.stdout_mut().get_mut().set_deadline(None);
+
+            return match self.read_done(msg) {
+                Ok(Some(packet)) => Ok(ReadOutcome::Packet(packet)),
+                // end of an input file: continue with the next one
+                Ok(None) if self.next_input()? => continue,
+                Ok(None) => Ok(ReadOutcome::Eof),
+                Err(RTSharkError::Io(e)) if e.kind() == std::io::ErrorKind::TimedOut => {
+                    Ok(ReadOutcome::Timeout)
+                }
+                Err(e) => Err(e),
+            };
+        }
+    }
+
+    /// Read PDML lines until the end of a packet, and parse them. Lines read before an error
+    /// (like a timeout) are kept for the next call.
+    fn read_pdml_chunk(&mut self) -> Result<Option<Packet>> {
+        // bytes of TShark output already parsed by read()
+        let base = match &self.parser {
+            PacketParser::Pdml(xml_reader) => xml_reader.buffer_position(),
+            PacketParser::Json(_) => 0,
+        };
+        let stdout = self.parser.stdout_mut();
+        let chunk = &mut self.pdml_chunk;
+        chunk.started = true;
+
+        loop {
+            if stdout.read_until(b'\n', &mut chunk.lines)? == 0 {
+                // EOF: only the end of the document is expected here
+                let chunk_offset = base + chunk.offset;
+                let error = match chunk.packet_offset() {
+                    None => None,
+                    Some(offset) if self.recover_xml_errors => Some(RTSharkError::skipped_packet(
+                        "incomplete packet at the end of TShark output",
+                        base + offset,
+                    )),
+                    // same error as read(), at its position in TShark output
+                    Some(offset) => {
+                        let mut reader = quick_xml::Reader::from_reader(chunk.lines.as_slice());
+                        Some(match parse_xml(&mut reader, &self.filters) {
+                            Err(RTSharkError::XmlParse {
+                                offset: position,
+                                message,
+                            }) => RTSharkError::XmlParse {
+                                offset: chunk_offset + position,
+                                message,
+                            },
+                            _ => RTSharkError::XmlParse {
+                                offset: base + offset,
+                                message: "incomplete packet at the end of TShark output".to_owned(),
+                            },
+                        })
+                    }
+                };
+                chunk.consume(chunk.lines.len());
+
+                return match error {
+                    Some(error) => Err(error),
+                    None => Ok(None),
+                };
+            }
+
+            let line = chunk.lines[chunk.line_start..].trim_ascii();
+            if line == b"<packet>" {
+                if let (Some(offset), true) = (chunk.packet_offset(), self.recover_xml_errors) {
+                    // the end of the previous packet is missing: skip it
+                    chunk.consume(chunk.line_start);
+                    chunk.packet_start = Some(0);
+                    chunk.line_start = chunk.lines.len();
+                    return Err(RTSharkError::skipped_packet(
+                        "end of packet not found",
+                        base + offset,
+                    ));
+                }
+                chunk.packet_start = Some(chunk.line_start);
+            } else if line == b"</packet>" {
+                let offset = base + chunk.offset;
+                let packet_offset = base + chunk.packet_offset().unwrap_or(chunk.offset);
+                let mut reader = quick_xml::Reader::from_reader(chunk.lines.as_slice());
+                let packet = parse_xml(&mut reader, &self.filters);
+                chunk.consume(chunk.lines.len());
+
+                return match packet {
+                    Err(RTSharkError::XmlParse { message, .. }) if self.recover_xml_errors => {
+                        Err(RTSharkError::skipped_packet(message, packet_offset))
+                    }
+                    // position of the error in TShark output, not in this packet
+                    Err(RTSharkError::XmlParse {
+                        offset: position,
+                        message,
+                    }) => Err(RTSharkError::XmlParse {
+                        offset: offset + position,
+                        message,
+                    }),
+                    packet => packet,
+                };
+            }
+            chunk.line_start = chunk.lines.len();
+        }
+    }
+
+    /// Update counters and check TShark exit status once a read is done.
+    fn read_done(&mut self, mut msg: Result<Option<Packet>>) -> Result<Option<Packet>> {
+        match &mut msg {
+            Ok(Some(packet)) => {
+                self.packets_delivered += 1;
+                packet.source_file = self.inputs.as_ref().map(|inputs| inputs.current.clone());
+            }
+            // Got None == EOF
+            Ok(None) => {
+                // TShark read a short capture, if the input reader failed
+                if let Some(input_error) = &self.input_error {
+                    input_error.check()?;
+                }
+                stderr::check_exit(&self.process, &self.stderr)?;
+                self.check_mergecap()?;
+            }
+            Err(_) => (),
+        }
+
+        msg
+    }
+
+    /// Iterate over packets, like the [Iterator] implemented by [RTShark], without consuming it.
+    /// It can still be used once the iteration ends, to get its warnings or exit status.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let builder = rtshark::RTSharkBuilder::builder().input_path("/tmp/my.pcap");
+    /// # let Ok(mut rtshark) = builder.spawn() else { return };
+    /// for packet in rtshark.packets() {
+    ///     match packet {
+    ///         Ok(packet) => println!("Got a packet with {} layers", packet.iter().count()),
+    ///         Err(e) => eprintln!("Got decoding error: {e}"),
+    ///     }
+    /// }
+    ///
+    /// for warning in rtshark.warnings() {
+    ///     eprintln!("TShark warning: {warning}");
+    /// }
+    /// ```
+    pub fn packets(&mut self) -> Packets<'_> {
+        Packets { rtshark: self }
+    }
+
+    /// Read and parse packets in a background thread, and receive them through a channel
+    /// holding at most `capacity` packets.
+    ///
+    /// A slow consumer does not slow down TShark, as long as the channel is not full. When it is
+    /// full, the policy tells whether the thread waits for the consumer or drops packets:
+    /// [PacketReceiver::dropped] counts dropped packets.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let builder = rtshark::RTSharkBuilder::builder().input_path("/tmp/my.pcap");
+    /// # let Ok(rtshark) = builder.spawn() else { return };
+    /// let receiver = match rtshark.into_channel(1000, rtshark::BackpressurePolicy::DropOldest) {
+    ///     Ok(receiver) => receiver,
+    ///     Err(e) => { eprintln!("Error starting reader thread: {e}"); return; }
+    /// };
+    ///
+    /// for packet in receiver.filter_map(|packet| packet.ok()) {
+    ///     println!("Got a packet");
+    /// }
+    /// ```
+    pub fn into_channel(
+        self,
+        capacity: usize,
+        policy: BackpressurePolicy,
+    ) -> Result<PacketReceiver> {
+        PacketReceiver::spawn(self, capacity, policy, None)
+    }
+
+    /// Wait for TShark termination and get how it terminated: exit status, stderr and
+    /// number of packets read.
+    ///
+    /// This is usually called once read() has returned None. If TShark is still running,
+    /// its remaining output is discarded until it exits.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let builder = rtshark::RTSharkBuilder::builder().input_path("/tmp/my.pcap");
+    /// # let Ok(mut rtshark) = builder.spawn() else { return };
+    /// while let Ok(Some(_)) = rtshark.read() {}
+    ///
+    /// if let Ok(report) = rtshark.wait() {
+    ///     println!(
+    ///         "TShark exited with code {:?} after {} packets",
+    ///         report.code(),
+    ///         report.packets_delivered()
+    ///     );
+    /// }
+    /// ```
+    pub fn wait(&mut self) -> Result<TerminationReport> {
+        if self.process.id().is_some() {
+            // TShark may be blocked on a full pipe
+            std::io::copy(self.parser.stdout_mut(), &mut std::io::sink())?;
+            self.process.wait()?;
+        }
+
+        Ok(TerminationReport::new(
+            &self.process,
+            self.stderr.wait_lines(),
+            self.stderr.diagnostics(),
+            self.packets_delivered,
+        ))
+    }
+
+    /// Get the non-fatal messages printed by TShark on stderr so far, like capture information
+    /// or dissector warnings. Only the last lines are kept.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let builder = rtshark::RTSharkBuilder::builder().input_path("/tmp/my.pcap");
+    /// # let Ok(mut rtshark) = builder.spawn() else { return };
+    /// while let Ok(Some(_)) = rtshark.read() {}
+    ///
+    /// for warning in rtshark.warnings() {
+    ///     eprintln!("tshark: {warning}");
+    /// }
+    /// ```
+    pub fn warnings(&self) -> Vec<String> {
+        self.stderr.warnings()
+    }
+
+    /// Get the messages printed by TShark on stderr so far, classified by kind and severity.
+    ///
+    /// Some problems are not fatal: for instance, when a capture file is cut short in the middle
+    /// of a packet, all good packets are read and a [DiagnosticKind::TruncatedCapture] warning is kept.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let builder = rtshark::RTSharkBuilder::builder().input_path("/tmp/my.pcap");
+    /// # let Ok(mut rtshark) = builder.spawn() else { return };
+    /// while let Ok(Some(_)) = rtshark.read() {}
+    ///
+    /// for diagnostic in rtshark.diagnostics() {
+    ///     if diagnostic.kind() == rtshark::DiagnosticKind::TruncatedCapture {
+    ///         eprintln!("Capture file is truncated: {}", diagnostic.message());
+    ///     }
+    /// }
+    /// ```
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.stderr.diagnostics()
+    }
+
+    /// Get all lines printed by TShark on stderr so far, warnings and errors.
+    /// TShark stderr is read in background, so that TShark is never blocked by a full pipe.
+    /// Only the last lines are kept.
+    pub fn stderr_lines(&self) -> Vec<String> {
+        self.stderr.lines()
+    }
+
+    /// Kill the running TShark process associated to this rtshark instance.
+    /// Once TShark is killed, there is no way to start it again using this object.
+    /// Any new TShark instance has to be created using RTSharkBuilder.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// // Creates a builder with needed TShark parameters
+    /// let builder = rtshark::RTSharkBuilder::builder()
+    ///     .input_path("/tmp/my.pcap");
+    ///
+    /// // Start a new TShark process
+    /// let mut rtshark = match builder.spawn() {
+    ///     Err(err) => { eprintln!("Error running tshark: {err}"); return; }
+    ///     Ok(rtshark) => rtshark
+    /// };
+    ///
+    /// // kill running TShark process
+    /// rtshark.kill();
+    /// ```
+    pub fn kill(&mut self) {
+        self.process.kill()
+    }
+
+    /// Ask the running TShark process to stop gracefully, and kill it if it is still running after the timeout.
+    ///
+    /// On unix, TShark receives SIGINT, like with Ctrl-C: it stops its dumpcap child, flushes the file given
+    /// to output_path() and prints its capture statistics. Packets already captured are still delivered
+    /// by read(), until it returns None. On other platforms, TShark is killed.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let builder = rtshark::RTSharkBuilder::builder().input_path("/tmp/my.pcap");
+    /// # let Ok(mut rtshark) = builder.spawn() else { return };
+    /// rtshark.stop(std::time::Duration::from_secs(5)).unwrap();
+    ///
+    /// // read remaining packets
+    /// while let Ok(Some(packet)) = rtshark.read() {
+    ///     println!("Got a packet with {} layers", packet.iter().count());
+    /// }
+    /// ```
+    pub fn stop(&mut self, timeout: std::time::Duration) -> Result<()> {
+        self.process.stop(timeout)?;
+        Ok(())
+    }
+
+    /// Get a handle to kill or stop TShark from another thread, while this thread is blocked in read().
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let builder = rtshark::RTSharkBuilder::builder().input_path("/tmp/my.pcap");
+    /// # let Ok(mut rtshark) = builder.spawn() else { return };
+    /// let handle = rtshark.handle();
+    /// std::thread::spawn(move || handle.kill());
+    ///
+    /// // returns None once TShark is killed
+    /// while let Ok(Some(_)) = rtshark.read() {}
+    /// ```
+    pub fn handle(&self) -> RTSharkHandle {
+        RTSharkHandle::new(self.process.clone())
+    }
+
+    /// Returns tshark process id if tshark is running.
+    /// # Example
+    ///
+    /// ```
+    /// // Creates a builder with needed tshark parameters
+    /// let builder = rtshark::RTSharkBuilder::builder()
+    ///     .input_path("/tmp/my.pcap");
+    ///
+    /// // Start a new tshark process
+    /// let mut rtshark = match builder.spawn() {
+    ///     Err(err) => { eprintln!("Error running tshark: {err}"); return; }
+    ///     Ok(rtshark) => println!("tshark PID is {}", rtshark.pid().unwrap())
+    /// };
+    ///
+    /// ```
+    pub fn pid(&self) -> Option<u32> {
+        self.process.id()
+    }
+}
+
+/// Iterate over packets until the end of stream.
+///
+/// A recoverable error (see [RTSharkError::is_fatal]) is returned in place of the invalid packet,
+/// then next packets are returned. The iteration ends after a fatal error.
+///
+/// # Example
+///
+/// ```
+/// # let builder = rtshark::RTSharkBuilder::builder().input_path("/tmp/my.pcap");
+/// # let Ok(rtshark) = builder.spawn() else { return };
+/// let udp_packets = rtshark
+///     .filter_map(|packet| packet.ok())
+///     .filter(|packet| packet.layer_name("udp").is_some())
+///     .take(10)
+///     .count();
+/// ```
+impl Iterator for RTShark {
+    type Item = Result<Packet>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        match self.read() {
+            Ok(packet) => {
+                self.finished = packet.is_none();
+                packet.map(Ok)
+            }
+            Err(e) => {
+                self.finished = e.is_fatal();
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl FusedIterator for RTShark {}
+
+/// Iterator over packets borrowing a [RTShark], created by [RTShark::packets].
+pub struct Packets<'a> {
+    rtshark: &'a mut RTShark,
+}
+
+impl Iterator for Packets<'_> {
+    type Item = Result<Packet>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.rtshark.next()
+    }
+}
+
+impl FusedIterator for Packets<'_> {}
+
+impl Drop for RTShark {
+    fn drop(&mut self) {
+        // TShark gets the end of its input
+        if let Some(mut mergecap) = self.mergecap.take() {
+            let _ = mergecap.kill();
+            let _ = mergecap.wait();
+        }
+
+        self.process
+            .terminate(self.graceful_drop, self.parser.stdout_mut());
+    }
+}
+
+/// Prepare the TShark command, with piped outputs.
+fn tshark_command<S: AsRef<std::ffi::OsStr>>(
+    tshark_params: &[S],
+    env_path: &str,
+    #[cfg_attr(not(target_os = "linux"), allow(unused_variables))] kill_on_parent_death: bool,
+) -> Command {
+    let mut command = Command::new("tshark");
+    command
+        .args(tshark_params)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    if !env_path.is_empty() {
+        command.env("PATH", env_path);
+    }
+
+    // TShark and its dumpcap child are in their own process group, to be killed together
+    #[cfg(target_family = "unix")]
+    {
+        use std::os::unix::process::CommandExt;
+        command.process_group(0);
+    }
+
+    #[cfg(target_os = "linux")]
+    if kill_on_parent_death {
+        use std::os::unix::process::CommandExt;
+        let pipe = process::parent_death_pipe().map_err(|e| e.raw_os_error());
+        // SAFETY: only async-signal-safe functions are called in the forked child
+        unsafe {
+            command.pre_exec(move || match pipe {
+                Ok(pipe) => process::spawn_group_watchdog(pipe),
+                Err(errno) => Err(std::io::Error::from_raw_os_error(
+                    errno.unwrap_or(libc::EIO),
+                )),
+            });
+        }
+    }
+
+    command
+}
+
+/// Add context to an error returned when spawning TShark.
+fn tshark_spawn_error(e: std::io::Error) -> RTSharkError {
+    match e.kind() {
+        std::io::ErrorKind::NotFound => RTSharkError::TsharkNotFound(e),
+        _ => e.into(),
+    }
+}
+
+/// search for an attribute of a XML tag using its name and return a string, if found.
+fn rtshark_attr_by_name_opt(tag: &BytesStart, key: &[u8]) -> Result<Option<String>> {
+    let attrs = &mut tag.attributes();
+    for attr in attrs {
+        let attr = attr.map_err(|e| {
+            RTSharkError::invalid_data(format!("Error decoding xml attribute: {e:?}"))
+        })?;
+        if attr.key.as_ref() == key {
+            let value = std::str::from_utf8(&attr.value).map_err(|e| {
+                RTSharkError::invalid_data(format!("Error decoding utf8 value: {e:?}"))
+            })?;
+            return Ok(Some(value.to_owned()));
+        }
+    }
+
+    Ok(None)
+}
+
+/// search for an attribute of a XML tag using its name and return a string.
+fn rtshark_attr_by_name(tag: &BytesStart, key: &[u8]) -> Result<String> {
+    if let Some(value) = rtshark_attr_by_name_opt(tag, key)? {
+        return Ok(value);
+    }
+
+    let line =
+        std::str::from_utf8(tag.attributes_raw()).unwrap_or("Unable to decode UTF8 XML buffer");
+
+    Err(RTSharkError::invalid_data(format!(
+        "xml lookup error: no key '{}' in '{}'",
+        std::str::from_utf8(key).unwrap(),
+        line
+    )))
+}
+
+/// search for an attribute of a XML tag using its name and return a u32.
+fn rtshark_attr_by_name_u32(tag: &BytesStart, key: &[u8]) -> Result<u32> {
+    match rtshark_attr_by_name(tag, key) {
+        Err(e) => Err(e),
+        Ok(v) => v
+            .parse::<u32>()
+            .map_err(|e| RTSharkError::invalid_data(format!("Error decoding u32 value: {e:?}"))),
+    }
+}
+
+/// Build a metadata using attributes available on this XML "field" tag.
+/// Sample XML line : <field name="frame.time" show="test time" pos="0" size="0" showname="test time display"/>
+fn rtshark_build_metadata(tag: &BytesStart, filters: &[String]) -> Result<Option<Metadata>> {
+    let name = rtshark_attr_by_name(tag, b"name")?;
+
+    // skip "_ws.expert" info, not related to a packet metadata
+    if name.is_empty() || name.starts_with("_ws.") {
+        return Ok(None);
+    }
+
+    // skip data
+    if filters.contains(&name) {
+        return Ok(None);
+    }
+
+    // Issue #1 : uses pyshark-like algorithm to display the best 'value' for this field
+    // https://github.com/KimiNewt/pyshark/blob/master/src/pyshark/packet/fields.py#L14
+    // try first "show", then "value", finally "showname"
+    let value = match rtshark_attr_by_name_opt(tag, b"show")? {
+        Some(value) => value,
+        None => match rtshark_attr_by_name_opt(tag, b"value")? {
+            Some(value) => value,
+            None => match rtshark_attr_by_name_opt(tag, b"showname") {
+                Ok(Some(value)) => value,
+                _ => rtshark_attr_by_name(tag, b"value")?,
+            },
+        },
+    };
+
+    let mut metadata = Metadata::new(name, value, None, None, None);
+
+    if let Ok(position) = rtshark_attr_by_name_u32(tag, b"pos") {
+        metadata.position = Some(position);
+    }
+    if let Ok(size) = rtshark_attr_by_name_u32(tag, b"size") {
+        metadata.size = Some(size);
+    }
+    if let Ok(display) = rtshark_attr_by_name(tag, b"showname") {
+        metadata.display = Some(display);
+    }
+    if let Ok(raw_value) = rtshark_attr_by_name(tag, b"value") {
+        if raw_value != metadata.value {
+            metadata.raw_value = Some(raw_value);
+        }
+    }
+    Ok(Some(metadata))
+}
+
+/// Convert a "seconds.nanoseconds" epoch timestamp displayed by TShark into microseconds.
+fn parse_timestamp_micros(value: &str) -> Option<i64> {
+    use chrono::{LocalResult, TimeZone as _, Utc};
+
+    let (secs, frac) = value.split_once('.')?;
+    let secs = secs.parse().ok()?;
+    // nanoseconds are usually displayed with 9 digits, but not always
+    let nsecs = format!("{:0<9.9}", frac).parse().ok()?;
+
+    let LocalResult::Single(dt) = Utc.timestamp_opt(secs, nsecs) else {
+        return None;
+    };
+    Some(dt.timestamp_micros())
+}
+
+/// Process specific metadata in geninfo to fill the packet structure
+fn geninfo_metadata(tag: &BytesStart, packet: &mut Packet) -> Result<()> {
+    let name = rtshark_attr_by_name(tag, b"name")?;
+    if name == "num" {
+        let value = rtshark_attr_by_name(tag, b"show")?;
+        let frame_number = value.parse::<u64>().map_err(|_| {
+            RTSharkError::invalid_data(format!("Error decoding frame number: {value}"))
+        })?;
+        packet.frame_number.replace(frame_number);
+        return Ok(());
+    }
+    if name != "timestamp" {
+        return Ok(());
+    }
+    let value = rtshark_attr_by_name(tag, b"value")?;
+
+    let timestamp_micros = parse_timestamp_micros(&value)
+        .ok_or_else(|| RTSharkError::invalid_data(format!("Error decoding timestamp: {value}")))?;
+    packet.timestamp_micros.replace(timestamp_micros);
+
+    Ok(())
+}
+
+/// list of protocols in tshark output but not in packet data
+fn ignored_protocols(name: &str) -> bool {
+    name.eq("geninfo") || name.eq("fake-field-wrapper")
+}
+
+/// Main parser function used to decode XML output from tshark
+fn parse_xml<B: BufRead>(
+    xml_reader: &mut quick_xml::Reader<B>,
+    filters: &[String],
+) -> Result<Option<Packet>> {
+    let packet = parse_xml_packet(xml_reader, filters);
+
+    // An invalid field stops parsing in the middle of the packet: skip its end,
+    // so that the next read starts with the next packet.
+    if matches!(&packet, Err(e) if !e.is_fatal()) {
+        let mut buf = vec![];
+        loop {
+            match xml_reader.read_event_into(&mut buf) {
+                Ok(Event::End(ref e)) if e.name().as_ref() == b"packet" => break,
+                Ok(Event::Eof) | Err(_) => break,
+                Ok(_) => buf.clear(),
+            }
+        }
+    }
+
+    packet
+}
+
+/// Decode the next packet of XML output from tshark.
+fn parse_xml_packet<B: BufRead>(
+    xml_reader: &mut quick_xml::Reader<B>,
+    filters: &[String],
+) -> Result<Option<Packet>> {
+    let mut buf = vec![];
+    let mut packet = Packet::new();
+
+    let mut protoname = None;
+
+    // tshark pdml is something like : (default mode)
+    //
+    // <!-- You can find pdml2html.xsl in /usr/share/wireshark or at https://gitlab.com/wireshark/wireshark/-/raw/master/pdml2html.xsl. -->
+    // <pdml version="0" creator="wireshark/4.0.6" time="Sat Oct  7 09:51:54 2023" capture_file="src/test.pcap">
+    // <packet>
+    //   <proto name="geninfo" pos="0" showname="General information" size="28">
+    //     <field name="num" pos="0" show="1" showname="Number" value="1" size="28"/>
+    //   </proto>
+    //   <proto name="frame" pos="0" showname="General information" size="28">
+    //   ...
+    //
+    // or, if using "whitelist" with -e option
+    //
+    // <pdml version="0" creator="wireshark/4.0.6" time="Sat Oct  7 09:51:54 2023" capture_file="src/test.pcap">
+    // <packet>
+    //   <proto name="geninfo" pos="0" showname="General information" size="28">
+    //     <field name="num" pos="0" show="1" showname="Number" value="1" size="28"/>
+    // </proto>
+    // <field name="num" pos="0" show="1" showname="Number" value="1" size="28"/>
+    // ...
+
+    /// Create a new layer if required and add metadata to the given packet.
+    fn _add_metadata(packet: &mut Packet, metadata: Metadata) -> Result<()> {
+        // Create a new layer if the field's protocol does not exist yet as a layer.
+        if let Some(proto) = metadata.name().split('.').next() {
+            packet.push_if_not_exist(proto.to_owned());
+        }
+
+        if let Some(layer) = packet.last_layer_mut() {
+            layer.add(metadata);
+        } else {
+            return Err(RTSharkError::invalid_data(
+                "Cannot find protocol name to push a metadata",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Attach a complete metadata to its parent field if any, else to the right layer of the packet.
+    fn _attach_metadata(
+        packet: &mut Packet,
+        protoname: Option<&str>,
+        parents: &mut [Option<Metadata>],
+        metadata: Metadata,
+    ) -> Result<()> {
+        // Parent fields filtered out (blacklist, "_ws.expert" ...) are not stored:
+        // their children are attached to the closest parent which is kept.
+        if let Some(parent) = parents.iter_mut().rev().find_map(|p| p.as_mut()) {
+            parent.add_child(metadata);
+            return Ok(());
+        }
+
+        match protoname {
+            // Some dissectors place field items at the top level instead
+            // of inside a protocol. In these cases, in the PDML output the
+            // field items are placed inside a fake "<proto>" element named
+            // "fake-field-wrapper" in order to maximize compliance.
+            // See https://github.com/wireshark/wireshark/blob/master/doc/README.xml-output
+            //
+            // An example is "tcp.reassembled". We should try to add these
+            // items to the correct layer so that they are accessible.
+            Some("fake-field-wrapper") => {
+                let proto_from_name = metadata.name().split('.').next().unwrap_or("");
+                let proto_layer = packet
+                    .last_layer_mut()
+                    .filter(|layer| layer.name == proto_from_name);
+                if let Some(layer) = proto_layer {
+                    layer.add(metadata);
+                }
+            }
+            // We should have a layer : it was pushed in Event::Start
+            Some(_) => match packet.last_layer_mut() {
+                Some(layer) => layer.add(metadata),
+                None => _add_metadata(packet, metadata)?,
+            },
+            // No encapsulating "proto" in "whitelist" mode
+            None => _add_metadata(packet, metadata)?,
+        }
+
+        Ok(())
+    }
+
+    // Stack of fields which are not closed yet, to rebuild the fields hierarchy.
+    // A filtered out field is kept as None to match its closing tag.
+    let mut parents: Vec<Option<Metadata>> = vec![];
+
+    loop {
+        match xml_reader.read_event_into(&mut buf) {
+            Ok(Event::Start(ref e)) => {
+                // Here we have "packet" and "proto" and sometimes "field" tokens. Only "proto" and "field" are interesting today.
+                if b"proto" == e.name().as_ref() {
+                    let proto = rtshark_attr_by_name(e, b"name")?;
+                    protoname = Some(proto.to_owned());
+
+                    // If we face a new protocol, add it in the packet layers stack.
+                    if !ignored_protocols(proto.as_str()) {
+                        packet.push(proto);
+                    }
+                }
+
+                // There are cases where fields are mapped in fields. Keep the parent field until its
+                // end tag, so that its children can be attached to it.
+                if b"field" == e.name().as_ref() {
+                    parents.push(rtshark_build_metadata(e, filters)?);
+                }
+            }
+            Ok(Event::Empty(ref e)) => {
+                // Here we should not have anything else than "field" but do a test anyway.
+                if b"field" == e.name().as_ref() {
+                    // Here we have two cases : with or without encapsuling "proto"
+                    // We have a protocol if "whitelist" mode is disabled.
+                    // Protocol "geninfo" is always here.
+                    if protoname.as_deref() == Some("geninfo") && parents.is_empty() {
+                        // Put geninfo metadata in packet's object (timestamp ...).
+                        geninfo_metadata(e, &mut packet)?;
+                    } else if let Some(metadata) = rtshark_build_metadata(e, filters)? {
+                        _attach_metadata(
+                            &mut packet,
+                            protoname.as_deref(),
+                            &mut parents,
+                            metadata,
+                        )?;
+                    }
+                }
+            }
+            Ok(Event::End(ref e)) => match e.name().as_ref() {
+                b"packet" => return Ok(Some(packet)),
+                b"proto" => protoname = None,
+                b"field" => {
+                    if let Some(Some(metadata)) = parents.pop() {
+                        _attach_metadata(
+                            &mut packet,
+                            protoname.as_deref(),
+                            &mut parents,
+                            metadata,
+                        )?;
+                    }
+                }
+                _ => (),
+            },
+
+            Ok(Event::Eof) => {
+                return Ok(None);
+            }
+            Err(e) => {
+                return Err(RTSharkError::XmlParse {
+                    offset: xml_reader.buffer_position(),
+                    message: e.to_string(),
+                });
+            }
+            Ok(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use serial_test::serial;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_single_proto_metadata() {
+        let xml = r#"
+        <pdml>
+         <packet>
+          <proto name="frame">
+           <field name="frame.time" show="test time" pos="0" size="0" showname="test time display"/>
+          </proto>
+         </packet>
+        </pdml>"#;
+
+        let mut reader = quick_xml::Reader::from_reader(BufReader::new(xml.as_bytes()));
+
+        let msg = parse_xml(&mut reader, &[]).unwrap();
+        let pkt = match msg {
+            Some(p) => p,
+            _ => panic!("invalid Output type"),
+        };
+
+        assert_eq!(pkt.layers.len(), 1);
+        for layer in pkt.layers {
+            for m in layer {
+                assert!(m.name().eq("frame.time"));
+                assert!(m.value().eq("test time"));
+                assert_eq!(m.display(), Some("test time display"));
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_missing_optional_size() {
+        let xml = r#"
+        <pdml>
+         <packet>
+          <proto name="frame">
+          <field name="frame.time" show="test time" pos="0" showname="test time display"/>
+          </proto>
+         </packet>
+        </pdml>"#;
+
+        let mut reader = quick_xml::Reader::from_reader(BufReader::new(xml.as_bytes()));
+
+        let msg = parse_xml(&mut reader, &[]).unwrap();
+        let pkt = match msg {
+            Some(p) => p,
+            _ => panic!("invalid Output type"),
+        };
+
+        assert_eq!(pkt.layers.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_missing_optional_pos() {
+        let xml = r#"
+        <pdml>
+         <packet>
+          <proto name="frame">
+          <field name="frame.time" show="test time" size="0" showname="test time display"/>
+          </proto>
+         </packet>
+        </pdml>"#;
+
+        let mut reader = quick_xml::Reader::from_reader(BufReader::new(xml.as_bytes()));
+
+        let msg = parse_xml(&mut reader, &[]).unwrap();
+        let pkt = match msg {
+            Some(p) => p,
+            _ => panic!("invalid Output type"),
+        };
+
+        assert_eq!(pkt.layers.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_missing_optional_display() {
+        let xml = r#"
+        <pdml>
+         <packet>
+          <proto name="frame">
+          <field name="frame.time" show="test time" pos="0" size="0" />
+          </proto>
+         </packet>
+        </pdml>"#;
+
+        let mut reader = quick_xml::Reader::from_reader(BufReader::new(xml.as_bytes()));
+
+        let msg = parse_xml(&mut reader, &[]).unwrap();
+        let pkt = match msg {
+            Some(p) => p,
+            _ => panic!("invalid Output type"),
+        };
+
+        assert_eq!(pkt.layers.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_missing_mandatory_name() {
+        let xml = r#"
+        <pdml>
+         <packet>
+          <proto name="frame">
+          <field show="test time" pos="0" size="0" showname="test time display"/>
+          </proto>
+         </packet>
+        </pdml>"#;
+
+        let mut reader = quick_xml::Reader::from_reader(BufReader::new(xml.as_bytes()));
+
+        let msg = parse_xml(&mut reader, &[]);
+
+        match msg {
+            Err(_) => (),
+            _ => panic!("invalid result"),
+        }
+    }
+
+    #[test]
+    fn test_parse_all_value_fields_available() {
+        // Issue #1 : uses pyshark-like algorithm to display the best 'value' for this field
+        // https://github.com/KimiNewt/pyshark/blob/master/src/pyshark/packet/fields.py#L14
+        // try first "show", then "value", finally "showname"
+
+        let xml = r#"
+        <pdml>
+         <packet>
+          <proto name="icmp">
+           <field name="data" show="data is aa" value="0a" showname="data: a0"/>
+          </proto>
+         </packet>
+        </pdml>"#;
+
+        let mut reader = quick_xml::Reader::from_reader(BufReader::new(xml.as_bytes()));
+
+        let pkt = parse_xml(&mut reader, &[]).unwrap().unwrap();
+
+        let icmp = pkt.layer_name("icmp").unwrap();
+        let data = icmp.metadata("data").unwrap();
+        assert!(data.value().eq("data is aa"));
+        assert!(data.raw_value().eq("0a"));
+        assert_eq!(data.display(), Some("data: a0"));
+    }
+
+    #[test]
+    fn test_parse_missing_show_attribute() {
+        // Issue #1 : uses pyshark-like algorithm to display the best 'value' for this field
+        // https://github.com/KimiNewt/pyshark/blob/master/src/pyshark/packet/fields.py#L14
+        // try first "show", then "value", finally "showname"
+
+        let xml = r#"
+        <pdml>
+         <packet>
+          <proto name="icmp">
+           <field name="data" value="0a" showname="data: a0"/>
+          </proto>
+         </packet>
+        </pdml>"#;
+
+        let mut reader = quick_xml::Reader::from_reader(BufReader::new(xml.as_bytes()));
+
+        let pkt = parse_xml(&mut reader, &[]).unwrap().unwrap();
+
+        let icmp = pkt.layer_name("icmp").unwrap();
+        let data = icmp.metadata("data").unwrap();
+        assert!(data.value().eq("0a"));
+        assert!(data.raw_value() == data.value());
+    }
+
+    #[test]
+    fn test_parse_missing_show_and_value_attributes() {
+        // Issue #1 : uses pyshark-like algorithm to display the best 'value' for this field
+        // https://github.com/KimiNewt/pyshark/blob/master/src/pyshark/packet/fields.py#L14
+        // try first "show", then "value", finally "showname"
+
+        let xml = r#"
+        <pdml>
+         <packet>
+          <proto name="icmp">
+           <field name="data" showname="data: a0"/>
+          </proto>
+         </packet>
+        </pdml>"#;
+
+        let mut reader = quick_xml::Reader::from_reader(BufReader::new(xml.as_bytes()));
+
+        let pkt = parse_xml(&mut reader, &[]).unwrap().unwrap();
+
+        let icmp = pkt.layer_name("icmp").unwrap();
+        let data = icmp.metadata("data").unwrap();
+        assert!(data.value().eq("data: a0"));
+        assert!(data.raw_value() == data.value());
+    }
+
+    #[test]
+    fn test_parse_missing_any_show() {
+        let xml = r#"
+        <pdml>
+         <packet>
+          <proto name="frame">
+          <field name="frame.time" pos="0" size="0"/>
+          </proto>
+         </packet>
+        </pdml>"#;
+
+        let mut reader = quick_xml::Reader::from_reader(BufReader::new(xml.as_bytes()));
+
+        let msg = parse_xml(&mut reader, &[]);
+        match msg {
+            Err(_) => (),
+            _ => panic!("invalid result"),
+        }
+    }
+
+    const XML_TCP: &str = r#"
+    <pdml>
+     <packet>
+      <proto name="frame">
+       <field name="frame.time" show="Mar  5, 2021 08:49:52.736275000 CET"/>
+      </proto>
+      <proto name="ip">
+       <field name="ip.src" show="1.1.1.1" />
+       <field name="ip.dst" show="1.1.1.2" />
+      </proto>
+      <proto name="tcp">
+       <field name="tcp.srcport" show="52796" value="ce3c"/>
+       <field name="tcp.dstport" show="5432" value="1538"/>
+       <field name="tcp.seq_raw" show="1963007432" value="75011dc8"/>
+       <field name="tcp.stream" show="4"/>
+      </proto>
+     </packet>
+    </pdml>"#;
+
+    #[test]
+    fn test_access_packet_into_iter() {
+        let mut reader = quick_xml::Reader::from_reader(BufReader::new(XML_TCP.as_bytes()));
+
+        let msg = parse_xml(&mut reader, &[]).unwrap();
+        let pkt = match msg {
+            Some(p) => p,
+            _ => panic!("invalid Output type"),
+        };
+
+        assert_eq!(pkt.layers.len(), 3);
+
+        let mut iter = pkt.into_iter();
+        let frame = iter.next().unwrap();
+        assert!(frame.name().eq("frame"));
+        let ip = iter.next().unwrap();
+        assert!(ip.name().eq("ip"));
+        let tcp = iter.next().unwrap();
+        assert!(tcp.name().eq("tcp"));
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn test_access_packet_iter() {
+        let mut reader = quick_xml::Reader::from_reader(BufReader::new(XML_TCP.as_bytes()));
+
+        let msg = parse_xml(&mut reader, &[]).unwrap();
+        let pkt = match msg {
+            Some(p) => p,
+            _ => panic!("invalid Output type"),
+        };
+
+        assert_eq!(pkt.layers.len(), 3);
+
+        let mut iter = pkt.iter();
+        let frame = iter.next().unwrap();
+        assert!(frame.name().eq("frame"));
+        let ip = iter.next().unwrap();
+        assert!(ip.name().eq("ip"));
+        let tcp = iter.next().unwrap();
+        assert!(tcp.name().eq("tcp"));
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn test_access_layer_index() {
+        let mut reader = quick_xml::Reader::from_reader(BufReader::new(XML_TCP.as_bytes()));
 
-            Ok(Event::Eof) => {
-                return Ok(None);
-            }
-            Err(e) => {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::InvalidData,
-                    format!(
-                        "xml parsing error: {} at tshark output offset {}",
-                        e,
-                        xml_reader.buffer_position()
-                    ),
-                ));
-            }
-            Ok(_) => {}
-        }
+        let msg = parse_xml(&mut reader, &[]).unwrap();
+        let pkt = match msg {
+            Some(p) => p,
+            _ => panic!("invalid Output type"),
+        };
+
+        assert_eq!(pkt.layers.len(), 3);
+
+        assert!(pkt.layer_index(0).unwrap().name().eq("frame"));
+        assert!(pkt.layer_index(1).unwrap().name().eq("ip"));
+        assert!(pkt.layer_index(2).unwrap().name().eq("tcp"));
+
+        assert!(pkt.layer_index(3).is_none());
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use std::io::Write;
+    #[test]
+    fn test_access_layer_name() {
+        let mut reader = quick_xml::Reader::from_reader(BufReader::new(XML_TCP.as_bytes()));
 
-    use serial_test::serial;
+        let msg = parse_xml(&mut reader, &[]).unwrap();
+        let pkt = match msg {
+            Some(p) => p,
+            _ => panic!("invalid Output type"),
+        };
 
-    use super::*;
+        assert_eq!(pkt.layers.len(), 3);
+
+        assert!(pkt.layer_name("frame").unwrap().name().eq("frame"));
+        assert!(pkt.layer_name("ip").unwrap().name().eq("ip"));
+        assert!(pkt.layer_name("tcp").unwrap().name().eq("tcp"));
+
+        assert!(pkt.layer_name("udp").is_none());
+    }
 
     #[test]
-    fn test_parse_single_proto_metadata() {
+    fn test_access_layer_name_with_tunnel() {
         let xml = r#"
         <pdml>
          <packet>
           <proto name="frame">
-           <field name="frame.time" show="test time" pos="0" size="0" showname="test time display"/>
+           <field name="frame.time" show="Mar  5, 2021 08:49:52.736275000 CET"/>
+          </proto>
+          <proto name="ip">
+           <field name="ip.src" show="10.215.215.9" />
+           <field name="ip.dst" show="10.215.215.10" />
+          </proto>
+          <proto name="ip">
+           <field name="ip.src" show="10.10.215.9" />
+           <field name="ip.dst" show="10.10.215.10" />
+          </proto>
+          <proto name="tcp">
+           <field name="tcp.srcport" show="52796" value="ce3c"/>
+           <field name="tcp.dstport" show="5432" value="1538"/>
+           <field name="tcp.seq_raw" show="1963007432" value="75011dc8"/>
+           <field name="tcp.stream" show="4"/>
           </proto>
          </packet>
         </pdml>"#;
@@ -1536,28 +3383,38 @@ mod tests {
             _ => panic!("invalid Output type"),
         };
 
-        assert_eq!(pkt.layers.len(), 1);
-        for layer in pkt.layers {
-            for m in layer {
-                assert!(m.name().eq("frame.time"));
-                assert!(m.value().eq("test time"));
-                assert_eq!(m.display(), Some("test time display"));
-            }
-        }
+        assert_eq!(pkt.layers.len(), 4);
+
+        assert!(pkt.layer_name("frame").unwrap().name().eq("frame"));
+        assert!(pkt.layer_name("ip").unwrap().name().eq("ip"));
+        assert!(pkt.layer_name("ip").unwrap().index() == 1usize);
+        assert!(pkt.layer_index(1).unwrap().name().eq("ip"));
+        assert!(pkt.layer_index(2).unwrap().name().eq("ip"));
+        assert!(pkt.layer_name("tcp").unwrap().name().eq("tcp"));
+
+        assert!(pkt.layer_name("udp").is_none());
     }
 
     #[test]
-    fn test_parse_missing_optional_size() {
-        let xml = r#"
-        <pdml>
-         <packet>
-          <proto name="frame">
-          <field name="frame.time" show="test time" pos="0" showname="test time display"/>
-          </proto>
-         </packet>
-        </pdml>"#;
+    fn test_access_layer_iter() {
+        let mut reader = quick_xml::Reader::from_reader(BufReader::new(XML_TCP.as_bytes()));
 
-        let mut reader = quick_xml::Reader::from_reader(BufReader::new(xml.as_bytes()));
+        let msg = parse_xml(&mut reader, &[]).unwrap();
+        let pkt = match msg {
+            Some(p) => p,
+            _ => panic!("invalid Output type"),
+        };
+
+        let ip = pkt.layer_name("ip").unwrap();
+        let mut iter = ip.iter();
+        assert!(iter.next().unwrap().name().eq("ip.src"));
+        assert!(iter.next().unwrap().name().eq("ip.dst"));
+        assert!(iter.next().is_none());
+    }
+
+    #[test]
+    fn test_access_layer_into_iter() {
+        let mut reader = quick_xml::Reader::from_reader(BufReader::new(XML_TCP.as_bytes()));
 
         let msg = parse_xml(&mut reader, &[]).unwrap();
         let pkt = match msg {
@@ -1565,136 +3422,511 @@ mod tests {
             _ => panic!("invalid Output type"),
         };
 
-        assert_eq!(pkt.layers.len(), 1);
+        let ip = pkt.layer_name("ip").unwrap().clone();
+        let mut iter = ip.into_iter();
+        assert!(iter.next().unwrap().name().eq("ip.src"));
+        assert!(iter.next().unwrap().name().eq("ip.dst"));
+        assert!(iter.next().is_none());
     }
 
     #[test]
-    fn test_parse_missing_optional_pos() {
+    fn test_access_layer_metadata() {
+        let mut reader = quick_xml::Reader::from_reader(BufReader::new(XML_TCP.as_bytes()));
+
+        let msg = parse_xml(&mut reader, &[]).unwrap();
+        let pkt = match msg {
+            Some(p) => p,
+            _ => panic!("invalid Output type"),
+        };
+
+        let ip = pkt.layer_name("ip").unwrap();
+        let src = ip.metadata("ip.src").unwrap();
+        assert!(src.value().eq("1.1.1.1"));
+
+        let dst = ip.metadata("ip.dst").unwrap();
+        assert!(dst.value().eq("1.1.1.2"));
+    }
+
+    #[test]
+    fn test_parser_filter_metadata() {
+        let mut reader = quick_xml::Reader::from_reader(BufReader::new(XML_TCP.as_bytes()));
+
+        let msg = parse_xml(&mut reader, &["ip.src".to_string()]).unwrap();
+        let pkt = match msg {
+            Some(p) => p,
+            _ => panic!("invalid Output type"),
+        };
+
+        let ip = pkt.layer_name("ip").unwrap();
+        assert!(ip.metadata("ip.src").is_none());
+        assert!(ip.metadata("ip.dst").unwrap().value().eq("1.1.1.2"));
+    }
+
+    #[test]
+    fn test_parser_multiple_packets() {
         let xml = r#"
         <pdml>
          <packet>
-          <proto name="frame">
-          <field name="frame.time" show="test time" size="0" showname="test time display"/>
-          </proto>
+          <proto name="tcp"></proto>
+         </packet>
+         <packet>
+          <proto name="udp"></proto>
+         </packet>
+         <packet>
+          <proto name="igmp"></proto>
          </packet>
         </pdml>"#;
 
         let mut reader = quick_xml::Reader::from_reader(BufReader::new(xml.as_bytes()));
-
-        let msg = parse_xml(&mut reader, &[]).unwrap();
-        let pkt = match msg {
-            Some(p) => p,
+        match parse_xml(&mut reader, &[]).unwrap() {
+            Some(p) => assert!(p.layer_name("tcp").is_some()),
             _ => panic!("invalid Output type"),
-        };
-
-        assert_eq!(pkt.layers.len(), 1);
+        }
+        match parse_xml(&mut reader, &[]).unwrap() {
+            Some(p) => assert!(p.layer_name("udp").is_some()),
+            _ => panic!("invalid Output type"),
+        }
+        match parse_xml(&mut reader, &[]).unwrap() {
+            Some(p) => assert!(p.layer_name("igmp").is_some()),
+            _ => panic!("invalid Output type"),
+        }
+        match parse_xml(&mut reader, &[]).unwrap() {
+            None => (),
+            _ => panic!("invalid Output type"),
+        }
     }
 
     #[test]
-    fn test_parse_missing_optional_display() {
+    fn test_rtshark_field_in_field() {
         let xml = r#"
         <pdml>
          <packet>
-          <proto name="frame">
-          <field name="frame.time" show="test time" pos="0" size="0" />
+          <proto name="btcommon">
+            <field name="btcommon.eir_ad.entry.data" showname="Data: <data>" size="8" pos="39" show="<some data>" value="<some data>">
+              <field name="_ws.expert" showname="Expert Info (Note/Undecoded): Undecoded" size="0" pos="39">
+                <field name="btcommon.eir_ad.undecoded" showname="Undecoded" size="0" pos="0" show="" value=""/>
+                <field name="_ws.expert.message" showname="Message: Undecoded" hide="yes" size="0" pos="0" show="Undecoded"/>
+                <field name="_ws.expert.severity" showname="Severity level: Note" size="0" pos="0" show="4194304"/>
+                <field name="_ws.expert.group" showname="Group: Undecoded" size="0" pos="0" show="83886080"/>
+              </field>
+            </field>
           </proto>
          </packet>
         </pdml>"#;
 
-        let mut reader = quick_xml::Reader::from_reader(BufReader::new(xml.as_bytes()));
+        let mut reader = quick_xml::Reader::from_reader(BufReader::new(xml.as_bytes()));
+        match parse_xml(&mut reader, &[]).unwrap() {
+            Some(p) => match p.layer_name("btcommon") {
+                Some(layer) => {
+                    layer
+                        .metadata("btcommon.eir_ad.entry.data")
+                        .unwrap_or_else(|| panic!("Missing btcommon.eir_ad.entry.data"));
+
+                    layer
+                        .metadata("btcommon.eir_ad.undecoded")
+                        .unwrap_or_else(|| panic!("Missing btcommon.eir_ad.undecoded"));
+
+                    // "_ws.expert" is skipped, so its child is attached to the closest parent
+                    let data = &layer.tree()[0];
+                    assert_eq!(data.name(), "btcommon.eir_ad.entry.data");
+                    assert_eq!(data.children()[0].name(), "btcommon.eir_ad.undecoded");
+                }
+                None => panic!("missing protocol"),
+            },
+            _ => panic!("invalid Output type"),
+        }
+    }
+
+    const XML_TCP_FLAGS: &str = r#"
+    <pdml>
+     <packet>
+      <proto name="tcp">
+       <field name="tcp.srcport" show="52796" value="ce3c"/>
+       <field name="tcp.flags" show="0x0018" value="018">
+        <field name="tcp.flags.res" show="False" value="0"/>
+        <field name="tcp.flags.push" show="True" value="1"/>
+        <field name="tcp.flags.str" show="·······AP···" value="0018"/>
+       </field>
+       <field name="tcp.window_size_value" show="512" value="0200"/>
+      </proto>
+     </packet>
+    </pdml>"#;
+
+    #[test]
+    fn test_parse_field_tree() {
+        let mut reader = quick_xml::Reader::from_reader(BufReader::new(XML_TCP_FLAGS.as_bytes()));
+
+        let pkt = parse_xml(&mut reader, &[]).unwrap().unwrap();
+        let tcp = pkt.layer_name("tcp").unwrap();
+
+        let roots: Vec<&str> = tcp.tree().iter().map(|m| m.name()).collect();
+        assert_eq!(roots, ["tcp.srcport", "tcp.flags", "tcp.window_size_value"]);
+
+        let flags = tcp.metadata("tcp.flags").unwrap();
+        let children: Vec<&str> = flags.children().iter().map(|m| m.name()).collect();
+        assert_eq!(
+            children,
+            ["tcp.flags.res", "tcp.flags.push", "tcp.flags.str"]
+        );
+
+        // child metadata are still reachable by name
+        assert!(tcp.metadata("tcp.flags.push").unwrap().value().eq("True"));
+    }
+
+    #[test]
+    fn test_parse_field_tree_flat_iter() {
+        let mut reader = quick_xml::Reader::from_reader(BufReader::new(XML_TCP_FLAGS.as_bytes()));
+
+        let pkt = parse_xml(&mut reader, &[]).unwrap().unwrap();
+        let tcp = pkt.layer_name("tcp").unwrap().clone();
+
+        let expected = [
+            "tcp.srcport",
+            "tcp.flags",
+            "tcp.flags.res",
+            "tcp.flags.push",
+            "tcp.flags.str",
+            "tcp.window_size_value",
+        ];
+
+        let names: Vec<&str> = tcp.iter().map(|m| m.name()).collect();
+        assert_eq!(names, expected);
+
+        let owned: Vec<Metadata> = tcp.into_iter().collect();
+        let names: Vec<&str> = owned.iter().map(|m| m.name()).collect();
+        assert_eq!(names, expected);
+        assert!(owned.iter().all(|m| m.children().is_empty()));
+    }
+
+    #[test]
+    fn test_parse_field_tree_filtered_parent() {
+        let mut reader = quick_xml::Reader::from_reader(BufReader::new(XML_TCP_FLAGS.as_bytes()));
+
+        let pkt = parse_xml(&mut reader, &["tcp.flags".to_string()])
+            .unwrap()
+            .unwrap();
+        let tcp = pkt.layer_name("tcp").unwrap();
+
+        // children of a filtered out field are moved to the parent level
+        assert!(tcp.metadata("tcp.flags").is_none());
+        let roots: Vec<&str> = tcp.tree().iter().map(|m| m.name()).collect();
+        assert_eq!(
+            roots,
+            [
+                "tcp.srcport",
+                "tcp.flags.res",
+                "tcp.flags.push",
+                "tcp.flags.str",
+                "tcp.window_size_value"
+            ]
+        );
+    }
+
+    /// Create a RTShark reading the output of a shell script instead of TShark.
+    #[cfg(target_family = "unix")]
+    pub(crate) fn fake_rtshark(script: &str, output_format: OutputFormat) -> RTShark {
+        let child = Command::new("sh")
+            .args(["-c", script])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        RTShark::new(child, output_format, vec![], None, false)
+    }
+
+    /// PDML output of packets with the given frame numbers.
+    #[cfg(target_family = "unix")]
+    pub(crate) fn pdml(frame_numbers: &[u64]) -> String {
+        let packets: String = frame_numbers
+            .iter()
+            .map(|n| {
+                format!(
+                    r#"<packet>
+<proto name="geninfo"><field name="num" show="{n}"/></proto>
+</packet>
+"#
+                )
+            })
+            .collect();
+        format!("<pdml>\n{packets}</pdml>\n")
+    }
+
+    /// Write an executable script, like a fake TShark.
+    #[cfg(target_family = "unix")]
+    pub(crate) fn write_script(dir: &std::path::Path, name: &str, content: &str) {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = dir.join(name);
+        std::fs::write(&path, content).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    /// PATH finding the scripts of a directory first, for [RTSharkBuilderReady::env_path].
+    #[cfg(target_family = "unix")]
+    pub(crate) fn env_path(dir: &std::path::Path) -> String {
+        format!("{}:{}", dir.display(), std::env::var("PATH").unwrap())
+    }
 
-        let msg = parse_xml(&mut reader, &[]).unwrap();
-        let pkt = match msg {
-            Some(p) => p,
-            _ => panic!("invalid Output type"),
-        };
+    /// Environment variable telling test_kill_on_parent_death_child where to spawn TShark.
+    #[cfg(target_os = "linux")]
+    const PARENT_DEATH_DIR: &str = "RTSHARK_TEST_PARENT_DEATH_DIR";
 
-        assert_eq!(pkt.layers.len(), 1);
+    /// Spawn a fake TShark, then wait to be killed by test_kill_on_parent_death.
+    /// Nothing is done when run as a normal test.
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_kill_on_parent_death_child() {
+        let Ok(dir) = std::env::var(PARENT_DEATH_DIR) else {
+            return;
+        };
+        let env_path = env_path(std::path::Path::new(&dir));
+        let _rtshark = RTSharkBuilder::builder()
+            .input_path("eth0")
+            .live_capture()
+            .kill_on_parent_death()
+            .env_path(&env_path)
+            .spawn()
+            .unwrap();
+        std::thread::sleep(std::time::Duration::from_secs(60));
     }
 
+    #[cfg(target_os = "linux")]
     #[test]
-    fn test_parse_missing_mandatory_name() {
-        let xml = r#"
-        <pdml>
-         <packet>
-          <proto name="frame">
-          <field show="test time" pos="0" size="0" showname="test time display"/>
-          </proto>
-         </packet>
-        </pdml>"#;
+    fn test_kill_on_parent_death() {
+        // TShark starting a dumpcap child, whose pid is written next to the script
+        const FAKE_TSHARK: &str = r#"#!/bin/sh
+sleep 60 &
+echo $! > "$(dirname "$0")/dumpcap.pid.tmp"
+mv "$(dirname "$0")/dumpcap.pid.tmp" "$(dirname "$0")/dumpcap.pid"
+wait
+"#;
+        let tmp_dir = tempdir::TempDir::new("test_parent_death").unwrap();
+        write_script(tmp_dir.path(), "tshark", FAKE_TSHARK);
+
+        // this test binary, running only test_kill_on_parent_death_child, is the parent
+        let mut parent = Command::new(std::env::current_exe().unwrap())
+            .args([
+                "--exact",
+                "tests::test_kill_on_parent_death_child",
+                "--test-threads=1",
+            ])
+            .env(PARENT_DEATH_DIR, tmp_dir.path())
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap();
 
-        let mut reader = quick_xml::Reader::from_reader(BufReader::new(xml.as_bytes()));
+        let pid_path = tmp_dir.path().join("dumpcap.pid");
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        while !pid_path.exists() && std::time::Instant::now() < deadline {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        let dumpcap = std::fs::read_to_string(&pid_path).unwrap();
+        let stat_path = format!("/proc/{}/stat", dumpcap.trim());
+        // exited, or zombie waiting to be reaped
+        let running = || {
+            std::fs::read_to_string(&stat_path)
+                .is_ok_and(|stat| !stat.rsplit(')').next().unwrap_or("").starts_with(" Z"))
+        };
+        assert!(running());
 
-        let msg = parse_xml(&mut reader, &[]);
+        parent.kill().unwrap();
+        parent.wait().unwrap();
 
-        match msg {
-            Err(_) => (),
-            _ => panic!("invalid result"),
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while running() && std::time::Instant::now() < deadline {
+            std::thread::sleep(std::time::Duration::from_millis(10));
         }
+        assert!(!running());
     }
 
+    #[cfg(target_family = "unix")]
     #[test]
-    fn test_parse_all_value_fields_available() {
-        // Issue #1 : uses pyshark-like algorithm to display the best 'value' for this field
-        // https://github.com/KimiNewt/pyshark/blob/master/src/pyshark/packet/fields.py#L14
-        // try first "show", then "value", finally "showname"
+    fn test_read_timeout_partial_packet() {
+        // the second packet is printed in two parts
+        let script = r#"
+            printf '<pdml>\n<packet>\n<proto name="frame">\n<field name="frame.number" show="1"/>\n</proto>\n</packet>\n'
+            printf '<packet>\n<proto name="frame">\n<field name="frame'
+            sleep 1
+            printf '.number" show="2"/>\n</proto>\n</packet>\n'
+            sleep 1
+            printf '<packet>\n<proto name="frame">\n<field name="frame.number" show="3"/>\n</proto>\n</packet>\n</pdml>\n'
+        "#;
+        let mut rtshark = fake_rtshark(script, OutputFormat::Pdml);
+        let timeout = std::time::Duration::from_millis(200);
+        let number = |packet: Packet| {
+            packet
+                .layer_name("frame")
+                .unwrap()
+                .metadata("frame.number")
+                .unwrap()
+                .value()
+                .to_owned()
+        };
 
-        let xml = r#"
-        <pdml>
-         <packet>
-          <proto name="icmp">
-           <field name="data" show="data is aa" value="0a" showname="data: a0"/>
-          </proto>
-         </packet>
-        </pdml>"#;
+        let Ok(ReadOutcome::Packet(packet)) = rtshark.read_timeout(timeout) else {
+            panic!("first packet expected")
+        };
+        assert_eq!(number(packet), "1");
+        assert!(matches!(
+            rtshark.read_timeout(timeout),
+            Ok(ReadOutcome::Timeout)
+        ));
+
+        let packet = loop {
+            match rtshark.read_timeout(timeout) {
+                Ok(ReadOutcome::Packet(packet)) => break packet,
+                Ok(ReadOutcome::Timeout) => (),
+                other => panic!("unexpected outcome {other:?}"),
+            }
+        };
+        assert_eq!(number(packet), "2");
+
+        // read() can be used after read_timeout()
+        assert!(matches!(
+            rtshark.read_timeout(timeout),
+            Ok(ReadOutcome::Timeout)
+        ));
+        assert_eq!(number(rtshark.read().unwrap().unwrap()), "3");
+        assert!(rtshark.read().unwrap().is_none());
+        assert!(matches!(
+            rtshark.read_timeout(timeout),
+            Ok(ReadOutcome::Eof)
+        ));
+    }
 
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_read_after_read_timeout() {
+        // invalid end tag in packet 3
+        let xml = r#"<pdml>
+<packet>
+<proto name="geninfo"><field name="num" show="1"/></proto>
+</packet>
+<packet>
+<proto name="geninfo"><field name="num" show="2"/></proto>
+</packet>
+<packet>
+<proto name="geninfo"><field name="num" show="3"/></geninfo>
+</packet>
+<packet>
+<proto name="geninfo"><field name="num" show="4"/></proto>
+</packet>
+</pdml>
+"#;
+        // error position when the whole output is parsed by read()
         let mut reader = quick_xml::Reader::from_reader(BufReader::new(xml.as_bytes()));
+        parse_xml(&mut reader, &[]).unwrap();
+        parse_xml(&mut reader, &[]).unwrap();
+        let Err(RTSharkError::XmlParse { offset, .. }) = parse_xml(&mut reader, &[]) else {
+            panic!("XML error expected")
+        };
 
-        let pkt = parse_xml(&mut reader, &[]).unwrap().unwrap();
-
-        let icmp = pkt.layer_name("icmp").unwrap();
-        let data = icmp.metadata("data").unwrap();
-        assert!(data.value().eq("data is aa"));
-        assert!(data.raw_value().eq("0a"));
-        assert_eq!(data.display(), Some("data: a0"));
+        let script = format!("printf '%s' '{xml}'");
+        let mut rtshark = fake_rtshark(&script, OutputFormat::Pdml);
+        let timeout = std::time::Duration::from_secs(5);
+        assert_eq!(rtshark.read().unwrap().unwrap().frame_number(), Some(1));
+        let Ok(ReadOutcome::Packet(packet)) = rtshark.read_timeout(timeout) else {
+            panic!("second packet expected")
+        };
+        assert_eq!(packet.frame_number(), Some(2));
+
+        // read() continues where read_timeout() stopped
+        assert!(matches!(
+            rtshark.read(),
+            Err(RTSharkError::XmlParse { offset: position, .. }) if position == offset
+        ));
+        assert_eq!(rtshark.read().unwrap().unwrap().frame_number(), Some(4));
+        assert!(rtshark.read().unwrap().is_none());
     }
 
+    #[cfg(target_family = "unix")]
     #[test]
-    fn test_parse_missing_show_attribute() {
-        // Issue #1 : uses pyshark-like algorithm to display the best 'value' for this field
-        // https://github.com/KimiNewt/pyshark/blob/master/src/pyshark/packet/fields.py#L14
-        // try first "show", then "value", finally "showname"
-
-        let xml = r#"
-        <pdml>
-         <packet>
-          <proto name="icmp">
-           <field name="data" value="0a" showname="data: a0"/>
-          </proto>
-         </packet>
-        </pdml>"#;
-
-        let mut reader = quick_xml::Reader::from_reader(BufReader::new(xml.as_bytes()));
+    fn test_read_timeout_truncated() {
+        // TShark output cut in the middle of the second packet
+        let script = r#"printf '<pdml>\n<packet>\n<proto name="frame"/>\n</packet>\n<packet>\n<proto name="frame">\n<field name="fr'"#;
+
+        let mut rtshark = fake_rtshark(script, OutputFormat::Pdml);
+        assert!(rtshark.read().unwrap().is_some());
+        let Err(RTSharkError::XmlParse { offset, .. }) = rtshark.read() else {
+            panic!("XML error expected")
+        };
 
-        let pkt = parse_xml(&mut reader, &[]).unwrap().unwrap();
+        // read_timeout() reports the same error
+        let timeout = std::time::Duration::from_secs(5);
+        let mut rtshark = fake_rtshark(script, OutputFormat::Pdml);
+        assert!(matches!(
+            rtshark.read_timeout(timeout),
+            Ok(ReadOutcome::Packet(_))
+        ));
+        assert!(matches!(
+            rtshark.read_timeout(timeout),
+            Err(RTSharkError::XmlParse { offset: position, .. }) if position == offset
+        ));
+
+        assert!(matches!(
+            rtshark.read_timeout(timeout),
+            Ok(ReadOutcome::Eof)
+        ));
+
+        // the packet is cut between two lines
+        let script = r#"printf '<pdml>\n<packet>\n<proto name="frame">\n'"#;
+        let mut rtshark = fake_rtshark(script, OutputFormat::Pdml);
+        assert!(matches!(
+            rtshark.read_timeout(timeout),
+            Err(RTSharkError::XmlParse { offset: 7, .. })
+        ));
+    }
 
-        let icmp = pkt.layer_name("icmp").unwrap();
-        let data = icmp.metadata("data").unwrap();
-        assert!(data.value().eq("0a"));
-        assert!(data.raw_value() == data.value());
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_read_unmatched_end() {
+        let script =
+            r#"printf '<pdml>\n<packet>\n<proto name="frame"/>\n</packet>\n</proto>\n</pdml>\n'"#;
+        let mut rtshark = fake_rtshark(script, OutputFormat::Pdml);
+        assert!(rtshark.read().unwrap().is_some());
+        assert!(matches!(rtshark.read(), Err(RTSharkError::XmlParse { .. })));
     }
 
+    #[cfg(target_family = "unix")]
     #[test]
-    fn test_parse_missing_show_and_value_attributes() {
-        // Issue #1 : uses pyshark-like algorithm to display the best 'value' for this field
-        // https://github.com/KimiNewt/pyshark/blob/master/src/pyshark/packet/fields.py#L14
-        // try first "show", then "value", finally "showname"
+    fn test_read_timeout_json() {
+        let script = r#"
+            printf '[\n{"_source": {"layers": {"frame": {"frame.number": "1"}'
+            sleep 1
+            printf '}}}\n]\n'
+        "#;
+        let mut rtshark = fake_rtshark(script, OutputFormat::Json);
+        let timeout = std::time::Duration::from_millis(200);
+        assert!(matches!(
+            rtshark.read_timeout(timeout),
+            Ok(ReadOutcome::Timeout)
+        ));
+        let packet = loop {
+            match rtshark.read_timeout(timeout) {
+                Ok(ReadOutcome::Packet(packet)) => break packet,
+                Ok(ReadOutcome::Timeout) => (),
+                other => panic!("unexpected outcome {other:?}"),
+            }
+        };
+        let frame = packet.layer_name("frame").unwrap();
+        assert_eq!(frame.metadata("frame.number").unwrap().value(), "1");
+        assert!(matches!(
+            rtshark.read_timeout(timeout),
+            Ok(ReadOutcome::Eof)
+        ));
+    }
 
+    #[test]
+    fn test_parse_frame_number() {
         let xml = r#"
         <pdml>
          <packet>
-          <proto name="icmp">
-           <field name="data" showname="data: a0"/>
+          <proto name="geninfo" pos="0" showname="General information" size="28">
+           <field name="num" pos="0" show="42" showname="Number" value="2a" size="28"/>
+          </proto>
+          <proto name="frame">
+           <field name="frame.time" show="test time" pos="0" size="0" showname="test time display"/>
           </proto>
          </packet>
         </pdml>"#;
@@ -1702,318 +3934,369 @@ mod tests {
         let mut reader = quick_xml::Reader::from_reader(BufReader::new(xml.as_bytes()));
 
         let pkt = parse_xml(&mut reader, &[]).unwrap().unwrap();
-
-        let icmp = pkt.layer_name("icmp").unwrap();
-        let data = icmp.metadata("data").unwrap();
-        assert!(data.value().eq("data: a0"));
-        assert!(data.raw_value() == data.value());
+        assert_eq!(pkt.frame_number(), Some(42));
+        assert!(pkt.layer_name("geninfo").is_none());
     }
 
+    const XML_INVALID_SECOND_PACKET: &str = r#"<pdml>
+<packet>
+<proto name="frame"><field name="frame.number" show="1"/></proto>
+</packet>
+<packet>
+<proto name="frame"><field name="frame.number" show="2"/></proto>
+<proto showname="no name"><field name="x.y" show="2"/></proto>
+<proto name="udp"><field name="udp.port" show="53"/></proto>
+</packet>
+<packet>
+<proto name="frame"><field name="frame.number" show="3"/></proto>
+</packet>
+</pdml>
+"#;
+
     #[test]
-    fn test_parse_missing_any_show() {
-        let xml = r#"
-        <pdml>
-         <packet>
-          <proto name="frame">
-          <field name="frame.time" pos="0" size="0"/>
-          </proto>
-         </packet>
-        </pdml>"#;
+    fn test_parse_invalid_packet_skipped() {
+        let mut reader =
+            quick_xml::Reader::from_reader(BufReader::new(XML_INVALID_SECOND_PACKET.as_bytes()));
 
-        let mut reader = quick_xml::Reader::from_reader(BufReader::new(xml.as_bytes()));
+        assert!(parse_xml(&mut reader, &[]).unwrap().is_some());
+        let err = parse_xml(&mut reader, &[]).unwrap_err();
+        assert!(!err.is_fatal());
 
-        let msg = parse_xml(&mut reader, &[]);
-        match msg {
-            Err(_) => (),
-            _ => panic!("invalid result"),
-        }
+        // next packet is read from its beginning
+        let pkt = parse_xml(&mut reader, &[]).unwrap().unwrap();
+        let frame = pkt.layer_name("frame").unwrap();
+        assert_eq!(frame.metadata("frame.number").unwrap().value(), "3");
+        assert!(pkt.layer_name("udp").is_none());
+        assert!(parse_xml(&mut reader, &[]).unwrap().is_none());
     }
 
-    const XML_TCP: &str = r#"
-    <pdml>
-     <packet>
-      <proto name="frame">
-       <field name="frame.time" show="Mar  5, 2021 08:49:52.736275000 CET"/>
-      </proto>
-      <proto name="ip">
-       <field name="ip.src" show="1.1.1.1" />
-       <field name="ip.dst" show="1.1.1.2" />
-      </proto>
-      <proto name="tcp">
-       <field name="tcp.srcport" show="52796" value="ce3c"/>
-       <field name="tcp.dstport" show="5432" value="1538"/>
-       <field name="tcp.seq_raw" show="1963007432" value="75011dc8"/>
-       <field name="tcp.stream" show="4"/>
-      </proto>
-     </packet>
-    </pdml>"#;
-
+    #[cfg(target_family = "unix")]
     #[test]
-    fn test_access_packet_into_iter() {
-        let mut reader = quick_xml::Reader::from_reader(BufReader::new(XML_TCP.as_bytes()));
+    fn test_rtshark_iterator() {
+        let script = format!("printf '%s' '{XML_INVALID_SECOND_PACKET}'");
+        let rtshark = fake_rtshark(&script, OutputFormat::Pdml);
+
+        let results: Vec<Result<Packet>> = rtshark.collect();
+        assert_eq!(results.len(), 3);
+        assert!(results[0].is_ok());
+        assert!(matches!(results[1], Err(RTSharkError::InvalidData { .. })));
+        assert!(results[2].is_ok());
+
+        // the iteration ends after a fatal error
+        let script = "echo 'tshark: fatal' >&2; exit 2";
+        let mut rtshark = fake_rtshark(script, OutputFormat::Pdml);
+        let mut packets = rtshark.packets();
+        assert!(matches!(
+            packets.next(),
+            Some(Err(RTSharkError::TsharkExited { .. }))
+        ));
+        assert!(packets.next().is_none());
+        assert!(packets.next().is_none());
+        assert_eq!(rtshark.stderr_lines(), ["tshark: fatal"]);
+    }
 
-        let msg = parse_xml(&mut reader, &[]).unwrap();
-        let pkt = match msg {
-            Some(p) => p,
-            _ => panic!("invalid Output type"),
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_rtshark_recover_xml_errors() {
+        // invalid end tag in packet 2, no end in packet 3, packet 5 is cut
+        let xml = r#"<pdml>
+<packet>
+<proto name="frame"><field name="frame.number" show="1"/></proto>
+</packet>
+<packet>
+<proto name="frame"><field name="frame.number" show="2"/></frame>
+</packet>
+<packet>
+<proto name="frame"><field name="frame.number" show="3"/></proto>
+<packet>
+<proto name="frame"><field name="frame.number" show="4"/></proto>
+</packet>
+<packet>
+<proto name="frame">"#;
+        let offsets: Vec<u64> = xml
+            .match_indices("<packet>")
+            .map(|(i, _)| i as u64)
+            .collect();
+        let script = format!("printf '%s' '{xml}'");
+
+        let mut rtshark = fake_rtshark(&script, OutputFormat::Pdml);
+        rtshark.recover_xml_errors = true;
+        let results: Vec<Result<Packet>> = rtshark.collect();
+        assert_eq!(results.len(), 5);
+
+        let number = |packet: &Packet| {
+            let frame = packet.layer_name("frame").unwrap();
+            frame.metadata("frame.number").unwrap().value().to_owned()
         };
+        assert_eq!(number(results[0].as_ref().unwrap()), "1");
+        assert_eq!(number(results[3].as_ref().unwrap()), "4");
 
-        assert_eq!(pkt.layers.len(), 3);
+        for (i, packet) in [(1, &results[1]), (2, &results[2]), (4, &results[4])] {
+            let Err(RTSharkError::SkippedPacket(skipped)) = packet else {
+                panic!("packet {} should be skipped: {packet:?}", i + 1)
+            };
+            assert_eq!(skipped.byte_offset(), offsets[i]);
+            assert!(!skipped.reason().is_empty());
+        }
 
-        let mut iter = pkt.into_iter();
-        let frame = iter.next().unwrap();
-        assert!(frame.name().eq("frame"));
-        let ip = iter.next().unwrap();
-        assert!(ip.name().eq("ip"));
-        let tcp = iter.next().unwrap();
-        assert!(tcp.name().eq("tcp"));
-        assert!(iter.next().is_none());
+        // without recovery, the XML error is fatal
+        let rtshark = fake_rtshark(&script, OutputFormat::Pdml);
+        let results: Vec<Result<Packet>> = rtshark.collect();
+        assert_eq!(results.len(), 2);
+        assert!(matches!(results[1], Err(RTSharkError::XmlParse { .. })));
     }
 
     #[test]
-    fn test_access_packet_iter() {
-        let mut reader = quick_xml::Reader::from_reader(BufReader::new(XML_TCP.as_bytes()));
+    fn test_rtshark_input_pcap() {
+        let pcap = include_bytes!("test.pcap");
 
-        let msg = parse_xml(&mut reader, &[]).unwrap();
-        let pkt = match msg {
-            Some(p) => p,
+        // create temp dir and copy pcap in it
+        let tmp_dir = tempdir::TempDir::new("test_pcap").unwrap();
+        let pcap_path = tmp_dir.path().join("file.pcap");
+        let mut output = std::fs::File::create(&pcap_path).expect("unable to open file");
+        output.write_all(pcap).expect("unable to write pcap");
+        output.flush().expect("unable to flush");
+
+        // spawn tshark on it
+        let builder = RTSharkBuilder::builder().input_path(pcap_path.to_str().unwrap());
+
+        let mut rtshark = builder.spawn().unwrap();
+
+        // read a packet
+        match rtshark.read().unwrap() {
+            Some(p) => assert!(p.layer_name("udp").is_some()),
             _ => panic!("invalid Output type"),
-        };
+        }
 
-        assert_eq!(pkt.layers.len(), 3);
+        loop {
+            match rtshark.read().unwrap() {
+                None => break,
+                Some(_) => todo!(),
+            }
+        }
 
-        let mut iter = pkt.iter();
-        let frame = iter.next().unwrap();
-        assert!(frame.name().eq("frame"));
-        let ip = iter.next().unwrap();
-        assert!(ip.name().eq("ip"));
-        let tcp = iter.next().unwrap();
-        assert!(tcp.name().eq("tcp"));
-        assert!(iter.next().is_none());
+        rtshark.kill();
+
+        assert!(rtshark.pid().is_none());
+
+        tmp_dir.close().expect("Error deleting fifo dir");
     }
 
     #[test]
-    fn test_access_layer_index() {
-        let mut reader = quick_xml::Reader::from_reader(BufReader::new(XML_TCP.as_bytes()));
+    fn test_rtshark_input_pcap_output_format() {
+        let pcap = include_bytes!("test.pcap");
 
-        let msg = parse_xml(&mut reader, &[]).unwrap();
-        let pkt = match msg {
-            Some(p) => p,
-            _ => panic!("invalid Output type"),
-        };
+        // create temp dir and copy pcap in it
+        let tmp_dir = tempdir::TempDir::new("test_pcap").unwrap();
+        let pcap_path = tmp_dir.path().join("file.pcap");
+        let mut output = std::fs::File::create(&pcap_path).expect("unable to open file");
+        output.write_all(pcap).expect("unable to write pcap");
+        output.flush().expect("unable to flush");
 
-        assert_eq!(pkt.layers.len(), 3);
+        let mut pdml = RTSharkBuilder::builder()
+            .input_path(pcap_path.to_str().unwrap())
+            .spawn()
+            .unwrap();
+        let expected = pdml.read().unwrap().unwrap();
 
-        assert!(pkt.layer_index(0).unwrap().name().eq("frame"));
-        assert!(pkt.layer_index(1).unwrap().name().eq("ip"));
-        assert!(pkt.layer_index(2).unwrap().name().eq("tcp"));
+        for format in [OutputFormat::Json, OutputFormat::Ek] {
+            let mut rtshark = RTSharkBuilder::builder()
+                .input_path(pcap_path.to_str().unwrap())
+                .output_format(format)
+                .spawn()
+                .unwrap();
+
+            let packet = rtshark.read().unwrap().unwrap();
+            assert_eq!(packet.timestamp_micros(), expected.timestamp_micros());
+            assert_eq!(
+                packet.iter().map(|l| l.name()).collect::<Vec<_>>(),
+                expected.iter().map(|l| l.name()).collect::<Vec<_>>()
+            );
+            assert_eq!(
+                packet
+                    .layer_name("udp")
+                    .unwrap()
+                    .metadata("udp.srcport")
+                    .unwrap()
+                    .value(),
+                expected
+                    .layer_name("udp")
+                    .unwrap()
+                    .metadata("udp.srcport")
+                    .unwrap()
+                    .value()
+            );
+
+            assert!(rtshark.read().unwrap().is_none());
+        }
 
-        assert!(pkt.layer_index(3).is_none());
+        tmp_dir.close().expect("Error deleting fifo dir");
     }
 
     #[test]
-    fn test_access_layer_name() {
-        let mut reader = quick_xml::Reader::from_reader(BufReader::new(XML_TCP.as_bytes()));
+    fn test_rtshark_input_pcap_fields() {
+        let pcap = include_bytes!("test.pcap");
 
-        let msg = parse_xml(&mut reader, &[]).unwrap();
-        let pkt = match msg {
-            Some(p) => p,
-            _ => panic!("invalid Output type"),
-        };
+        // create temp dir and copy pcap in it
+        let tmp_dir = tempdir::TempDir::new("test_pcap").unwrap();
+        let pcap_path = tmp_dir.path().join("file.pcap");
+        let mut output = std::fs::File::create(&pcap_path).expect("unable to open file");
+        output.write_all(pcap).expect("unable to write pcap");
+        output.flush().expect("unable to flush");
 
-        assert_eq!(pkt.layers.len(), 3);
+        // no whitelist, no columns
+        let builder = RTSharkBuilder::builder().input_path(pcap_path.to_str().unwrap());
+        assert!(builder.spawn_fields().is_err());
 
-        assert!(pkt.layer_name("frame").unwrap().name().eq("frame"));
-        assert!(pkt.layer_name("ip").unwrap().name().eq("ip"));
-        assert!(pkt.layer_name("tcp").unwrap().name().eq("tcp"));
+        let mut reader = builder
+            .metadata_whitelist("ip.src")
+            .metadata_whitelist("tcp.srcport")
+            .metadata_whitelist("udp.srcport")
+            .spawn_fields()
+            .unwrap();
+
+        let row = reader.read().unwrap().unwrap();
+        assert_eq!(row.columns(), ["ip.src", "tcp.srcport", "udp.srcport"]);
+        assert_eq!(row.value("ip.src"), Some("127.0.0.1"));
+        assert!(row.values("tcp.srcport").unwrap().is_empty());
+        assert_eq!(row.values("udp.srcport").unwrap().len(), 1);
+
+        assert!(reader.read().unwrap().is_none());
+        assert!(reader.pid().is_none());
 
-        assert!(pkt.layer_name("udp").is_none());
+        tmp_dir.close().expect("Error deleting fifo dir");
     }
 
     #[test]
-    fn test_access_layer_name_with_tunnel() {
-        let xml = r#"
-        <pdml>
-         <packet>
-          <proto name="frame">
-           <field name="frame.time" show="Mar  5, 2021 08:49:52.736275000 CET"/>
-          </proto>
-          <proto name="ip">
-           <field name="ip.src" show="10.215.215.9" />
-           <field name="ip.dst" show="10.215.215.10" />
-          </proto>
-          <proto name="ip">
-           <field name="ip.src" show="10.10.215.9" />
-           <field name="ip.dst" show="10.10.215.10" />
-          </proto>
-          <proto name="tcp">
-           <field name="tcp.srcport" show="52796" value="ce3c"/>
-           <field name="tcp.dstport" show="5432" value="1538"/>
-           <field name="tcp.seq_raw" show="1963007432" value="75011dc8"/>
-           <field name="tcp.stream" show="4"/>
-          </proto>
-         </packet>
-        </pdml>"#;
+    fn test_rtshark_input_pcap_summary() {
+        let pcap = include_bytes!("test.pcap");
 
-        let mut reader = quick_xml::Reader::from_reader(BufReader::new(xml.as_bytes()));
+        // create temp dir and copy pcap in it
+        let tmp_dir = tempdir::TempDir::new("test_pcap").unwrap();
+        let pcap_path = tmp_dir.path().join("file.pcap");
+        let mut output = std::fs::File::create(&pcap_path).expect("unable to open file");
+        output.write_all(pcap).expect("unable to write pcap");
+        output.flush().expect("unable to flush");
 
-        let msg = parse_xml(&mut reader, &[]).unwrap();
-        let pkt = match msg {
-            Some(p) => p,
-            _ => panic!("invalid Output type"),
-        };
+        // whitelist is ignored
+        let mut reader = RTSharkBuilder::builder()
+            .input_path(pcap_path.to_str().unwrap())
+            .metadata_whitelist("ip.src")
+            .spawn_summary()
+            .unwrap();
 
-        assert_eq!(pkt.layers.len(), 4);
+        let summary = reader.read().unwrap().unwrap();
+        assert_eq!(summary.column("No."), Some("1"));
+        assert_eq!(summary.column("Source"), Some("127.0.0.1"));
+        assert!(reader.columns().is_some());
 
-        assert!(pkt.layer_name("frame").unwrap().name().eq("frame"));
-        assert!(pkt.layer_name("ip").unwrap().name().eq("ip"));
-        assert!(pkt.layer_name("ip").unwrap().index() == 1usize);
-        assert!(pkt.layer_index(1).unwrap().name().eq("ip"));
-        assert!(pkt.layer_index(2).unwrap().name().eq("ip"));
-        assert!(pkt.layer_name("tcp").unwrap().name().eq("tcp"));
+        assert!(reader.read().unwrap().is_none());
 
-        assert!(pkt.layer_name("udp").is_none());
+        tmp_dir.close().expect("Error deleting fifo dir");
     }
 
     #[test]
-    fn test_access_layer_iter() {
-        let mut reader = quick_xml::Reader::from_reader(BufReader::new(XML_TCP.as_bytes()));
+    fn test_rtshark_input_pcap_wait() {
+        let pcap = include_bytes!("test.pcap");
 
-        let msg = parse_xml(&mut reader, &[]).unwrap();
-        let pkt = match msg {
-            Some(p) => p,
-            _ => panic!("invalid Output type"),
-        };
+        // create temp dir and copy pcap in it
+        let tmp_dir = tempdir::TempDir::new("test_pcap").unwrap();
+        let pcap_path = tmp_dir.path().join("file.pcap");
+        let mut output = std::fs::File::create(&pcap_path).expect("unable to open file");
+        output.write_all(pcap).expect("unable to write pcap");
+        output.flush().expect("unable to flush");
 
-        let ip = pkt.layer_name("ip").unwrap();
-        let mut iter = ip.iter();
-        assert!(iter.next().unwrap().name().eq("ip.src"));
-        assert!(iter.next().unwrap().name().eq("ip.dst"));
-        assert!(iter.next().is_none());
-    }
+        let mut rtshark = RTSharkBuilder::builder()
+            .input_path(pcap_path.to_str().unwrap())
+            .spawn()
+            .unwrap();
 
-    #[test]
-    fn test_access_layer_into_iter() {
-        let mut reader = quick_xml::Reader::from_reader(BufReader::new(XML_TCP.as_bytes()));
+        while rtshark.read().unwrap().is_some() {}
 
-        let msg = parse_xml(&mut reader, &[]).unwrap();
-        let pkt = match msg {
-            Some(p) => p,
-            _ => panic!("invalid Output type"),
-        };
+        let report = rtshark.wait().unwrap();
+        assert!(report.success());
+        assert_eq!(report.code(), Some(0));
+        assert!(!report.killed());
+        assert_eq!(report.packets_delivered(), 1);
+        assert!(rtshark.pid().is_none());
 
-        let ip = pkt.layer_name("ip").unwrap().clone();
-        let mut iter = ip.into_iter();
-        assert!(iter.next().unwrap().name().eq("ip.src"));
-        assert!(iter.next().unwrap().name().eq("ip.dst"));
-        assert!(iter.next().is_none());
+        tmp_dir.close().expect("Error deleting fifo dir");
     }
 
     #[test]
-    fn test_access_layer_metadata() {
-        let mut reader = quick_xml::Reader::from_reader(BufReader::new(XML_TCP.as_bytes()));
+    fn test_rtshark_input_pcap_stop() {
+        let pcap = include_bytes!("test.pcap");
 
-        let msg = parse_xml(&mut reader, &[]).unwrap();
-        let pkt = match msg {
-            Some(p) => p,
-            _ => panic!("invalid Output type"),
-        };
+        // create temp dir and copy pcap in it
+        let tmp_dir = tempdir::TempDir::new("test_pcap").unwrap();
+        let pcap_path = tmp_dir.path().join("file.pcap");
+        let mut output = std::fs::File::create(&pcap_path).expect("unable to open file");
+        output.write_all(pcap).expect("unable to write pcap");
+        output.flush().expect("unable to flush");
 
-        let ip = pkt.layer_name("ip").unwrap();
-        let src = ip.metadata("ip.src").unwrap();
-        assert!(src.value().eq("1.1.1.1"));
+        let mut rtshark = RTSharkBuilder::builder()
+            .input_path(pcap_path.to_str().unwrap())
+            .spawn()
+            .unwrap();
 
-        let dst = ip.metadata("ip.dst").unwrap();
-        assert!(dst.value().eq("1.1.1.2"));
-    }
+        rtshark.stop(std::time::Duration::from_secs(5)).unwrap();
 
-    #[test]
-    fn test_parser_filter_metadata() {
-        let mut reader = quick_xml::Reader::from_reader(BufReader::new(XML_TCP.as_bytes()));
+        // no error is reported once stopped
+        while rtshark.read().unwrap().is_some() {}
 
-        let msg = parse_xml(&mut reader, &["ip.src".to_string()]).unwrap();
-        let pkt = match msg {
-            Some(p) => p,
-            _ => panic!("invalid Output type"),
-        };
+        let report = rtshark.wait().unwrap();
+        assert!(report.status().is_some());
+        assert!(rtshark.pid().is_none());
 
-        let ip = pkt.layer_name("ip").unwrap();
-        assert!(ip.metadata("ip.src").is_none());
-        assert!(ip.metadata("ip.dst").unwrap().value().eq("1.1.1.2"));
+        // graceful drop
+        let rtshark = RTSharkBuilder::builder()
+            .input_path(pcap_path.to_str().unwrap())
+            .graceful_drop(std::time::Duration::from_secs(5))
+            .spawn()
+            .unwrap();
+        drop(rtshark);
+
+        tmp_dir.close().expect("Error deleting fifo dir");
     }
 
     #[test]
-    fn test_parser_multiple_packets() {
-        let xml = r#"
-        <pdml>
-         <packet>
-          <proto name="tcp"></proto>
-         </packet>
-         <packet>
-          <proto name="udp"></proto>
-         </packet>
-         <packet>
-          <proto name="igmp"></proto>
-         </packet>
-        </pdml>"#;
+    fn test_rtshark_input_pcap_statistics() {
+        let pcap = include_bytes!("test.pcap");
 
-        let mut reader = quick_xml::Reader::from_reader(BufReader::new(xml.as_bytes()));
-        match parse_xml(&mut reader, &[]).unwrap() {
-            Some(p) => assert!(p.layer_name("tcp").is_some()),
-            _ => panic!("invalid Output type"),
-        }
-        match parse_xml(&mut reader, &[]).unwrap() {
-            Some(p) => assert!(p.layer_name("udp").is_some()),
-            _ => panic!("invalid Output type"),
-        }
-        match parse_xml(&mut reader, &[]).unwrap() {
-            Some(p) => assert!(p.layer_name("igmp").is_some()),
-            _ => panic!("invalid Output type"),
-        }
-        match parse_xml(&mut reader, &[]).unwrap() {
-            None => (),
-            _ => panic!("invalid Output type"),
-        }
-    }
+        // create temp dir and copy pcap in it
+        let tmp_dir = tempdir::TempDir::new("test_pcap").unwrap();
+        let pcap_path = tmp_dir.path().join("file.pcap");
+        let mut output = std::fs::File::create(&pcap_path).expect("unable to open file");
+        output.write_all(pcap).expect("unable to write pcap");
+        output.flush().expect("unable to flush");
 
-    #[test]
-    fn test_rtshark_field_in_field() {
-        let xml = r#"
-        <pdml>
-         <packet>
-          <proto name="btcommon">
-            <field name="btcommon.eir_ad.entry.data" showname="Data: <data>" size="8" pos="39" show="<some data>" value="<some data>">
-              <field name="_ws.expert" showname="Expert Info (Note/Undecoded): Undecoded" size="0" pos="39">
-                <field name="btcommon.eir_ad.undecoded" showname="Undecoded" size="0" pos="0" show="" value=""/>
-                <field name="_ws.expert.message" showname="Message: Undecoded" hide="yes" size="0" pos="0" show="Undecoded"/>
-                <field name="_ws.expert.severity" showname="Severity level: Note" size="0" pos="0" show="4194304"/>
-                <field name="_ws.expert.group" showname="Group: Undecoded" size="0" pos="0" show="83886080"/>
-              </field>
-            </field>
-          </proto>
-         </packet>
-        </pdml>"#;
+        let builder = RTSharkBuilder::builder().input_path(pcap_path.to_str().unwrap());
 
-        let mut reader = quick_xml::Reader::from_reader(BufReader::new(xml.as_bytes()));
-        match parse_xml(&mut reader, &[]).unwrap() {
-            Some(p) => match p.layer_name("btcommon") {
-                Some(layer) => {
-                    layer
-                        .metadata("btcommon.eir_ad.entry.data")
-                        .unwrap_or_else(|| panic!("Missing btcommon.eir_ad.entry.data"));
+        let conversations = builder.conversations(ConversationType::Udp).unwrap();
+        assert_eq!(conversations.len(), 1);
+        assert_eq!(conversations[0].frames(), 1);
 
-                    layer
-                        .metadata("btcommon.eir_ad.undecoded")
-                        .unwrap_or_else(|| panic!("Missing btcommon.eir_ad.undecoded"));
-                }
-                None => panic!("missing protocol"),
-            },
-            _ => panic!("invalid Output type"),
-        }
+        let endpoints = builder.endpoints(ConversationType::Ip).unwrap();
+        assert_eq!(endpoints.len(), 1);
+        assert_eq!(endpoints[0].address(), "127.0.0.1");
+
+        let hierarchy = builder.protocol_hierarchy().unwrap();
+        assert_eq!(hierarchy[0].frames(), 1);
+
+        let intervals = builder
+            .io_statistics(std::time::Duration::from_secs(1))
+            .unwrap();
+        assert_eq!(intervals.iter().map(|i| i.frames()).sum::<u64>(), 1);
+
+        assert!(builder.stats_tree("dns").is_ok());
+        assert!(builder.expert_info().is_ok());
+        assert!(builder.statistics("invalid,tap").is_err());
+
+        tmp_dir.close().expect("Error deleting fifo dir");
     }
 
     #[test]
-    fn test_rtshark_input_pcap() {
+    fn test_rtshark_input_pcap_follow_stream() {
         let pcap = include_bytes!("test.pcap");
 
         // create temp dir and copy pcap in it
@@ -2023,27 +4306,41 @@ mod tests {
         output.write_all(pcap).expect("unable to write pcap");
         output.flush().expect("unable to flush");
 
-        // spawn tshark on it
         let builder = RTSharkBuilder::builder().input_path(pcap_path.to_str().unwrap());
 
-        let mut rtshark = builder.spawn().unwrap();
-
-        // read a packet
-        match rtshark.read().unwrap() {
-            Some(p) => assert!(p.layer_name("udp").is_some()),
-            _ => panic!("invalid Output type"),
+        for mode in [FollowMode::Raw, FollowMode::Yaml] {
+            let stream = builder.follow_stream(FollowProtocol::Udp, 0, mode).unwrap();
+            assert_eq!(stream.client(), "127.0.0.1:53");
+            // the only packet has an empty payload
+            assert!(stream.chunks().iter().all(|c| c.data().is_empty()));
         }
 
-        loop {
-            match rtshark.read().unwrap() {
-                None => break,
-                Some(_) => todo!(),
-            }
-        }
+        tmp_dir.close().expect("Error deleting fifo dir");
+    }
 
-        rtshark.kill();
+    #[test]
+    fn test_rtshark_input_pcap_export_objects() {
+        let pcap = include_bytes!("test.pcap");
 
-        assert!(rtshark.pid().is_none());
+        // create temp dir and copy pcap in it
+        let tmp_dir = tempdir::TempDir::new("test_pcap").unwrap();
+        let pcap_path = tmp_dir.path().join("file.pcap");
+        let mut output = std::fs::File::create(&pcap_path).expect("unable to open file");
+        output.write_all(pcap).expect("unable to write pcap");
+        output.flush().expect("unable to flush");
+
+        let objects_path = tmp_dir.path().join("objects");
+
+        let builder = RTSharkBuilder::builder().input_path(pcap_path.to_str().unwrap());
+
+        // no HTTP in this capture
+        let objects = builder
+            .export_objects(ExportProtocol::Http, objects_path.to_str().unwrap())
+            .unwrap();
+        assert!(objects.is_empty());
+        assert!(objects_path.is_dir());
+        // the private export directory is removed
+        assert_eq!(std::fs::read_dir(&objects_path).unwrap().count(), 0);
 
         tmp_dir.close().expect("Error deleting fifo dir");
     }
@@ -2645,7 +4942,7 @@ mod tests {
         match rtshark.read().unwrap() {
             Some(p) => {
                 let tcp = p.layer_name("tcp").expect("tcp layer");
-                if !tcp.metadata.iter().any(|md| {
+                if !tcp.iter().any(|md| {
                     if let Some(display) = md.display() {
                         display.contains("relative sequence number")
                     } else {
@@ -2671,7 +4968,7 @@ mod tests {
         match rtshark.read().unwrap() {
             Some(p) => {
                 let tcp = p.layer_name("tcp").expect("tcp layer");
-                if tcp.metadata.iter().any(|md| {
+                if tcp.iter().any(|md| {
                     if let Some(display) = md.display() {
                         display.contains("relative sequence number")
                     } else {
@@ -3083,6 +5380,39 @@ mod tests {
         builder.version().expect("Error getting tshark version");
     }
 
+    #[test]
+    fn test_validate_metadata() {
+        let catalog = FieldCatalog::parse(
+            "F\tSource Address\tip.src\tFT_IPv4\tip\t\t0x0\t\n",
+            "Internet Protocol Version 4\tIPv4\tip\n",
+        );
+
+        let builder = RTSharkBuilder::builder()
+            .input_path("/tmp/my.pcap")
+            .metadata_whitelist("ip.src")
+            .metadata_blacklist("ip");
+        builder.validate_metadata(&catalog).unwrap();
+
+        let err = builder
+            .metadata_whitelist("ip.srcc")
+            .metadata_blacklist("ipp")
+            .validate_metadata(&catalog)
+            .unwrap_err();
+        assert!(err.to_string().contains("ip.srcc, ipp"));
+    }
+
+    #[test]
+    fn test_tshark_field_catalog() {
+        let catalog = RTSharkBuilder::builder()
+            .field_catalog()
+            .expect("Error getting tshark field catalog");
+        assert_eq!(
+            catalog.field("ip.src").unwrap().field_type(),
+            FieldType::Ipv4
+        );
+        assert!(catalog.protocol("tcp").is_some());
+    }
+
     #[test]
     fn test_batch() {
         let pcap = include_bytes!("test.pcap");
@@ -3119,4 +5449,28 @@ mod tests {
 
         assert_eq!(normalized, output);
     }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_batch_discards_output() {
+        // more output than a pipe can hold, then an error
+        const FAKE_TSHARK: &str = r#"#!/bin/sh
+i=0
+while [ $i -lt 20000 ]; do echo '<packet></packet>'; i=$((i + 1)); done
+echo 'tshark: fatal' >&2
+exit 2
+"#;
+        let tmp_dir = tempdir::TempDir::new("test_batch").unwrap();
+        tests::write_script(tmp_dir.path(), "tshark", FAKE_TSHARK);
+
+        let env_path = tests::env_path(tmp_dir.path());
+        let err = RTSharkBuilder::builder()
+            .input_path("-")
+            .env_path(&env_path)
+            .batch()
+            .unwrap_err();
+        assert!(
+            matches!(err, RTSharkError::TsharkExited { stderr, .. } if stderr == "tshark: fatal")
+        );
+    }
 }
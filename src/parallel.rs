@@ -0,0 +1,576 @@
+//! Dissect a large capture file with several TShark processes, see
+//! [crate::RTSharkBuilderReady::spawn_parallel].
+
+use crate::channel::Notifier;
+use crate::{
+    tshark_spawn_error, BackpressurePolicy, Packet, PacketReceiver, RTSharkBuilderReady,
+    RTSharkError, ReadOutcome, Result,
+};
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How [ParallelReader] splits the input file between TShark processes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SplitMode {
+    /// Split the file in chunks of this number of packets, with editcap. Each chunk is dissected
+    /// by its own TShark process: TCP reassembly, and other dissections relying on previous
+    /// packets, are not complete at the beginning of a chunk.
+    Packets(u64),
+    /// Each TShark process reads the whole file, but prints only its share of TCP streams
+    /// (tcp.stream modulo the number of workers). Packets which are not TCP, or which contain
+    /// several TCP streams of different shares (like ICMP errors quoting a TCP segment), are
+    /// printed by the first worker. This needs TShark 4.0 or later.
+    TcpStream,
+}
+
+/// Options of [crate::RTSharkBuilderReady::spawn_parallel].
+#[derive(Clone, Debug)]
+pub struct ParallelOptions {
+    /// how the input file is split
+    split: SplitMode,
+    /// number of TShark processes running at the same time
+    workers: usize,
+    /// deliver packets in their original order
+    ordered: bool,
+    /// packets parsed in advance by each worker
+    capacity: usize,
+}
+
+impl ParallelOptions {
+    /// Create options with the given split mode. By default, there is one worker per CPU,
+    /// packets are delivered in their original order, and each worker parses up to 10000 packets
+    /// in advance.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use rtshark::{ParallelOptions, SplitMode};
+    ///
+    /// let options = ParallelOptions::new(SplitMode::Packets(100_000))
+    ///     .workers(8)
+    ///     .capacity(50_000);
+    /// ```
+    pub fn new(split: SplitMode) -> Self {
+        ParallelOptions {
+            split,
+            workers: std::thread::available_parallelism().map_or(1, |n| n.get()),
+            ordered: true,
+            capacity: 10_000,
+        }
+    }
+
+    /// Set the number of TShark processes running at the same time.
+    #[must_use]
+    pub fn workers(&self, workers: usize) -> Self {
+        let mut new = self.clone();
+        new.workers = workers.max(1);
+        new
+    }
+
+    /// Deliver packets as soon as they are parsed by any worker, instead of their original order.
+    #[must_use]
+    pub fn unordered(&self) -> Self {
+        let mut new = self.clone();
+        new.ordered = false;
+        new
+    }
+
+    /// Set the number of packets each worker can parse in advance, before waiting for the
+    /// consumer.
+    ///
+    /// With ordered delivery of [SplitMode::Packets] chunks, workers wait for the consumer
+    /// to read previous chunks: the parallelism is limited by this capacity.
+    #[must_use]
+    pub fn capacity(&self, capacity: usize) -> Self {
+        let mut new = self.clone();
+        new.capacity = capacity.max(1);
+        new
+    }
+}
+
+/// Part of the input file dissected by one TShark process.
+enum Job {
+    /// chunk file created by editcap, and number of packets in previous chunks
+    Chunk { path: PathBuf, frame_offset: u64 },
+    /// TCP streams equal to index, modulo count
+    Streams { index: usize, count: usize },
+}
+
+/// A TShark process, and its packets parsed in background.
+struct Worker {
+    receiver: PacketReceiver,
+    /// number of packets in previous chunks
+    frame_offset: u64,
+    /// next packet, kept when merging outputs of all workers
+    head: Option<Result<Packet>>,
+}
+
+impl Worker {
+    fn recv(&self) -> Option<Result<Packet>> {
+        self.receiver
+            .recv()
+            .map(|packet| packet.map(|p| self.renumber(p)))
+    }
+
+    /// Set the frame number in the input file, instead of the chunk file.
+    fn renumber(&self, mut packet: Packet) -> Packet {
+        if let Some(frame_number) = packet.frame_number.as_mut() {
+            *frame_number += self.frame_offset;
+        }
+        packet
+    }
+}
+
+/// Temporary directory of chunk files, removed when dropped.
+struct ChunkDir(PathBuf);
+
+impl ChunkDir {
+    fn new() -> Result<Self> {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        let name = format!(
+            "rtshark-{}-{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        );
+        let path = std::env::temp_dir().join(name);
+        std::fs::create_dir(&path)?;
+
+        Ok(ChunkDir(path))
+    }
+}
+
+impl Drop for ChunkDir {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_dir_all(&self.0) {
+            eprintln!("Error removing {}: {e}", self.0.display());
+        }
+    }
+}
+
+/// Iterator over packets of a capture file dissected by several TShark processes,
+/// created by [crate::RTSharkBuilderReady::spawn_parallel].
+///
+/// Like [crate::RTShark], it returns recoverable errors in place of invalid packets. A fatal
+/// error only ends the packets of one worker, except when TShark cannot be started.
+pub struct ParallelReader<'a> {
+    /// settings of all TShark processes
+    builder: RTSharkBuilderReady<'a>,
+    options: ParallelOptions,
+    /// parts of the input file not dissected yet
+    jobs: VecDeque<Job>,
+    /// running TShark processes, in the order of their jobs
+    workers: Vec<Worker>,
+    /// worker checked first when delivering unordered packets
+    next_worker: usize,
+    /// notified when any worker has parsed a packet, or is done
+    notifier: Arc<Notifier>,
+    /// chunk files, removed once all workers are dropped
+    _chunks: Option<ChunkDir>,
+}
+
+impl<'a> ParallelReader<'a> {
+    /// Split the input file. TShark processes are started when packets are read.
+    pub(crate) fn spawn(
+        builder: &RTSharkBuilderReady<'a>,
+        options: &ParallelOptions,
+    ) -> Result<Self> {
+        if builder.live_capture
+            || builder.input_path.len() != 1
+            || !builder.input_globs.is_empty()
+            || builder.input_reader.is_some()
+        {
+            return Err(RTSharkError::invalid_argument(
+                "parallel reading needs one input file",
+            ));
+        }
+        if !builder.output_path.is_empty() {
+            return Err(RTSharkError::invalid_argument(
+                "output_path cannot be used with parallel reading",
+            ));
+        }
+        // check the input file and other parameters
+        builder.prepare_args()?;
+
+        let (jobs, chunks) = match options.split {
+            SplitMode::Packets(count) => {
+                let dir = ChunkDir::new()?;
+                let jobs = split_file(builder, count.max(1), &dir.0)?;
+                (jobs, Some(dir))
+            }
+            SplitMode::TcpStream => {
+                let count = options.workers;
+                let jobs = (0..count).map(|index| Job::Streams { index, count });
+                (jobs.collect(), None)
+            }
+        };
+
+        Ok(ParallelReader {
+            // packets are merged and renumbered with their frame number
+            builder: builder.with_frame_number(),
+            options: options.clone(),
+            jobs,
+            workers: vec![],
+            next_worker: 0,
+            notifier: Arc::default(),
+            _chunks: chunks,
+        })
+    }
+
+    /// Start TShark processes for next jobs, up to the number of workers.
+    fn start_workers(&mut self) -> Result<()> {
+        while self.workers.len() < self.options.workers {
+            let Some(job) = self.jobs.pop_front() else {
+                break;
+            };
+
+            let (rtshark, frame_offset) = match &job {
+                Job::Chunk { path, frame_offset } => {
+                    let path = path.to_str().ok_or_else(|| {
+                        RTSharkError::invalid_argument(format!("Invalid path {}", path.display()))
+                    })?;
+                    let builder = RTSharkBuilderReady {
+                        input_path: vec![path],
+                        ..self.builder.clone()
+                    };
+                    (builder.spawn()?, *frame_offset)
+                }
+                Job::Streams { index, count } => {
+                    let filter = match (self.builder.display_filter, streams_filter(*index, *count))
+                    {
+                        (filter, None) => filter.to_owned(),
+                        ("", Some(streams)) => streams,
+                        (filter, Some(streams)) => format!("({filter}) && ({streams})"),
+                    };
+                    let builder = RTSharkBuilderReady {
+                        display_filter: &filter,
+                        ..self.builder.clone()
+                    };
+                    (builder.spawn()?, 0)
+                }
+            };
+
+            let receiver = PacketReceiver::spawn(
+                rtshark,
+                self.options.capacity,
+                BackpressurePolicy::Block,
+                Some(self.notifier.clone()),
+            )?;
+            self.workers.push(Worker {
+                receiver,
+                frame_offset,
+                head: None,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Receive from the first worker: chunks are delivered one after the other.
+    /// Returns None when the worker is done.
+    fn recv_first(&mut self) -> Option<Result<Packet>> {
+        let packet = self.workers[0].recv();
+        if packet.is_none() {
+            self.workers.remove(0);
+        }
+        packet
+    }
+
+    /// Receive the packet with the lowest frame number, among the next packets of all workers.
+    /// Returns None when all workers are done.
+    fn recv_merged(&mut self) -> Option<Result<Packet>> {
+        for worker in &mut self.workers {
+            if worker.head.is_none() {
+                worker.head = worker.recv();
+            }
+        }
+        self.workers.retain(|worker| worker.head.is_some());
+
+        // errors and packets without frame number first
+        let (index, _) = self
+            .workers
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, worker)| match &worker.head {
+                Some(Ok(packet)) => packet.frame_number(),
+                _ => None,
+            })?;
+        match self.workers[index].head.take() {
+            Some(Ok(packet)) if packet.frame_number().is_none() => {
+                Some(Err(RTSharkError::invalid_data(
+                    "TShark printed a packet without frame number, it cannot be merged in order",
+                )))
+            }
+            head => head,
+        }
+    }
+
+    /// Receive from any worker having a parsed packet. Returns None when a worker is done.
+    fn recv_any(&mut self) -> Option<Result<Packet>> {
+        loop {
+            // events counted before checking workers are not missed
+            let events = self.notifier.events();
+            for _ in 0..self.workers.len() {
+                let index = self.next_worker % self.workers.len();
+                self.next_worker = index + 1;
+
+                let worker = &self.workers[index];
+                match worker.receiver.recv_timeout(Duration::ZERO) {
+                    Ok(ReadOutcome::Packet(packet)) => return Some(Ok(worker.renumber(packet))),
+                    Ok(ReadOutcome::Timeout) => (),
+                    Ok(ReadOutcome::Eof) => {
+                        self.workers.remove(index);
+                        return None;
+                    }
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+            self.notifier.wait(events);
+        }
+    }
+}
+
+impl Iterator for ParallelReader<'_> {
+    type Item = Result<Packet>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Err(e) = self.start_workers() {
+                // TShark cannot be started: no more packets
+                self.jobs.clear();
+                self.workers.clear();
+                return Some(Err(e));
+            }
+            if self.workers.is_empty() {
+                return None;
+            }
+
+            let packet = match (self.options.ordered, self.options.split) {
+                (false, _) => self.recv_any(),
+                (true, SplitMode::Packets(_)) => self.recv_first(),
+                (true, SplitMode::TcpStream) => self.recv_merged(),
+            };
+            // None: a worker is done, start the next one
+            if packet.is_some() {
+                return packet;
+            }
+        }
+    }
+}
+
+/// Display filter of the TCP streams printed by a worker, or None if it prints all packets.
+///
+/// A packet may contain several TCP streams: it is printed by a worker other than the first one
+/// if all its streams are in its share, else by the first worker, so that it is printed once.
+fn streams_filter(index: usize, count: usize) -> Option<String> {
+    let share = |index| format!("all tcp.stream % {count} == {index}");
+    match index {
+        _ if count <= 1 => None,
+        0 => {
+            let others: Vec<String> = (1..count).map(|i| format!("!({})", share(i))).collect();
+            Some(others.join(" && "))
+        }
+        _ => Some(share(index)),
+    }
+}
+
+/// Split the input file in chunk files of `count` packets, with editcap.
+fn split_file(builder: &RTSharkBuilderReady, count: u64, dir: &Path) -> Result<VecDeque<Job>> {
+    let mut command = Command::new("editcap");
+    command
+        .arg("-c")
+        .arg(count.to_string())
+        .arg(builder.input_path[0])
+        .arg(dir.join("chunk.pcapng"))
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped());
+
+    if !builder.env_path.is_empty() {
+        command.env("PATH", builder.env_path);
+    }
+
+    let output = command.output().map_err(tshark_spawn_error)?;
+    if !output.status.success() {
+        return Err(RTSharkError::TsharkExited {
+            status: Some(output.status),
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        });
+    }
+
+    // editcap names chunk files chunk_00000_<time>.pcapng, chunk_00001_<time>.pcapng ...
+    let mut paths = std::fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<std::io::Result<Vec<PathBuf>>>()?;
+    paths.sort();
+
+    let jobs = paths.into_iter().enumerate().map(|(i, path)| Job::Chunk {
+        path,
+        frame_offset: i as u64 * count,
+    });
+
+    Ok(jobs.collect())
+}
+
+#[cfg(all(test, target_family = "unix"))]
+mod tests {
+    use super::*;
+    use crate::tests::{env_path, pdml, write_script};
+    use crate::RTSharkBuilder;
+
+    /// TShark printing the "capture file" given to -r, which is already PDML. With a TCP streams
+    /// filter, it prints file.0 for the first worker, or file.1.
+    const FAKE_TSHARK: &str = r#"#!/bin/sh
+while [ $# -gt 0 ]; do
+    case "$1" in
+        -r) file="$2"; shift;;
+        -Y) filter="$2"; shift;;
+    esac
+    shift
+done
+case "$filter" in
+    "") cat "$file";;
+    "!("*) cat "$file.0";;
+    *) cat "$file.1";;
+esac
+"#;
+
+    /// editcap creating 3 chunks from input.0, input.1 and input.2
+    const FAKE_EDITCAP: &str = r#"#!/bin/sh
+for i in 0 1 2; do
+    cp "$3.$i" "${4%.pcapng}_0000${i}_20240101000000.pcapng"
+done
+"#;
+
+    fn frame_numbers(reader: ParallelReader) -> Vec<u64> {
+        reader
+            .map(|packet| packet.unwrap().frame_number().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_parallel_packets() {
+        let tmp_dir = tempdir::TempDir::new("test_parallel").unwrap();
+        write_script(tmp_dir.path(), "tshark", FAKE_TSHARK);
+        write_script(tmp_dir.path(), "editcap", FAKE_EDITCAP);
+
+        let input = tmp_dir.path().join("input.pcap");
+        std::fs::write(&input, "").unwrap();
+        std::fs::write(tmp_dir.path().join("input.pcap.0"), pdml(&[1, 2])).unwrap();
+        std::fs::write(tmp_dir.path().join("input.pcap.1"), pdml(&[1, 2])).unwrap();
+        std::fs::write(tmp_dir.path().join("input.pcap.2"), pdml(&[1])).unwrap();
+
+        let env_path = env_path(tmp_dir.path());
+        let builder = RTSharkBuilder::builder()
+            .input_path(input.to_str().unwrap())
+            .env_path(&env_path);
+
+        let options = ParallelOptions::new(SplitMode::Packets(2))
+            .workers(2)
+            .capacity(1);
+        let reader = builder.spawn_parallel(&options).unwrap();
+        let chunk_dir = reader._chunks.as_ref().unwrap().0.clone();
+        assert_eq!(frame_numbers(reader), [1, 2, 3, 4, 5]);
+        // chunk files are removed
+        assert!(!chunk_dir.exists());
+
+        let reader = builder.spawn_parallel(&options.unordered()).unwrap();
+        let mut numbers = frame_numbers(reader);
+        numbers.sort();
+        assert_eq!(numbers, [1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_parallel_tcp_streams() {
+        let tmp_dir = tempdir::TempDir::new("test_parallel").unwrap();
+        write_script(tmp_dir.path(), "tshark", FAKE_TSHARK);
+
+        let input = tmp_dir.path().join("input.pcap");
+        std::fs::write(&input, "").unwrap();
+        std::fs::write(tmp_dir.path().join("input.pcap.0"), pdml(&[1, 4, 5])).unwrap();
+        std::fs::write(tmp_dir.path().join("input.pcap.1"), pdml(&[2, 3, 6])).unwrap();
+
+        let env_path = env_path(tmp_dir.path());
+        let builder = RTSharkBuilder::builder()
+            .input_path(input.to_str().unwrap())
+            .env_path(&env_path);
+
+        let options = ParallelOptions::new(SplitMode::TcpStream).workers(2);
+        let reader = builder.spawn_parallel(&options).unwrap();
+        assert_eq!(frame_numbers(reader), [1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn test_parallel_merge_without_frame_number() {
+        let tmp_dir = tempdir::TempDir::new("test_parallel").unwrap();
+        write_script(tmp_dir.path(), "tshark", FAKE_TSHARK);
+
+        // the second packet of the first worker has no frame number
+        let input = tmp_dir.path().join("input.pcap");
+        std::fs::write(&input, "").unwrap();
+        let packets = pdml(&[1, 4]).replacen(
+            "</packet>\n",
+            "</packet>\n<packet>\n<proto name=\"frame\"></proto>\n</packet>\n",
+            1,
+        );
+        std::fs::write(tmp_dir.path().join("input.pcap.0"), packets).unwrap();
+        std::fs::write(tmp_dir.path().join("input.pcap.1"), pdml(&[2, 3])).unwrap();
+
+        let env_path = env_path(tmp_dir.path());
+        let builder = RTSharkBuilder::builder()
+            .input_path(input.to_str().unwrap())
+            .env_path(&env_path);
+
+        // an error is returned in place of the packet, instead of a packet out of order
+        let options = ParallelOptions::new(SplitMode::TcpStream).workers(2);
+        let results: Vec<_> = builder.spawn_parallel(&options).unwrap().collect();
+        assert_eq!(results.len(), 5);
+        assert_eq!(results[0].as_ref().unwrap().frame_number(), Some(1));
+        assert!(matches!(results[1], Err(RTSharkError::InvalidData { .. })));
+        let numbers: Vec<_> = results[2..]
+            .iter()
+            .map(|packet| packet.as_ref().unwrap().frame_number().unwrap())
+            .collect();
+        assert_eq!(numbers, [2, 3, 4]);
+
+        // frame numbers of JSON packets are kept by the whitelist
+        let builder = builder
+            .output_format(crate::OutputFormat::Json)
+            .metadata_whitelist("ip.src");
+        let reader = builder.spawn_parallel(&options).unwrap();
+        assert_eq!(
+            reader.builder.metadata_whitelist.as_deref().unwrap(),
+            ["ip.src", "frame.number"]
+        );
+    }
+
+    #[test]
+    fn test_streams_filter() {
+        assert_eq!(streams_filter(0, 1), None);
+        assert_eq!(
+            streams_filter(0, 3).unwrap(),
+            "!(all tcp.stream % 3 == 1) && !(all tcp.stream % 3 == 2)"
+        );
+        assert_eq!(streams_filter(2, 3).unwrap(), "all tcp.stream % 3 == 2");
+    }
+
+    #[test]
+    fn test_parallel_invalid_arguments() {
+        let options = ParallelOptions::new(SplitMode::TcpStream);
+
+        let builder = RTSharkBuilder::builder().input_path("eth0").live_capture();
+        assert!(matches!(
+            builder.spawn_parallel(&options),
+            Err(RTSharkError::InvalidArgument { .. })
+        ));
+
+        let builder = RTSharkBuilder::builder().input_path("/tmp/rtshark_missing.pcap");
+        assert!(matches!(
+            builder.spawn_parallel(&options),
+            Err(RTSharkError::InputNotFound { .. })
+        ));
+    }
+}
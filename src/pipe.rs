@@ -0,0 +1,77 @@
+//! TShark piped output, with an optional deadline to read it (see [crate::RTShark::read_timeout]).
+
+use std::io::Read;
+use std::process::ChildStdout;
+#[cfg(target_family = "unix")]
+use std::time::Instant;
+
+/// TShark stdout. Once a deadline is set, read() fails with [std::io::ErrorKind::TimedOut]
+/// if no data is available before it.
+pub(crate) struct PipeReader {
+    stdout: ChildStdout,
+    #[cfg(target_family = "unix")]
+    deadline: Option<Instant>,
+}
+
+impl PipeReader {
+    pub(crate) fn new(stdout: ChildStdout) -> Self {
+        PipeReader {
+            stdout,
+            #[cfg(target_family = "unix")]
+            deadline: None,
+        }
+    }
+
+    /// Set or remove the deadline of next reads.
+    #[cfg(target_family = "unix")]
+    pub(crate) fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.deadline = deadline;
+    }
+
+    /// Wait until the pipe is readable (data, EOF or error) or the deadline is reached.
+    #[cfg(target_family = "unix")]
+    fn poll(&self, deadline: Instant) -> std::io::Result<bool> {
+        use std::os::fd::AsRawFd;
+
+        let mut fd = libc::pollfd {
+            fd: self.stdout.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+
+        loop {
+            // round up, so that the deadline is reached when poll() times out
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let timeout = remaining
+                .as_micros()
+                .div_ceil(1000)
+                .min(libc::c_int::MAX as u128) as libc::c_int;
+
+            // SAFETY: fd is a valid pollfd, and its file descriptor is owned by self.stdout
+            match unsafe { libc::poll(&mut fd, 1, timeout) } {
+                -1 => {
+                    let err = std::io::Error::last_os_error();
+                    if err.kind() != std::io::ErrorKind::Interrupted {
+                        return Err(err);
+                    }
+                }
+                0 if timeout == 0 => return Ok(false),
+                0 => (),
+                _ => return Ok(true),
+            }
+        }
+    }
+}
+
+impl Read for PipeReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        #[cfg(target_family = "unix")]
+        if let Some(deadline) = self.deadline {
+            if !self.poll(deadline)? {
+                return Err(std::io::ErrorKind::TimedOut.into());
+            }
+        }
+
+        self.stdout.read(buf)
+    }
+}
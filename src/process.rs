@@ -0,0 +1,634 @@
+//! TShark process handling: termination and exit status, see [crate::RTShark::wait].
+
+use crate::Diagnostic;
+use std::io::Read;
+use std::process::{Child, ExitStatus};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+/// Delay between two checks of TShark termination.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// State of a TShark process, shared with the stop watchdog.
+struct ProcessState {
+    /// Contains the TShark process handle, when TShark is running
+    child: Option<Child>,
+    /// exit status, once TShark has terminated
+    status: Option<ExitStatus>,
+    /// TShark was killed by rtshark
+    killed: bool,
+    /// TShark was asked to stop or was killed: no next process is started
+    stopped: bool,
+    /// the failure of TShark was already reported by its reader
+    failure_reported: bool,
+}
+
+impl ProcessState {
+    /// Check once if TShark has exited and keep its exit status. Returns true if not running.
+    fn try_reap(&mut self) -> std::io::Result<bool> {
+        let Some(child) = self.child.as_mut() else {
+            return Ok(true);
+        };
+
+        match child.try_wait()? {
+            Some(status) => {
+                self.status = Some(status);
+                self.child = None;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Kill TShark if it is running and wait for its termination.
+    fn kill(&mut self) {
+        self.stopped = true;
+        let done = match self.try_reap() {
+            Ok(done) => done,
+            Err(e) => {
+                eprintln!("Error while killing rtshark: wait: {e}");
+                false
+            }
+        };
+        if done {
+            return;
+        }
+
+        let Some(mut child) = self.child.take() else {
+            return;
+        };
+        // TShark and its dumpcap child are killed together
+        #[cfg(target_family = "unix")]
+        let killed = signal_group(child.id(), libc::SIGKILL);
+        #[cfg(not(target_family = "unix"))]
+        let killed = child.kill();
+        match killed {
+            Ok(()) => self.killed = true,
+            Err(e) => eprintln!("Error while killing rtshark: kill: {e}"),
+        }
+        match child.wait() {
+            Ok(status) => self.status = Some(status),
+            Err(e) => eprintln!("Error while killing rtshark: wait: {e}"),
+        }
+    }
+}
+
+/// A TShark process, which keeps its exit status once terminated.
+///
+/// The child process is only reaped while the state is locked, so that its pid is never
+/// signaled once it may have been reused by another process.
+#[derive(Clone)]
+pub(crate) struct TsharkProcess {
+    shared: Arc<Mutex<ProcessState>>,
+}
+
+impl TsharkProcess {
+    pub(crate) fn new(child: Child) -> Self {
+        let state = ProcessState {
+            child: Some(child),
+            status: None,
+            killed: false,
+            stopped: false,
+            failure_reported: false,
+        };
+
+        TsharkProcess {
+            shared: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, ProcessState> {
+        lock(&self.shared)
+    }
+
+    /// Returns tshark process id if tshark is running.
+    pub(crate) fn id(&self) -> Option<u32> {
+        self.state().child.as_ref().map(|c| c.id())
+    }
+
+    /// Exit status, once TShark has terminated.
+    pub(crate) fn status(&self) -> Option<ExitStatus> {
+        self.state().status
+    }
+
+    /// TShark was killed by rtshark.
+    pub(crate) fn killed(&self) -> bool {
+        self.state().killed
+    }
+
+    /// Continue with a new TShark process once the previous one has exited (next input file).
+    /// The new process is given back if TShark was stopped or killed in the meantime.
+    pub(crate) fn replace(&self, child: Child) -> Result<(), Child> {
+        let mut state = self.state();
+        if state.stopped || state.child.is_some() {
+            return Err(child);
+        }
+
+        state.child = Some(child);
+        state.status = None;
+        state.failure_reported = false;
+        Ok(())
+    }
+
+    /// TShark was asked to stop or was killed.
+    pub(crate) fn stopped(&self) -> bool {
+        self.state().stopped
+    }
+
+    /// Get the exit status of TShark if it has failed, once, so that a failure is reported once.
+    /// A termination requested by kill() or stop() is not a failure.
+    pub(crate) fn take_failure(&self) -> Option<ExitStatus> {
+        let mut state = self.state();
+        let status = state.status?;
+        if status.success() || state.stopped || state.failure_reported {
+            return None;
+        }
+
+        state.failure_reported = true;
+        Some(status)
+    }
+
+    /// Check if TShark has exited, once its output is closed, and keep its exit status.
+    /// Why not doing a simple wait ? TShark may still be running after closing its output,
+    /// so it is only waited for a little.
+    pub(crate) fn try_wait_exited(&self) -> bool {
+        let mut count = 3;
+        while count != 0 {
+            if let Ok(true) = self.state().try_reap() {
+                return true;
+            }
+
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            count -= 1;
+        }
+
+        false
+    }
+
+    /// Wait for TShark termination.
+    pub(crate) fn wait(&self) -> std::io::Result<()> {
+        // the state is not locked while waiting, so that the stop watchdog can kill TShark
+        while !self.state().try_reap()? {
+            std::thread::sleep(POLL_INTERVAL);
+        }
+
+        Ok(())
+    }
+
+    /// Kill TShark if it is running and wait for its termination.
+    pub(crate) fn kill(&self) {
+        self.state().kill()
+    }
+
+    /// Terminate TShark when its reader is dropped: stop it gracefully if a timeout is given,
+    /// discarding its remaining output until it exits, or kill it.
+    pub(crate) fn terminate(&self, graceful_drop: Option<Duration>, stdout: &mut impl Read) {
+        let Some(timeout) = graceful_drop else {
+            return self.kill();
+        };
+
+        if let Err(e) = self.stop(timeout) {
+            eprintln!("Error while stopping rtshark: {e}");
+            return self.kill();
+        }
+        // remaining output is discarded until TShark exits, or is killed after the timeout
+        if let Err(e) = std::io::copy(stdout, &mut std::io::sink()) {
+            eprintln!("Error while stopping rtshark: {e}");
+            return self.kill();
+        }
+        if let Err(e) = self.wait() {
+            eprintln!("Error while stopping rtshark: wait: {e}");
+        }
+    }
+
+    /// Ask TShark and its dumpcap child to stop with SIGINT, and kill them if TShark is still running
+    /// after the timeout. TShark then flushes its outputs.
+    #[cfg(target_family = "unix")]
+    pub(crate) fn stop(&self, timeout: Duration) -> std::io::Result<()> {
+        {
+            // signal is sent while locked, so that TShark cannot be reaped in the meantime
+            let mut state = self.state();
+            state.stopped = true;
+            if state.try_reap()? {
+                return Ok(());
+            }
+            if let Some(child) = &state.child {
+                signal_group(child.id(), libc::SIGINT)?;
+            }
+        }
+
+        let shared = self.shared.clone();
+        let watchdog = std::thread::Builder::new()
+            .name("rtshark-stop".to_owned())
+            .spawn(move || {
+                let deadline = std::time::Instant::now() + timeout;
+                while std::time::Instant::now() < deadline {
+                    if !matches!(lock(&shared).try_reap(), Ok(false)) {
+                        return;
+                    }
+                    std::thread::sleep(POLL_INTERVAL);
+                }
+                lock(&shared).kill();
+            });
+
+        if let Err(e) = watchdog {
+            eprintln!("Error while stopping rtshark: {e}");
+            self.kill();
+        }
+
+        Ok(())
+    }
+
+    /// Stop TShark: there is no graceful way on this platform, so TShark is killed.
+    #[cfg(not(target_family = "unix"))]
+    pub(crate) fn stop(&self, _timeout: Duration) -> std::io::Result<()> {
+        self.kill();
+        Ok(())
+    }
+}
+
+/// Lock the process state, even if another thread panicked while holding it.
+fn lock(shared: &Mutex<ProcessState>) -> MutexGuard<'_, ProcessState> {
+    shared.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Send a signal to the process group led by a child process (TShark and dumpcap),
+/// or only to this child if it is not a group leader.
+///
+/// The child must not be reaped yet, so that its pid cannot have been reused.
+#[cfg(target_family = "unix")]
+pub(crate) fn signal_group(pid: u32, signal: libc::c_int) -> std::io::Result<()> {
+    let pid = libc::pid_t::try_from(pid)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+
+    // SAFETY: kill has no memory safety requirement
+    if unsafe { libc::kill(-pid, signal) } == 0 {
+        return Ok(());
+    }
+
+    // SAFETY: kill has no memory safety requirement
+    if unsafe { libc::kill(pid, signal) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+/// Script of the watchdog killing TShark process group when this process dies: it waits for
+/// the end of the parent death pipe. SIGINT is ignored, as stop() sends it to the whole group.
+#[cfg(target_os = "linux")]
+const WATCHDOG_SCRIPT: &std::ffi::CStr = c"trap '' INT; read _; kill -KILL 0";
+
+/// Get the read end of a pipe whose write end is only closed when this process dies.
+#[cfg(target_os = "linux")]
+pub(crate) fn parent_death_pipe() -> std::io::Result<libc::c_int> {
+    static READ_END: std::sync::OnceLock<libc::c_int> = std::sync::OnceLock::new();
+    if let Some(fd) = READ_END.get() {
+        return Ok(*fd);
+    }
+
+    let mut fds = [0; 2];
+    // SAFETY: fds has room for the two file descriptors
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    // the write end is never closed, it is closed by the kernel when this process dies
+    if READ_END.set(fds[0]).is_err() {
+        // created by another thread in the meantime
+        // SAFETY: both file descriptors are owned by this function
+        unsafe {
+            libc::close(fds[0]);
+            libc::close(fds[1]);
+        }
+    }
+
+    Ok(*READ_END.get().unwrap())
+}
+
+/// Start a watchdog in TShark process group, which kills the group (TShark, dumpcap and itself)
+/// when this process dies, and exits with TShark.
+/// This is called in the forked child, once in its own process group, before TShark is executed.
+#[cfg(target_os = "linux")]
+pub(crate) fn spawn_group_watchdog(pipe: libc::c_int) -> std::io::Result<()> {
+    // SAFETY: only async-signal-safe functions are called in the forked processes
+    unsafe {
+        let tshark = libc::getpid();
+        match libc::fork() {
+            -1 => Err(std::io::Error::last_os_error()),
+            0 => run_watchdog(pipe, tshark),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Execute the watchdog script, reading the parent death pipe.
+///
+/// # Safety
+///
+/// Must only be called in a forked child, which only calls async-signal-safe functions.
+#[cfg(target_os = "linux")]
+unsafe fn run_watchdog(pipe: libc::c_int, tshark: libc::pid_t) -> ! {
+    // the watchdog exits with TShark; TShark may have exited before prctl
+    if libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL) != 0 || libc::getppid() != tshark {
+        libc::_exit(0);
+    }
+
+    // TShark standard streams are not kept open by the watchdog
+    let null = libc::open(c"/dev/null".as_ptr(), libc::O_RDWR);
+    if null < 0 || libc::dup2(pipe, 0) < 0 || libc::dup2(null, 1) < 0 || libc::dup2(null, 2) < 0 {
+        libc::_exit(1);
+    }
+
+    let argv = [
+        c"sh".as_ptr(),
+        c"-c".as_ptr(),
+        WATCHDOG_SCRIPT.as_ptr(),
+        std::ptr::null(),
+    ];
+    libc::execv(c"/bin/sh".as_ptr(), argv.as_ptr());
+    libc::_exit(127)
+}
+
+/// A handle to control a TShark process from any thread, returned by [crate::RTShark::handle].
+///
+/// A blocked [crate::RTShark::read] returns once TShark is killed or stopped with this handle:
+/// remaining packets are delivered, then None is returned.
+///
+/// # Example
+///
+/// ```
+/// # let builder = rtshark::RTSharkBuilder::builder().input_path("/tmp/my.pcap");
+/// # let Ok(mut rtshark) = builder.spawn() else { return };
+/// let handle = rtshark.handle();
+///
+/// let watchdog = std::thread::spawn(move || {
+///     std::thread::sleep(std::time::Duration::from_secs(10));
+///     handle.stop(std::time::Duration::from_secs(5)).unwrap();
+/// });
+///
+/// while let Ok(Some(packet)) = rtshark.read() {
+///     println!("Got a packet with {} layers", packet.iter().count());
+/// }
+/// ```
+#[derive(Clone)]
+pub struct RTSharkHandle {
+    process: TsharkProcess,
+}
+
+impl RTSharkHandle {
+    pub(crate) fn new(process: TsharkProcess) -> Self {
+        RTSharkHandle { process }
+    }
+
+    /// Kill the running TShark process, like [crate::RTShark::kill].
+    pub fn kill(&self) {
+        self.process.kill()
+    }
+
+    /// Ask the running TShark process to stop gracefully, and kill it if it is still running
+    /// after the timeout, like [crate::RTShark::stop].
+    pub fn stop(&self, timeout: Duration) -> crate::Result<()> {
+        self.process.stop(timeout)?;
+        Ok(())
+    }
+
+    /// Returns tshark process id if tshark is running.
+    pub fn pid(&self) -> Option<u32> {
+        self.process.id()
+    }
+
+    /// Check if TShark is still running.
+    pub fn is_running(&self) -> bool {
+        !matches!(self.process.state().try_reap(), Ok(true))
+    }
+}
+
+impl std::fmt::Debug for RTSharkHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RTSharkHandle")
+            .field("pid", &self.pid())
+            .finish()
+    }
+}
+
+/// How a TShark process terminated, returned by [crate::RTShark::wait].
+#[derive(Clone, Debug)]
+pub struct TerminationReport {
+    status: Option<ExitStatus>,
+    killed: bool,
+    stderr: Vec<String>,
+    diagnostics: Vec<Diagnostic>,
+    packets_delivered: u64,
+}
+
+impl TerminationReport {
+    pub(crate) fn new(
+        process: &TsharkProcess,
+        stderr: Vec<String>,
+        diagnostics: Vec<Diagnostic>,
+        packets_delivered: u64,
+    ) -> Self {
+        TerminationReport {
+            status: process.status(),
+            killed: process.killed(),
+            stderr,
+            diagnostics,
+            packets_delivered,
+        }
+    }
+
+    /// TShark exit status, if it could be retrieved.
+    pub fn status(&self) -> Option<ExitStatus> {
+        self.status
+    }
+
+    /// TShark exited successfully.
+    pub fn success(&self) -> bool {
+        self.status.is_some_and(|s| s.success())
+    }
+
+    /// TShark exit code, if TShark exited by itself.
+    pub fn code(&self) -> Option<i32> {
+        self.status.and_then(|s| s.code())
+    }
+
+    /// Signal which terminated TShark, if any (unix only).
+    pub fn signal(&self) -> Option<i32> {
+        #[cfg(target_family = "unix")]
+        {
+            use std::os::unix::process::ExitStatusExt;
+            self.status.and_then(|s| s.signal())
+        }
+        #[cfg(not(target_family = "unix"))]
+        {
+            None
+        }
+    }
+
+    /// TShark was killed by rtshark, with kill() or when dropped.
+    pub fn killed(&self) -> bool {
+        self.killed
+    }
+
+    /// Lines printed by TShark on stderr. Only the last lines are kept.
+    pub fn stderr(&self) -> &[String] {
+        &self.stderr
+    }
+
+    /// Messages printed by TShark on stderr, classified by kind and severity.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Number of packets returned by read().
+    pub fn packets_delivered(&self) -> u64 {
+        self.packets_delivered
+    }
+}
+
+#[cfg(all(test, target_family = "unix"))]
+mod tests {
+    use super::*;
+    use std::process::{Command, Stdio};
+
+    #[test]
+    fn test_process_exit_status() {
+        let child = Command::new("sh")
+            .args(["-c", "exit 3"])
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap();
+        let process = TsharkProcess::new(child);
+        assert!(process.id().is_some());
+
+        process.wait().unwrap();
+        assert!(process.id().is_none());
+        assert!(process.try_wait_exited());
+
+        let report = TerminationReport::new(&process, vec![], vec![], 2);
+        assert_eq!(report.code(), Some(3));
+        assert_eq!(report.signal(), None);
+        assert!(!report.success());
+        assert!(!report.killed());
+        assert_eq!(report.packets_delivered(), 2);
+    }
+
+    #[test]
+    fn test_process_stop() {
+        // exits cleanly on SIGINT
+        let child = Command::new("sh")
+            .args(["-c", "trap 'exit 4' INT; while true; do sleep 0.01; done"])
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap();
+        let process = TsharkProcess::new(child);
+        // let sh install its trap
+        std::thread::sleep(Duration::from_millis(200));
+
+        process.stop(Duration::from_secs(10)).unwrap();
+        process.wait().unwrap();
+        assert_eq!(process.status().unwrap().code(), Some(4));
+        assert!(!process.killed());
+
+        // ignores SIGINT, killed after the timeout
+        let child = Command::new("sh")
+            .args(["-c", "trap '' INT; while true; do sleep 0.01; done"])
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap();
+        let process = TsharkProcess::new(child);
+        std::thread::sleep(Duration::from_millis(200));
+
+        process.stop(Duration::from_millis(200)).unwrap();
+        process.wait().unwrap();
+        assert!(process.killed());
+        assert_eq!(process.status().unwrap().code(), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_process_group_kill() {
+        use std::io::BufRead;
+        use std::os::unix::process::CommandExt;
+
+        // like TShark and dumpcap
+        let mut child = Command::new("sh")
+            .args(["-c", "sleep 100 & echo $!; wait"])
+            .stdout(Stdio::piped())
+            .process_group(0)
+            .spawn()
+            .unwrap();
+        let mut line = String::new();
+        std::io::BufReader::new(child.stdout.take().unwrap())
+            .read_line(&mut line)
+            .unwrap();
+        let grandchild: u32 = line.trim().parse().unwrap();
+
+        let process = TsharkProcess::new(child);
+        process.kill();
+        assert!(process.killed());
+
+        // grandchild is killed too: it disappears, or is a zombie until reaped by init
+        let stat = format!("/proc/{grandchild}/stat");
+        let terminated = (0..100).any(|_| {
+            let done = std::fs::read_to_string(&stat).map_or(true, |s| {
+                s.rsplit(')')
+                    .next()
+                    .unwrap_or("")
+                    .trim_start()
+                    .starts_with('Z')
+            });
+            std::thread::sleep(Duration::from_millis(10));
+            done
+        });
+        assert!(terminated);
+    }
+
+    #[test]
+    fn test_handle_kill() {
+        use std::io::Read;
+
+        let mut child = Command::new("sleep")
+            .arg("10")
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut stdout = child.stdout.take().unwrap();
+        let handle = RTSharkHandle::new(TsharkProcess::new(child));
+        assert!(handle.is_running());
+
+        let killer = handle.clone();
+        let thread = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            killer.kill();
+        });
+
+        // blocked read returns once killed
+        let start = std::time::Instant::now();
+        assert_eq!(stdout.read(&mut [0; 16]).unwrap(), 0);
+        assert!(start.elapsed() < Duration::from_secs(5));
+        thread.join().unwrap();
+
+        assert!(!handle.is_running());
+        assert!(handle.pid().is_none());
+    }
+
+    #[test]
+    fn test_process_kill() {
+        let child = Command::new("sleep")
+            .arg("10")
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap();
+        let process = TsharkProcess::new(child);
+        assert!(!process.try_wait_exited());
+
+        process.kill();
+        assert!(process.id().is_none());
+
+        let report = TerminationReport::new(&process, vec![], vec![], 0);
+        assert!(report.killed());
+        assert_eq!(report.code(), None);
+        assert_eq!(report.signal(), Some(9));
+    }
+}
@@ -0,0 +1,336 @@
+//! Reader for TShark packet summary output (-T psml), see [crate::RTSharkBuilderReady::spawn_summary].
+
+use crate::input::InputError;
+use crate::reader::ReaderCore;
+use crate::stderr::StderrReader;
+use crate::{RTSharkError, RTSharkHandle, Result, TerminationReport};
+use quick_xml::events::Event;
+use std::io::{BufRead, BufReader};
+use std::process::{Child, ChildStdout};
+use std::sync::Arc;
+
+/// One line of the packet list, as displayed by Wireshark.
+///
+/// Column titles come from TShark (default columns are "No.", "Time", "Source", "Destination",
+/// "Protocol", "Length" and "Info") and can be changed with the "gui.column.format" preference.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PacketSummary {
+    /// column titles, shared by all packets
+    columns: Arc<[String]>,
+    /// column values
+    values: Vec<String>,
+}
+
+impl PacketSummary {
+    /// Get the column titles of this summary.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let builder = rtshark::RTSharkBuilder::builder().input_path("/tmp/my.pcap");
+    /// # let Ok(mut reader) = builder.spawn_summary() else { return };
+    /// while let Ok(Some(summary)) = reader.read() {
+    ///     assert!(summary.columns().iter().any(|c| c == "Protocol"));
+    /// }
+    /// ```
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// Get a column value using its title.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let builder = rtshark::RTSharkBuilder::builder().input_path("/tmp/my.pcap");
+    /// # let Ok(mut reader) = builder.spawn_summary() else { return };
+    /// while let Ok(Some(summary)) = reader.read() {
+    ///     println!("{:?}: {:?}", summary.column("Protocol"), summary.column("Info"));
+    /// }
+    /// ```
+    pub fn column(&self, title: &str) -> Option<&str> {
+        let index = self.columns.iter().position(|c| c == title)?;
+        self.get(index)
+    }
+
+    /// Get a column value using its index.
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.values.get(index).map(String::as_str)
+    }
+
+    /// Get all column values, in column order.
+    pub fn values(&self) -> &[String] {
+        &self.values
+    }
+
+    /// Iterate over columns, as (title, value) pairs.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.columns
+            .iter()
+            .map(String::as_str)
+            .zip(self.values.iter().map(String::as_str))
+    }
+}
+
+/// SummaryReader structure represents a TShark process started with summary output (-T psml).
+/// It is created by [crate::RTSharkBuilderReady::spawn_summary].
+pub struct SummaryReader {
+    /// TShark process and stderr
+    core: ReaderCore,
+    /// xml parser on TShark piped output
+    parser: quick_xml::Reader<BufReader<ChildStdout>>,
+    /// column titles, once the psml structure has been read
+    columns: Option<Arc<[String]>>,
+}
+
+impl SummaryReader {
+    /// create a new SummaryReader instance from a successful builder call.
+    pub(crate) fn new(
+        process: Child,
+        parser: quick_xml::Reader<BufReader<ChildStdout>>,
+        stderr: StderrReader,
+        graceful_drop: Option<std::time::Duration>,
+        input_error: Option<InputError>,
+    ) -> Self {
+        SummaryReader {
+            core: ReaderCore::new(process, stderr, graceful_drop, input_error),
+            parser,
+            columns: None,
+        }
+    }
+
+    /// Get the column titles, once the first packet has been read.
+    pub fn columns(&self) -> Option<&[String]> {
+        self.columns.as_deref()
+    }
+
+    /// Read the summary of the next packet.
+    /// Reading summaries can be done until 'None' is returned, like with [crate::RTShark::read].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let builder = rtshark::RTSharkBuilder::builder().input_path("/tmp/my.pcap");
+    /// let mut reader = match builder.spawn_summary() {
+    ///     Err(err) => { eprintln!("Error running tshark: {err}"); return; }
+    ///     Ok(reader) => reader,
+    /// };
+    ///
+    /// loop {
+    ///     match reader.read() {
+    ///         Ok(Some(summary)) => println!("{}", summary.values().join(" ")),
+    ///         Ok(None) => break,
+    ///         Err(e) => { eprintln!("Got decoding error: {e}"); break; }
+    ///     }
+    /// }
+    /// ```
+    pub fn read(&mut self) -> Result<Option<PacketSummary>> {
+        let msg = parse_psml(&mut self.parser, &mut self.columns);
+
+        match msg {
+            Ok(Some(_)) => self.core.delivered(),
+            // Got None == EOF
+            Ok(None) => self.core.check_eof()?,
+            Err(_) => (),
+        }
+
+        msg
+    }
+
+    /// Wait for TShark termination and get how it terminated, like [crate::RTShark::wait].
+    /// The number of summaries read is reported as the number of packets delivered.
+    pub fn wait(&mut self) -> Result<TerminationReport> {
+        self.core.wait(self.parser.get_mut())
+    }
+
+    /// Get the non-fatal messages printed by TShark on stderr so far, like [crate::RTShark::warnings].
+    pub fn warnings(&self) -> Vec<String> {
+        self.core.warnings()
+    }
+
+    /// Kill the running TShark process associated to this reader.
+    pub fn kill(&mut self) {
+        self.core.kill()
+    }
+
+    /// Ask TShark to stop gracefully, and kill it if it is still running after the timeout,
+    /// like [crate::RTShark::stop].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let builder = rtshark::RTSharkBuilder::builder().input_path("/tmp/my.pcap").spawn_summary();
+    /// # let Ok(mut reader) = builder else { return };
+    /// reader.stop(std::time::Duration::from_secs(5)).unwrap();
+    ///
+    /// // read remaining summaries
+    /// while let Ok(Some(_)) = reader.read() {}
+    /// ```
+    pub fn stop(&mut self, timeout: std::time::Duration) -> Result<()> {
+        self.core.stop(timeout)
+    }
+
+    /// Get a handle to kill or stop TShark from another thread, like [crate::RTShark::handle].
+    pub fn handle(&self) -> RTSharkHandle {
+        self.core.handle()
+    }
+
+    /// Returns tshark process id if tshark is running.
+    pub fn pid(&self) -> Option<u32> {
+        self.core.pid()
+    }
+}
+
+impl Drop for SummaryReader {
+    fn drop(&mut self) {
+        self.core.terminate(self.parser.get_mut());
+    }
+}
+
+/// Parse psml output until the end of the next packet. Column titles are stored when the structure is read.
+fn parse_psml<B: BufRead>(
+    xml_reader: &mut quick_xml::Reader<B>,
+    columns: &mut Option<Arc<[String]>>,
+) -> Result<Option<PacketSummary>> {
+    // tshark psml is something like :
+    //
+    // <psml version="0" creator="wireshark/4.0.6">
+    // <structure>
+    // <section>No.</section>
+    // <section>Time</section>
+    // ...
+    // </structure>
+    //
+    // <packet>
+    // <section>1</section>
+    // <section>0.000000</section>
+    // ...
+    // </packet>
+
+    let mut buf = vec![];
+    let mut sections = vec![];
+    let mut section = None;
+
+    loop {
+        match xml_reader.read_event_into(&mut buf) {
+            Ok(Event::Start(ref e)) => {
+                if b"section" == e.name().as_ref() {
+                    section = Some(String::new());
+                }
+            }
+            Ok(Event::Empty(ref e)) => {
+                if b"section" == e.name().as_ref() {
+                    sections.push(String::new());
+                }
+            }
+            Ok(Event::Text(ref e)) => {
+                if let Some(section) = section.as_mut() {
+                    let text = e.unescape().map_err(|e| RTSharkError::XmlParse {
+                        offset: xml_reader.buffer_position(),
+                        message: e.to_string(),
+                    })?;
+                    section.push_str(&text);
+                }
+            }
+            Ok(Event::End(ref e)) => match e.name().as_ref() {
+                b"section" => sections.extend(section.take()),
+                b"structure" => *columns = Some(std::mem::take(&mut sections).into()),
+                b"packet" => {
+                    let Some(columns) = columns.clone() else {
+                        return Err(RTSharkError::invalid_data(
+                            "psml packet found before psml structure",
+                        ));
+                    };
+                    return Ok(Some(PacketSummary {
+                        columns,
+                        values: sections,
+                    }));
+                }
+                _ => (),
+            },
+            Ok(Event::Eof) => {
+                return Ok(None);
+            }
+            Err(e) => {
+                return Err(RTSharkError::XmlParse {
+                    offset: xml_reader.buffer_position(),
+                    message: e.to_string(),
+                });
+            }
+            Ok(_) => {}
+        }
+        buf.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PSML: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<psml version="0" creator="wireshark/4.0.6">
+<structure>
+<section>No.</section>
+<section>Time</section>
+<section>Source</section>
+<section>Destination</section>
+<section>Protocol</section>
+<section>Length</section>
+<section>Info</section>
+</structure>
+
+<packet>
+<section>1</section>
+<section>0.000000</section>
+<section>127.0.0.1</section>
+<section>127.0.0.1</section>
+<section>DNS</section>
+<section>28</section>
+<section>Unknown operation (0) 0x0000 &lt;Unknown extended label&gt;</section>
+</packet>
+
+<packet>
+<section>2</section>
+<section>0.000010</section>
+<section></section>
+<section/>
+<section>ETH</section>
+<section>60</section>
+<section>Ethernet II</section>
+</packet>
+
+</psml>
+"#;
+
+    #[test]
+    fn test_parse_psml() {
+        let mut reader = quick_xml::Reader::from_reader(PSML.as_bytes());
+        let mut columns = None;
+
+        let first = parse_psml(&mut reader, &mut columns).unwrap().unwrap();
+        assert_eq!(columns.as_deref().unwrap().len(), 7);
+        assert_eq!(first.column("No."), Some("1"));
+        assert_eq!(first.column("Protocol"), Some("DNS"));
+        assert_eq!(
+            first.column("Info"),
+            Some("Unknown operation (0) 0x0000 <Unknown extended label>")
+        );
+        assert!(first.column("Missing").is_none());
+
+        let second = parse_psml(&mut reader, &mut columns).unwrap().unwrap();
+        assert_eq!(second.column("Source"), Some(""));
+        assert_eq!(second.column("Destination"), Some(""));
+        assert_eq!(second.get(5), Some("60"));
+        assert_eq!(second.iter().last(), Some(("Info", "Ethernet II")));
+
+        assert!(parse_psml(&mut reader, &mut columns).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_parse_psml_without_structure() {
+        let xml = "<psml><packet><section>1</section></packet></psml>";
+        let mut reader = quick_xml::Reader::from_reader(xml.as_bytes());
+
+        assert!(parse_psml(&mut reader, &mut None).is_err());
+    }
+}
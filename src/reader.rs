@@ -0,0 +1,96 @@
+//! Process handling shared by the tabular readers, see [crate::FieldsReader] and
+//! [crate::SummaryReader].
+
+use crate::input::InputError;
+use crate::process::TsharkProcess;
+use crate::stderr::{check_exit, StderrReader};
+use crate::{RTSharkHandle, Result, TerminationReport};
+use std::io::Read;
+use std::process::Child;
+use std::time::Duration;
+
+/// TShark process of a reader, with its stderr and the number of items read.
+pub(crate) struct ReaderCore {
+    /// TShark process
+    process: TsharkProcess,
+    /// stderr, read in background
+    stderr: StderrReader,
+    /// number of items returned by the reader
+    delivered: u64,
+    /// stop TShark gracefully when dropped, with this timeout
+    graceful_drop: Option<Duration>,
+    /// error of the input_reader copied to TShark standard input
+    input_error: Option<InputError>,
+}
+
+impl ReaderCore {
+    pub(crate) fn new(
+        process: Child,
+        stderr: StderrReader,
+        graceful_drop: Option<Duration>,
+        input_error: Option<InputError>,
+    ) -> Self {
+        ReaderCore {
+            process: TsharkProcess::new(process),
+            stderr,
+            delivered: 0,
+            graceful_drop,
+            input_error,
+        }
+    }
+
+    /// Count an item returned by the reader.
+    pub(crate) fn delivered(&mut self) {
+        self.delivered += 1;
+    }
+
+    /// Check if the input reader or TShark has failed, once TShark output is closed.
+    pub(crate) fn check_eof(&self) -> Result<()> {
+        if let Some(input_error) = &self.input_error {
+            input_error.check()?;
+        }
+        check_exit(&self.process, &self.stderr)
+    }
+
+    /// Wait for TShark termination, discarding its remaining output.
+    pub(crate) fn wait(&mut self, stdout: &mut impl Read) -> Result<TerminationReport> {
+        if self.process.id().is_some() {
+            // TShark may be blocked on a full pipe
+            std::io::copy(stdout, &mut std::io::sink())?;
+            self.process.wait()?;
+        }
+
+        Ok(TerminationReport::new(
+            &self.process,
+            self.stderr.wait_lines(),
+            self.stderr.diagnostics(),
+            self.delivered,
+        ))
+    }
+
+    pub(crate) fn warnings(&self) -> Vec<String> {
+        self.stderr.warnings()
+    }
+
+    pub(crate) fn kill(&self) {
+        self.process.kill()
+    }
+
+    pub(crate) fn stop(&self, timeout: Duration) -> Result<()> {
+        self.process.stop(timeout)?;
+        Ok(())
+    }
+
+    pub(crate) fn handle(&self) -> RTSharkHandle {
+        RTSharkHandle::new(self.process.clone())
+    }
+
+    pub(crate) fn pid(&self) -> Option<u32> {
+        self.process.id()
+    }
+
+    /// Stop TShark when the reader is dropped, see [crate::RTSharkBuilderReady::graceful_drop].
+    pub(crate) fn terminate(&self, stdout: &mut impl Read) {
+        self.process.terminate(self.graceful_drop, stdout);
+    }
+}
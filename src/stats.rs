@@ -0,0 +1,1069 @@
+//! Typed results of TShark statistics (-q -z <tap>), see [crate::RTSharkBuilderReady::statistics].
+
+use crate::{RTSharkError, Result};
+use std::time::Duration;
+
+/// Type of conversations or endpoints, used by "-z conv,<type>" and "-z endpoints,<type>".
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConversationType {
+    /// Ethernet addresses
+    Ethernet,
+    /// IPv4 addresses
+    Ip,
+    /// IPv6 addresses
+    Ipv6,
+    /// TCP addresses and ports
+    Tcp,
+    /// UDP addresses and ports
+    Udp,
+}
+
+impl ConversationType {
+    /// Name of the type in TShark "-z" option.
+    pub(crate) fn tap_name(&self) -> &'static str {
+        match self {
+            ConversationType::Ethernet => "eth",
+            ConversationType::Ip => "ip",
+            ConversationType::Ipv6 => "ipv6",
+            ConversationType::Tcp => "tcp",
+            ConversationType::Udp => "udp",
+        }
+    }
+}
+
+/// A conversation between two endpoints (-z conv,<type>).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Conversation {
+    address_a: String,
+    address_b: String,
+    frames_a_to_b: u64,
+    bytes_a_to_b: u64,
+    frames_b_to_a: u64,
+    bytes_b_to_a: u64,
+    frames: u64,
+    bytes: u64,
+    relative_start: Duration,
+    duration: Duration,
+}
+
+impl Conversation {
+    /// First endpoint, with its port for TCP and UDP (example: "10.0.0.1:443").
+    pub fn address_a(&self) -> &str {
+        &self.address_a
+    }
+
+    /// Second endpoint, with its port for TCP and UDP.
+    pub fn address_b(&self) -> &str {
+        &self.address_b
+    }
+
+    /// Number of frames sent from A to B.
+    pub fn frames_a_to_b(&self) -> u64 {
+        self.frames_a_to_b
+    }
+
+    /// Number of bytes sent from A to B.
+    pub fn bytes_a_to_b(&self) -> u64 {
+        self.bytes_a_to_b
+    }
+
+    /// Number of frames sent from B to A.
+    pub fn frames_b_to_a(&self) -> u64 {
+        self.frames_b_to_a
+    }
+
+    /// Number of bytes sent from B to A.
+    pub fn bytes_b_to_a(&self) -> u64 {
+        self.bytes_b_to_a
+    }
+
+    /// Total number of frames.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Total number of bytes.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Start of the conversation, relative to the first packet of the capture.
+    pub fn relative_start(&self) -> Duration {
+        self.relative_start
+    }
+
+    /// Duration of the conversation.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+}
+
+/// Traffic statistics of an endpoint (-z endpoints,<type>).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Endpoint {
+    address: String,
+    port: Option<u16>,
+    packets: u64,
+    bytes: u64,
+    tx_packets: u64,
+    tx_bytes: u64,
+    rx_packets: u64,
+    rx_bytes: u64,
+}
+
+impl Endpoint {
+    /// Address of the endpoint.
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    /// Port of the endpoint, for TCP and UDP.
+    pub fn port(&self) -> Option<u16> {
+        self.port
+    }
+
+    /// Number of packets sent or received.
+    pub fn packets(&self) -> u64 {
+        self.packets
+    }
+
+    /// Number of bytes sent or received.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Number of packets sent.
+    pub fn tx_packets(&self) -> u64 {
+        self.tx_packets
+    }
+
+    /// Number of bytes sent.
+    pub fn tx_bytes(&self) -> u64 {
+        self.tx_bytes
+    }
+
+    /// Number of packets received.
+    pub fn rx_packets(&self) -> u64 {
+        self.rx_packets
+    }
+
+    /// Number of bytes received.
+    pub fn rx_bytes(&self) -> u64 {
+        self.rx_bytes
+    }
+}
+
+/// A protocol in the protocol hierarchy (-z io,phs), with the protocols it encapsulates.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProtocolStats {
+    protocol: String,
+    frames: u64,
+    bytes: u64,
+    children: Vec<ProtocolStats>,
+}
+
+impl ProtocolStats {
+    /// Protocol name (example: "udp").
+    pub fn protocol(&self) -> &str {
+        &self.protocol
+    }
+
+    /// Number of frames containing this protocol, at this position of the hierarchy.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Number of bytes of frames containing this protocol, at this position of the hierarchy.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Protocols encapsulated in this protocol.
+    pub fn children(&self) -> &[ProtocolStats] {
+        &self.children
+    }
+}
+
+/// Number of frames and bytes in a time interval (-z io,stat,<interval>).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IoInterval {
+    start: Duration,
+    end: Option<Duration>,
+    frames: u64,
+    bytes: u64,
+}
+
+impl IoInterval {
+    /// Start of the interval, relative to the first packet of the capture.
+    pub fn start(&self) -> Duration {
+        self.start
+    }
+
+    /// End of the interval, relative to the first packet of the capture.
+    /// None for the last interval, which ends with the capture.
+    pub fn end(&self) -> Option<Duration> {
+        self.end
+    }
+
+    /// Number of frames in the interval.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Number of bytes in the interval.
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+}
+
+/// Result of a statistics tree (example: -z http,tree or -z dns,tree).
+#[derive(Clone, Debug, PartialEq)]
+pub struct StatsTree {
+    name: String,
+    items: Vec<StatsTreeItem>,
+}
+
+impl StatsTree {
+    /// Name of the tree (example: "HTTP/Packet Counter").
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Top level items of the tree.
+    pub fn items(&self) -> &[StatsTreeItem] {
+        &self.items
+    }
+
+    /// Search an item by topic, in the whole tree.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # let builder = rtshark::RTSharkBuilder::builder().input_path("/tmp/my.pcap");
+    /// if let Ok(tree) = builder.stats_tree("http") {
+    ///     let gets = tree.item("GET").map(|i| i.count()).unwrap_or(0);
+    /// }
+    /// ```
+    pub fn item(&self, topic: &str) -> Option<&StatsTreeItem> {
+        let mut stack: Vec<&StatsTreeItem> = self.items.iter().rev().collect();
+        while let Some(item) = stack.pop() {
+            if item.topic == topic {
+                return Some(item);
+            }
+            stack.extend(item.children.iter().rev());
+        }
+        None
+    }
+}
+
+/// An item of a statistics tree, with its sub-items.
+/// Optional values are only provided by TShark for some items.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StatsTreeItem {
+    topic: String,
+    count: u64,
+    average: Option<f64>,
+    min: Option<f64>,
+    max: Option<f64>,
+    rate: Option<f64>,
+    percent: Option<f64>,
+    burst_rate: Option<f64>,
+    burst_start: Option<f64>,
+    children: Vec<StatsTreeItem>,
+}
+
+impl StatsTreeItem {
+    /// Topic of the item (example: "Total HTTP Packets").
+    pub fn topic(&self) -> &str {
+        &self.topic
+    }
+
+    /// Number of occurrences.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Average value.
+    pub fn average(&self) -> Option<f64> {
+        self.average
+    }
+
+    /// Minimum value.
+    pub fn min(&self) -> Option<f64> {
+        self.min
+    }
+
+    /// Maximum value.
+    pub fn max(&self) -> Option<f64> {
+        self.max
+    }
+
+    /// Rate, in occurrences per millisecond.
+    pub fn rate(&self) -> Option<f64> {
+        self.rate
+    }
+
+    /// Percentage of the parent item count.
+    pub fn percent(&self) -> Option<f64> {
+        self.percent
+    }
+
+    /// Highest rate, in occurrences per millisecond.
+    pub fn burst_rate(&self) -> Option<f64> {
+        self.burst_rate
+    }
+
+    /// Time of the highest rate, in seconds relative to the first packet of the capture.
+    pub fn burst_start(&self) -> Option<f64> {
+        self.burst_start
+    }
+
+    /// Sub-items.
+    pub fn children(&self) -> &[StatsTreeItem] {
+        &self.children
+    }
+}
+
+/// Severity of an expert information.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ExpertSeverity {
+    /// Comment
+    Comment,
+    /// Chat
+    Chat,
+    /// Note
+    Note,
+    /// Warning
+    Warning,
+    /// Error
+    Error,
+}
+
+/// An expert information, aggregated over packets (-z expert).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExpertInfo {
+    severity: ExpertSeverity,
+    frequency: u64,
+    group: String,
+    protocol: String,
+    summary: String,
+}
+
+impl ExpertInfo {
+    /// Severity of the information.
+    pub fn severity(&self) -> ExpertSeverity {
+        self.severity
+    }
+
+    /// Number of occurrences.
+    pub fn frequency(&self) -> u64 {
+        self.frequency
+    }
+
+    /// Group of the information (example: "Sequence", "Malformed").
+    pub fn group(&self) -> &str {
+        &self.group
+    }
+
+    /// Protocol reporting the information.
+    pub fn protocol(&self) -> &str {
+        &self.protocol
+    }
+
+    /// Description of the information.
+    pub fn summary(&self) -> &str {
+        &self.summary
+    }
+}
+
+/// Build an error for an unexpected line of statistics output.
+fn invalid_line(tap: &str, line: &str) -> RTSharkError {
+    RTSharkError::invalid_data(format!(
+        "Unexpected {} statistics line: {}",
+        tap,
+        line.trim()
+    ))
+}
+
+/// Check if a line is a separator ("=====" or "-----").
+fn is_separator(line: &str) -> bool {
+    let line = line.trim();
+    !line.is_empty() && line.chars().all(|c| c == '=' || c == '-')
+}
+
+/// Parse a number printed by TShark, with optional thousands separators.
+fn parse_count(value: &str) -> Option<u64> {
+    value.replace([',', '\u{a0}'], "").parse().ok()
+}
+
+/// Parse a size, with an optional unit in the next token ("1234", "1234 bytes", "12 kB").
+fn parse_size<'a>(tokens: &mut std::iter::Peekable<impl Iterator<Item = &'a str>>) -> Option<u64> {
+    let value = tokens.next()?.replace([',', '\u{a0}'], "");
+
+    let multiplier = match tokens.peek().copied() {
+        Some("bytes") | Some("B") => 1,
+        Some("kB") => 1_000,
+        Some("KiB") => 1 << 10,
+        Some("MB") => 1_000_000,
+        Some("MiB") => 1 << 20,
+        Some("GB") => 1_000_000_000,
+        Some("GiB") => 1 << 30,
+        _ => return value.parse().ok(),
+    };
+    tokens.next();
+
+    if let Ok(value) = value.parse::<u64>() {
+        return Some(value * multiplier);
+    }
+    value
+        .parse::<f64>()
+        .ok()
+        .map(|v| (v * multiplier as f64).round() as u64)
+}
+
+/// Parse seconds printed by TShark ("1.25").
+fn parse_seconds(value: &str) -> Option<Duration> {
+    Duration::try_from_secs_f64(value.parse().ok()?).ok()
+}
+
+/// Parse "-z conv,<type>" output.
+pub(crate) fn parse_conversations(output: &str) -> Result<Vec<Conversation>> {
+    // ================================================================================
+    // TCP Conversations
+    // Filter:<No Filter>
+    //                                            |       <-      | |       ->      | |     Total     |    Relative    |   Duration   |
+    //                                            | Frames  Bytes | | Frames  Bytes | | Frames  Bytes |      Start     |              |
+    // 10.0.0.1:443          <-> 10.0.0.2:51234        5 1,234 bytes   6 2,345 bytes  11 3,579 bytes     0.000000000         1.2345
+    // ================================================================================
+    let mut conversations = vec![];
+
+    for line in output.lines() {
+        let mut tokens = line.split_whitespace().peekable();
+        let (Some(address_a), Some("<->"), Some(address_b)) =
+            (tokens.next(), tokens.next(), tokens.next())
+        else {
+            continue;
+        };
+
+        let mut parse = || -> Option<Conversation> {
+            let frames_b_to_a = parse_count(tokens.next()?)?;
+            let bytes_b_to_a = parse_size(&mut tokens)?;
+            let frames_a_to_b = parse_count(tokens.next()?)?;
+            let bytes_a_to_b = parse_size(&mut tokens)?;
+            let frames = parse_count(tokens.next()?)?;
+            let bytes = parse_size(&mut tokens)?;
+            let relative_start = parse_seconds(tokens.next()?)?;
+            let duration = parse_seconds(tokens.next()?)?;
+
+            Some(Conversation {
+                address_a: address_a.to_owned(),
+                address_b: address_b.to_owned(),
+                frames_a_to_b,
+                bytes_a_to_b,
+                frames_b_to_a,
+                bytes_b_to_a,
+                frames,
+                bytes,
+                relative_start,
+                duration,
+            })
+        };
+
+        conversations.push(parse().ok_or_else(|| invalid_line("conv", line))?);
+    }
+
+    Ok(conversations)
+}
+
+/// Parse "-z endpoints,<type>" output.
+pub(crate) fn parse_endpoints(output: &str) -> Result<Vec<Endpoint>> {
+    // ================================================================================
+    // TCP Endpoints
+    // Filter:<No Filter>
+    //                        |  Port  ||  Packets  | |  Bytes  | | Tx Packets | | Tx Bytes | | Rx Packets | | Rx Bytes |
+    // 10.0.0.1                 443         11        3579          6            2345          5            1234
+    // ================================================================================
+    let mut endpoints = vec![];
+    let mut with_port = None;
+
+    for line in output.lines() {
+        if is_separator(line) || line.starts_with("Filter:") {
+            continue;
+        }
+        if line.contains('|') {
+            with_port = Some(line.contains("Port"));
+            continue;
+        }
+        let Some(with_port) = with_port else {
+            // title line
+            continue;
+        };
+
+        let mut tokens = line.split_whitespace().peekable();
+        let Some(address) = tokens.next() else {
+            continue;
+        };
+
+        let mut parse = || -> Option<Endpoint> {
+            let port = if with_port {
+                Some(tokens.next()?.parse().ok()?)
+            } else {
+                None
+            };
+
+            Some(Endpoint {
+                address: address.to_owned(),
+                port,
+                packets: parse_count(tokens.next()?)?,
+                bytes: parse_size(&mut tokens)?,
+                tx_packets: parse_count(tokens.next()?)?,
+                tx_bytes: parse_size(&mut tokens)?,
+                rx_packets: parse_count(tokens.next()?)?,
+                rx_bytes: parse_size(&mut tokens)?,
+            })
+        };
+
+        endpoints.push(parse().ok_or_else(|| invalid_line("endpoints", line))?);
+    }
+
+    Ok(endpoints)
+}
+
+/// Parse "-z io,phs" output.
+pub(crate) fn parse_protocol_hierarchy(output: &str) -> Result<Vec<ProtocolStats>> {
+    // ===================================================================
+    // Protocol Hierarchy Statistics
+    // Filter:
+    //
+    // eth                                      frames:4 bytes:296
+    //   ip                                     frames:4 bytes:296
+    //     udp                                  frames:4 bytes:296
+    // ===================================================================
+
+    /// Attach the last protocol of the stack to its parent.
+    fn pop(stack: &mut Vec<(usize, ProtocolStats)>, roots: &mut Vec<ProtocolStats>) {
+        if let Some((_, stats)) = stack.pop() {
+            match stack.last_mut() {
+                Some((_, parent)) => parent.children.push(stats),
+                None => roots.push(stats),
+            }
+        }
+    }
+
+    let mut roots = vec![];
+    let mut stack: Vec<(usize, ProtocolStats)> = vec![];
+
+    for line in output.lines() {
+        if !line.contains("frames:") {
+            continue;
+        }
+
+        let depth = line.len() - line.trim_start().len();
+        let mut tokens = line.split_whitespace();
+
+        let mut parse = || -> Option<ProtocolStats> {
+            let protocol = tokens.next()?;
+            let frames = tokens.next()?.strip_prefix("frames:")?;
+            let bytes = tokens.next()?.strip_prefix("bytes:")?;
+            Some(ProtocolStats {
+                protocol: protocol.to_owned(),
+                frames: parse_count(frames)?,
+                bytes: parse_count(bytes)?,
+                children: vec![],
+            })
+        };
+        let stats = parse().ok_or_else(|| invalid_line("io,phs", line))?;
+
+        while stack.last().is_some_and(|(d, _)| *d >= depth) {
+            pop(&mut stack, &mut roots);
+        }
+        stack.push((depth, stats));
+    }
+
+    while !stack.is_empty() {
+        pop(&mut stack, &mut roots);
+    }
+
+    Ok(roots)
+}
+
+/// Parse "-z io,stat,<interval>" output.
+pub(crate) fn parse_io_statistics(output: &str) -> Result<Vec<IoInterval>> {
+    // ===================================
+    // | IO Statistics                   |
+    // | Duration: 2.5 secs              |
+    // | Interval: 1 secs                |
+    // |---------------------------------|
+    // |          |1               |
+    // | Interval | Frames | Bytes |
+    // |---------------------------|
+    // | 0 <> 1   |      2 |   148 |
+    // | 1 <> 2   |      1 |    74 |
+    // | 2 <> Dur |      1 |    74 |
+    // ===================================
+    let mut intervals = vec![];
+
+    for line in output.lines() {
+        let cells: Vec<&str> = line
+            .split('|')
+            .map(str::trim)
+            .filter(|c| !c.is_empty())
+            .collect();
+
+        let Some((start, end)) = cells.first().and_then(|c| c.split_once("<>")) else {
+            continue;
+        };
+
+        let parse = || -> Option<IoInterval> {
+            let end = end.trim();
+            Some(IoInterval {
+                start: parse_interval_time(start.trim())?,
+                end: if end == "Dur" {
+                    None
+                } else {
+                    Some(parse_interval_time(end)?)
+                },
+                frames: parse_count(cells.get(1)?)?,
+                bytes: parse_count(cells.get(2)?)?,
+            })
+        };
+
+        intervals.push(parse().ok_or_else(|| invalid_line("io,stat", line))?);
+    }
+
+    Ok(intervals)
+}
+
+/// Parse an interval time, in seconds ("1.5") or as "hh:mm:ss" for long captures.
+fn parse_interval_time(value: &str) -> Option<Duration> {
+    if let Some(duration) = parse_seconds(value) {
+        return Some(duration);
+    }
+
+    let mut secs = 0.0;
+    for part in value.split(':') {
+        secs = secs * 60.0 + part.parse::<f64>().ok()?;
+    }
+    Duration::try_from_secs_f64(secs).ok()
+}
+
+/// Parse a statistics tree output (example: "-z http,tree").
+pub(crate) fn parse_stats_tree(output: &str) -> Result<StatsTree> {
+    // ===================================================================
+    // HTTP/Packet Counter:
+    // Topic / Item            Count         Average       Min Val       Max Val       Rate (ms)     Percent       Burst Rate    Burst Start
+    // -----------------------------------------------------------------------------------------------------------------------------------
+    // Total HTTP Packets      4                                                       0.0011        100%          0.0400        0.000
+    //  HTTP Request Packets   2                                                       0.0006        50.00%        0.0200        0.000
+    //   GET                   2                                                       0.0006        100.00%       0.0200        0.000
+    // ===================================================================
+
+    /// Attach the last item of the stack to its parent.
+    fn pop(stack: &mut Vec<(usize, StatsTreeItem)>, roots: &mut Vec<StatsTreeItem>) {
+        if let Some((_, item)) = stack.pop() {
+            match stack.last_mut() {
+                Some((_, parent)) => parent.children.push(item),
+                None => roots.push(item),
+            }
+        }
+    }
+
+    let mut name = None;
+    let mut columns: Option<Vec<(usize, String)>> = None;
+    let mut roots = vec![];
+    let mut stack: Vec<(usize, StatsTreeItem)> = vec![];
+
+    for line in output.lines() {
+        if line.trim().is_empty() || is_separator(line) {
+            continue;
+        }
+
+        let Some(columns) = &columns else {
+            if line.starts_with("Topic / Item") {
+                columns = Some(header_columns(line));
+            } else if name.is_none() {
+                name = Some(line.trim().trim_end_matches(':').to_owned());
+            }
+            continue;
+        };
+
+        // values are left aligned on column titles. Column positions are byte offsets when
+        // TShark pads non-ASCII topics in bytes, else character offsets: the count is always
+        // printed at the start of its column.
+        let in_bytes = line.is_ascii()
+            || columns.get(1).is_some_and(|(start, _)| {
+                line.is_char_boundary(*start)
+                    && line[..*start].ends_with(' ')
+                    && line[*start..].starts_with(|c: char| !c.is_whitespace())
+            });
+        let boundary = |position: usize| -> usize {
+            if in_bytes {
+                return position.min(line.len());
+            }
+            line.char_indices()
+                .nth(position)
+                .map_or(line.len(), |(i, _)| i)
+        };
+        let cell = |title: &str| -> Option<&str> {
+            let index = columns
+                .iter()
+                .position(|(_, t)| t.eq_ignore_ascii_case(title))?;
+            let start = boundary(columns[index].0);
+            let end = columns.get(index + 1).map_or(line.len(), |c| boundary(c.0));
+            let value = line.get(start..end)?.trim();
+            (!value.is_empty()).then_some(value)
+        };
+        let number =
+            |title: &str| -> Option<f64> { cell(title)?.trim_end_matches('%').parse().ok() };
+
+        let topic_end = columns.get(1).map_or(line.len(), |c| boundary(c.0));
+        let topic = line.get(..topic_end).unwrap_or(line);
+        let depth = topic.len() - topic.trim_start().len();
+
+        let item = StatsTreeItem {
+            topic: topic.trim().to_owned(),
+            count: cell("Count")
+                .and_then(parse_count)
+                .ok_or_else(|| invalid_line("stats tree", line))?,
+            average: number("Average"),
+            min: number("Min Val"),
+            max: number("Max Val"),
+            rate: number("Rate (ms)"),
+            percent: number("Percent"),
+            burst_rate: number("Burst Rate"),
+            burst_start: number("Burst Start"),
+            children: vec![],
+        };
+
+        while stack.last().is_some_and(|(d, _)| *d >= depth) {
+            pop(&mut stack, &mut roots);
+        }
+        stack.push((depth, item));
+    }
+
+    while !stack.is_empty() {
+        pop(&mut stack, &mut roots);
+    }
+
+    let Some(name) = name else {
+        return Err(RTSharkError::invalid_data(
+            "No statistics tree found in TShark output",
+        ));
+    };
+
+    Ok(StatsTree { name, items: roots })
+}
+
+/// Get the position and title of each column of a header, columns being separated by at least 2 spaces.
+fn header_columns(line: &str) -> Vec<(usize, String)> {
+    let mut columns = vec![];
+    let mut start = None;
+    let mut spaces = 0;
+
+    for (i, c) in line.char_indices() {
+        if c == ' ' {
+            spaces += 1;
+            continue;
+        }
+        if start.is_none() || spaces >= 2 {
+            if let Some(s) = start {
+                columns.push((s, line[s..i].trim().to_owned()));
+            }
+            start = Some(i);
+        }
+        spaces = 0;
+    }
+    if let Some(s) = start {
+        columns.push((s, line[s..].trim().to_owned()));
+    }
+
+    columns
+}
+
+/// Parse "-z expert" output.
+pub(crate) fn parse_expert(output: &str) -> Result<Vec<ExpertInfo>> {
+    // Errors (1)
+    // =============
+    //    Frequency      Group           Protocol  Summary
+    //            1  Malformed                DNS  Malformed Packet (Exception occurred)
+    //
+    // Warns (2)
+    // =============
+    //    Frequency      Group           Protocol  Summary
+    //            2   Sequence                TCP  Previous segment not captured
+    let mut infos = vec![];
+    let mut severity = None;
+
+    for line in output.lines() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || is_separator(line) || trimmed.starts_with("Frequency") {
+            continue;
+        }
+
+        if !line.starts_with(' ') {
+            severity = if trimmed.starts_with("Error") {
+                Some(ExpertSeverity::Error)
+            } else if trimmed.starts_with("Warn") {
+                Some(ExpertSeverity::Warning)
+            } else if trimmed.starts_with("Note") {
+                Some(ExpertSeverity::Note)
+            } else if trimmed.starts_with("Chat") {
+                Some(ExpertSeverity::Chat)
+            } else if trimmed.starts_with("Comment") {
+                Some(ExpertSeverity::Comment)
+            } else {
+                None
+            };
+            continue;
+        }
+
+        let Some(severity) = severity else {
+            continue;
+        };
+
+        // groups may contain a space ("Response Code"), columns are separated by at least 2 spaces
+        let cells = header_columns(line);
+        let parse = || -> Option<ExpertInfo> {
+            Some(ExpertInfo {
+                severity,
+                frequency: parse_count(&cells.first()?.1)?,
+                group: cells.get(1)?.1.clone(),
+                protocol: cells.get(2)?.1.clone(),
+                // the summary may contain several spaces
+                summary: line[cells.get(3)?.0..].trim().to_owned(),
+            })
+        };
+
+        infos.push(parse().ok_or_else(|| invalid_line("expert", line))?);
+    }
+
+    Ok(infos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_conversations() {
+        let output = "================================================================================
+TCP Conversations
+Filter:<No Filter>
+                                               |       <-      | |       ->      | |     Total     |    Relative    |   Duration   |
+                                               | Frames  Bytes | | Frames  Bytes | | Frames  Bytes |      Start     |              |
+10.0.0.1:443           <-> 10.0.0.2:51234           5 1,234 bytes       6 2 kB        11 3,234 bytes     0.500000000         1.2500
+10.0.0.1:80            <-> 10.0.0.3:40000           1 60            1 60              2 120             1.000000000         0.0000
+================================================================================
+";
+        let conversations = parse_conversations(output).unwrap();
+        assert_eq!(conversations.len(), 2);
+
+        let c = &conversations[0];
+        assert_eq!(c.address_a(), "10.0.0.1:443");
+        assert_eq!(c.address_b(), "10.0.0.2:51234");
+        assert_eq!(c.frames_b_to_a(), 5);
+        assert_eq!(c.bytes_b_to_a(), 1234);
+        assert_eq!(c.frames_a_to_b(), 6);
+        assert_eq!(c.bytes_a_to_b(), 2000);
+        assert_eq!(c.frames(), 11);
+        assert_eq!(c.bytes(), 3234);
+        assert_eq!(c.relative_start(), Duration::from_millis(500));
+        assert_eq!(c.duration(), Duration::from_millis(1250));
+
+        assert_eq!(conversations[1].bytes(), 120);
+
+        assert!(parse_conversations("a <-> b 1 2").is_err());
+    }
+
+    #[test]
+    fn test_parse_endpoints() {
+        let output = "================================================================================
+UDP Endpoints
+Filter:<No Filter>
+                       |  Port  ||  Packets  | |  Bytes  | | Tx Packets | | Tx Bytes | | Rx Packets | | Rx Bytes |
+127.0.0.1                 53          2          56           1              28           1              28
+================================================================================
+";
+        let endpoints = parse_endpoints(output).unwrap();
+        assert_eq!(
+            endpoints,
+            vec![Endpoint {
+                address: "127.0.0.1".to_owned(),
+                port: Some(53),
+                packets: 2,
+                bytes: 56,
+                tx_packets: 1,
+                tx_bytes: 28,
+                rx_packets: 1,
+                rx_bytes: 28,
+            }]
+        );
+
+        let output = "IPv4 Endpoints
+Filter:<No Filter>
+                       |  Packets  | |  Bytes  | | Tx Packets | | Tx Bytes | | Rx Packets | | Rx Bytes |
+127.0.0.1                    2          56 bytes     1              28 bytes     1              28 bytes
+";
+        let endpoints = parse_endpoints(output).unwrap();
+        assert_eq!(endpoints[0].port(), None);
+        assert_eq!(endpoints[0].rx_bytes(), 28);
+    }
+
+    #[test]
+    fn test_parse_protocol_hierarchy() {
+        let output = "
+===================================================================
+Protocol Hierarchy Statistics
+Filter:
+
+eth                                      frames:4 bytes:296
+  ip                                     frames:3 bytes:222
+    udp                                  frames:3 bytes:222
+      dns                                frames:3 bytes:222
+  arp                                    frames:1 bytes:74
+===================================================================
+";
+        let roots = parse_protocol_hierarchy(output).unwrap();
+        assert_eq!(roots.len(), 1);
+        assert_eq!(roots[0].protocol(), "eth");
+        assert_eq!(roots[0].frames(), 4);
+
+        let children: Vec<&str> = roots[0].children().iter().map(|p| p.protocol()).collect();
+        assert_eq!(children, vec!["ip", "arp"]);
+        assert_eq!(
+            roots[0].children()[0].children()[0].children()[0].bytes(),
+            222
+        );
+    }
+
+    #[test]
+    fn test_parse_io_statistics() {
+        let output = "
+===================================
+| IO Statistics                   |
+|                                 |
+| Duration: 2.5 secs              |
+| Interval: 1 secs                |
+|                                 |
+| Col 1: Frames and bytes         |
+|---------------------------------|
+|          |1               |
+| Interval | Frames | Bytes |
+|---------------------------|
+| 0 <> 1   |      2 |   148 |
+| 1 <> 2   |      1 |    74 |
+| 2 <> Dur |      1 |    74 |
+===================================
+";
+        let intervals = parse_io_statistics(output).unwrap();
+        assert_eq!(intervals.len(), 3);
+        assert_eq!(intervals[0].start(), Duration::ZERO);
+        assert_eq!(intervals[0].end(), Some(Duration::from_secs(1)));
+        assert_eq!(intervals[0].frames(), 2);
+        assert_eq!(intervals[0].bytes(), 148);
+        assert_eq!(intervals[2].end(), None);
+
+        assert_eq!(
+            parse_interval_time("01:00:02"),
+            Some(Duration::from_secs(3602))
+        );
+    }
+
+    #[test]
+    fn test_parse_stats_tree() {
+        let output = "
+===================================================================
+HTTP/Packet Counter:
+Topic / Item            Count         Average       Min Val       Max Val       Rate (ms)     Percent       Burst Rate    Burst Start
+------------------------------------------------------------------------------------------------------------------------------------
+Total HTTP Packets      4                                                       0.0011        100%          0.0400        0.000
+ HTTP Request Packets   2                                                       0.0006        50.00%        0.0200        0.000
+  GET                   2                                                       0.0006        100.00%       0.0200        0.000
+ HTTP Response Packets  2                                                       0.0006        50.00%        0.0200        0.010
+  2xx: Success          2                                                       0.0006        100.00%       0.0200        0.010
+   200 OK               2                                                       0.0006        100.00%       0.0200        0.010
+
+-------------------------------------------------------------------------------------------------------------------------------------
+";
+        let tree = parse_stats_tree(output).unwrap();
+        assert_eq!(tree.name(), "HTTP/Packet Counter");
+        assert_eq!(tree.items().len(), 1);
+
+        let total = &tree.items()[0];
+        assert_eq!(total.topic(), "Total HTTP Packets");
+        assert_eq!(total.count(), 4);
+        assert_eq!(total.average(), None);
+        assert_eq!(total.percent(), Some(100.0));
+        assert_eq!(total.burst_rate(), Some(0.04));
+        assert_eq!(total.children().len(), 2);
+
+        let ok = tree.item("200 OK").unwrap();
+        assert_eq!(ok.count(), 2);
+        assert_eq!(ok.burst_start(), Some(0.01));
+        assert_eq!(
+            tree.item("HTTP Response Packets").unwrap().children().len(),
+            1
+        );
+        assert!(tree.item("POST").is_none());
+
+        assert!(parse_stats_tree("").is_err());
+    }
+
+    #[test]
+    fn test_parse_stats_tree_utf8() {
+        // topics padded in characters, or in bytes
+        let output = "
+===================================================================
+DNS/Query names:
+Topic / Item            Count         Average       Min Val       Max Val       Rate (ms)     Percent       Burst Rate    Burst Start
+------------------------------------------------------------------------------------------------------------------------------------
+Query names             3                                                       0.0011        100%          0.0400        0.000
+ bücher.de              2                                                       0.0006        66.67%        0.0200        0.000
+ 日本.jp              1                                                       0.0003        33.33%        0.0100        0.010
+------------------------------------------------------------------------------------------------------------------------------------
+";
+        let tree = parse_stats_tree(output).unwrap();
+        let names = &tree.items()[0];
+        assert_eq!(names.children().len(), 2);
+
+        let books = tree.item("bücher.de").unwrap();
+        assert_eq!(books.count(), 2);
+        assert_eq!(books.percent(), Some(66.67));
+
+        let japan = tree.item("日本.jp").unwrap();
+        assert_eq!(japan.count(), 1);
+        assert_eq!(japan.rate(), Some(0.0003));
+        assert_eq!(japan.burst_start(), Some(0.01));
+    }
+
+    #[test]
+    fn test_parse_expert() {
+        let output = "
+Errors (1)
+=============
+   Frequency      Group           Protocol  Summary
+           1  Malformed                DNS  Malformed Packet (Exception occurred)
+
+Warns (2)
+=============
+   Frequency      Group           Protocol  Summary
+           2   Sequence                TCP  Previous segment(s) not captured  (common at capture start)
+           1  Response Code           HTTP  Server Error (500)
+";
+        let infos = parse_expert(output).unwrap();
+        assert_eq!(infos.len(), 3);
+
+        assert_eq!(infos[0].severity(), ExpertSeverity::Error);
+        assert_eq!(infos[0].frequency(), 1);
+        assert_eq!(infos[0].group(), "Malformed");
+        assert_eq!(infos[0].protocol(), "DNS");
+        assert_eq!(infos[0].summary(), "Malformed Packet (Exception occurred)");
+
+        assert_eq!(infos[1].severity(), ExpertSeverity::Warning);
+        assert_eq!(
+            infos[1].summary(),
+            "Previous segment(s) not captured  (common at capture start)"
+        );
+        assert_eq!(infos[2].group(), "Response Code");
+    }
+}
@@ -0,0 +1,396 @@
+//! Background reader of TShark error output.
+//!
+//! TShark may print many warnings on stderr during long captures. If nobody reads them,
+//! the pipe buffer fills up and TShark blocks forever. Lines are read by a thread as soon
+//! as they are printed and kept in a bounded buffer.
+
+use crate::diagnostic::{parse_diagnostics, Diagnostic, DiagnosticKind, DiagnosticSeverity};
+use crate::process::TsharkProcess;
+use crate::{RTSharkError, Result};
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader};
+use std::process::{ChildStderr, Output};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::Duration;
+
+/// Maximum number of stderr lines kept in memory. Oldest lines are dropped first.
+pub(crate) const STDERR_CAPACITY: usize = 1000;
+
+/// Maximum time to wait for TShark to close stderr once it has exited.
+const STDERR_CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Lines read from stderr.
+#[derive(Default)]
+pub(crate) struct StderrLines {
+    /// last lines, up to STDERR_CAPACITY
+    lines: VecDeque<String>,
+    /// first line which is not a known warning, kept even if dropped from lines
+    first_error: Option<String>,
+    /// number of stderr streams not closed yet
+    open: usize,
+}
+
+impl StderrLines {
+    /// Store a line read from stderr, dropping the oldest one if the buffer is full.
+    pub(crate) fn push(&mut self, line: &[u8]) {
+        let line = String::from_utf8_lossy(line)
+            .trim_end_matches(['\n', '\r'])
+            .to_owned();
+
+        if self.first_error.is_none() && !is_warning(&line) {
+            self.first_error = Some(line.clone());
+        }
+        if self.lines.len() == STDERR_CAPACITY {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line);
+    }
+
+    /// Get all lines kept in the buffer.
+    pub(crate) fn lines(&self) -> Vec<String> {
+        self.lines.iter().cloned().collect()
+    }
+
+    /// Get the lines kept in the buffer which are known to be non-fatal warnings.
+    pub(crate) fn warnings(&self) -> Vec<String> {
+        self.lines
+            .iter()
+            .filter(|l| is_warning(l))
+            .cloned()
+            .collect()
+    }
+
+    /// Parse the lines kept in the buffer.
+    pub(crate) fn diagnostics(&self) -> Vec<Diagnostic> {
+        parse_diagnostics(&self.lines())
+    }
+
+    /// Take the first error line, so that it is reported once.
+    pub(crate) fn take_error(&mut self) -> Option<String> {
+        self.first_error.take()
+    }
+}
+
+/// Reads TShark stderr in a background thread.
+pub(crate) struct StderrReader {
+    shared: Arc<(Mutex<StderrLines>, Condvar)>,
+}
+
+impl StderrReader {
+    /// Start reading stderr in a background thread.
+    pub(crate) fn new(stderr: ChildStderr) -> Self {
+        let reader = StderrReader {
+            shared: Arc::new((Mutex::new(StderrLines::default()), Condvar::new())),
+        };
+        reader.merge(stderr);
+        reader
+    }
+
+    /// Continue with the stderr of the next TShark process, once the previous one is closed.
+    /// Lines of previous processes are kept.
+    pub(crate) fn attach(&self, stderr: ChildStderr) {
+        drop(self.wait_closed());
+        self.merge(stderr);
+    }
+
+    /// Also read the stderr of another process, like mergecap piped to TShark.
+    pub(crate) fn merge(&self, stderr: ChildStderr) {
+        self.shared.0.lock().unwrap_or_else(|e| e.into_inner()).open += 1;
+
+        let thread_shared = self.shared.clone();
+        let spawned = std::thread::Builder::new()
+            .name("rtshark-stderr".to_owned())
+            .spawn(move || read_lines(BufReader::new(stderr), &thread_shared));
+
+        if let Err(e) = spawned {
+            eprintln!("Error while reading tshark stderr: {e}");
+            self.shared.0.lock().unwrap_or_else(|e| e.into_inner()).open -= 1;
+        }
+    }
+
+    /// Get all lines kept in the buffer.
+    pub(crate) fn lines(&self) -> Vec<String> {
+        self.shared
+            .0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .lines()
+    }
+
+    /// Get the lines kept in the buffer which are known to be non-fatal warnings.
+    pub(crate) fn warnings(&self) -> Vec<String> {
+        self.shared
+            .0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .warnings()
+    }
+
+    /// Parse the lines kept in the buffer.
+    pub(crate) fn diagnostics(&self) -> Vec<Diagnostic> {
+        self.shared
+            .0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .diagnostics()
+    }
+
+    /// Wait until stderr is closed (TShark has exited) and take the first error line.
+    pub(crate) fn take_error(&self) -> Option<String> {
+        self.wait_closed().take_error()
+    }
+
+    /// Wait until stderr is closed (TShark has exited) and get all lines kept in the buffer.
+    pub(crate) fn wait_lines(&self) -> Vec<String> {
+        self.wait_closed().lines()
+    }
+
+    /// Wait until all stderr streams are closed, for a little time.
+    fn wait_closed(&self) -> MutexGuard<'_, StderrLines> {
+        let (lock, closed) = &*self.shared;
+        let lines = lock.lock().unwrap_or_else(|e| e.into_inner());
+        let (lines, _) = closed
+            .wait_timeout_while(lines, STDERR_CLOSE_TIMEOUT, |l| l.open > 0)
+            .unwrap_or_else(|e| e.into_inner());
+        lines
+    }
+}
+
+/// Read stderr lines until it is closed.
+fn read_lines(mut stderr: BufReader<ChildStderr>, shared: &(Mutex<StderrLines>, Condvar)) {
+    let mut buf = vec![];
+    loop {
+        buf.clear();
+        match stderr.read_until(b'\n', &mut buf) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => (),
+        }
+
+        shared
+            .0
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(&buf);
+    }
+
+    shared.0.lock().unwrap_or_else(|e| e.into_inner()).open -= 1;
+    shared.1.notify_all();
+}
+
+/// Check if a stderr line is a known non-fatal message: capture information, dissector warnings,
+/// truncated capture file ...
+pub(crate) fn is_warning(line: &str) -> bool {
+    line.trim().is_empty() || Diagnostic::parse(line).severity() != DiagnosticSeverity::Error
+}
+
+/// Check the exit status of a TShark run to completion, and get its fatal error from stderr
+/// if it failed. A failed run is an error, even if TShark printed no error on stderr.
+pub(crate) fn check_output(output: &Output) -> Result<()> {
+    if output.status.success() {
+        return Ok(());
+    }
+
+    // if process stops, there may be due to an error, we can get it in stderr
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let lines: Vec<String> = stderr.lines().map(str::to_owned).collect();
+    match lines.iter().find(|l| !is_warning(l)) {
+        Some(line) => Err(RTSharkError::from_stderr(Some(output.status), line, &lines)),
+        // killed by a signal, or failed with warnings only
+        None => Err(RTSharkError::TsharkExited {
+            status: Some(output.status),
+            stderr: lines.join("\n"),
+        }),
+    }
+}
+
+/// Check if TShark has exited once its output is closed, and get its fatal error if it failed.
+/// A failure is an error even without any error line, unless TShark was killed or stopped by rtshark.
+pub(crate) fn check_exit(process: &TsharkProcess, stderr: &StderrReader) -> Result<()> {
+    if !process.try_wait_exited() {
+        // still running
+        return Ok(());
+    }
+
+    let Some(status) = process.take_failure() else {
+        return Ok(());
+    };
+
+    // if process stops, there may be due to an error, we can get it in stderr
+    let lines = stderr.wait_lines();
+    match stderr.take_error() {
+        Some(line) => Err(RTSharkError::from_stderr(Some(status), &line, &lines)),
+        None if is_truncated(&lines) => Ok(()),
+        // crashed, or failed with warnings only
+        None => Err(RTSharkError::TsharkExited {
+            status: Some(status),
+            stderr: lines.join("\n"),
+        }),
+    }
+}
+
+/// Check if TShark failed because the capture file was cut short, which is only a warning.
+pub(crate) fn is_truncated(lines: &[String]) -> bool {
+    parse_diagnostics(lines)
+        .iter()
+        .any(|d| d.kind() == DiagnosticKind::TruncatedCapture)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::{Command, Stdio};
+
+    #[test]
+    fn test_is_warning() {
+        assert!(is_warning(
+            "Running as user \"root\" and group \"root\". This could be dangerous."
+        ));
+        assert!(is_warning("Capturing on 'Loopback: lo'"));
+        assert!(is_warning(
+            " ** (tshark:1234) 10:00:00.000000 [Epan WARNING] -- Dissector bug"
+        ));
+        assert!(is_warning("12 packets captured"));
+        assert!(is_warning("1 packet dropped from Loopback: lo"));
+        assert!(is_warning("3 packets dropped (0.5%)"));
+        assert!(is_warning(
+            "tshark: The file \"/tmp/my.pcap\" appears to have been cut short in the middle of a packet."
+        ));
+        assert!(!is_warning("tshark: Invalid -Y filter"));
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_check_output() {
+        // the error is the first line which is not a warning
+        let script = r#"
+            echo 'Running as user "root" and group "root". This could be dangerous.' >&2
+            echo 'tshark: The file "/tmp/missing.pcap" doesn'"'"'t exist.' >&2
+            exit 2
+        "#;
+        let output = Command::new("sh").args(["-c", script]).output().unwrap();
+        assert!(matches!(
+            check_output(&output),
+            Err(RTSharkError::InputNotFound { path }) if path == "/tmp/missing.pcap"
+        ));
+
+        // failed without any error line
+        let script = r#"
+            echo 'Running as user "root" and group "root". This could be dangerous.' >&2
+            exit 2
+        "#;
+        let output = Command::new("sh").args(["-c", script]).output().unwrap();
+        assert!(matches!(
+            check_output(&output),
+            Err(RTSharkError::TsharkExited { status: Some(status), stderr })
+            if status.code() == Some(2) && stderr.starts_with("Running as user")
+        ));
+
+        let output = Command::new("sh")
+            .args(["-c", "kill -9 $$"])
+            .output()
+            .unwrap();
+        assert!(matches!(
+            check_output(&output),
+            Err(RTSharkError::TsharkExited { status: Some(status), .. }) if !status.success()
+        ));
+
+        let output = Command::new("sh").args(["-c", "exit 0"]).output().unwrap();
+        assert!(check_output(&output).is_ok());
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_stderr_reader() {
+        // more lines than the pipe buffer can hold
+        let mut child = Command::new("sh")
+            .args([
+                "-c",
+                "exec 1>&2; echo 'Capturing on lo'; i=0; while [ $i -lt 20000 ]; do echo '12 packets captured'; i=$((i+1)); done; echo 'tshark: fatal'; exit 2",
+            ])
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let stderr = StderrReader::new(child.stderr.take().unwrap());
+
+        // would block forever if stderr was not drained
+        assert!(!child.wait().unwrap().success());
+
+        let process = TsharkProcess::new(child);
+        let err = check_exit(&process, &stderr).unwrap_err();
+        assert!(
+            matches!(err, RTSharkError::TsharkExited { status: Some(status), .. }
+            if status.code() == Some(2))
+        );
+        assert!(process.id().is_none());
+
+        let lines = stderr.lines();
+        assert_eq!(lines.len(), STDERR_CAPACITY);
+        assert_eq!(lines.last().unwrap(), "tshark: fatal");
+        assert_eq!(stderr.warnings().len(), STDERR_CAPACITY - 1);
+
+        // fatal error is reported once
+        assert!(check_exit(&process, &stderr).is_ok());
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_check_exit_without_error_line() {
+        let mut child = Command::new("sh")
+            .args(["-c", "exit 1"])
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let stderr = StderrReader::new(child.stderr.take().unwrap());
+        child.wait().unwrap();
+
+        let process = TsharkProcess::new(child);
+        assert!(matches!(
+            check_exit(&process, &stderr),
+            Err(RTSharkError::TsharkExited { status: Some(status), stderr })
+            if status.code() == Some(1) && stderr.is_empty()
+        ));
+        assert!(check_exit(&process, &stderr).is_ok());
+
+        // killed by rtshark
+        let mut child = Command::new("sleep")
+            .arg("10")
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let stderr = StderrReader::new(child.stderr.take().unwrap());
+        let process = TsharkProcess::new(child);
+        process.kill();
+        assert!(check_exit(&process, &stderr).is_ok());
+    }
+
+    #[cfg(target_family = "unix")]
+    #[test]
+    fn test_truncated_capture() {
+        let mut child = Command::new("sh")
+            .args([
+                "-c",
+                "echo 'tshark: The file \"/tmp/my.pcap\" appears to have been cut short in the middle of a packet.' >&2; exit 2",
+            ])
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let stderr = StderrReader::new(child.stderr.take().unwrap());
+        child.wait().unwrap();
+
+        // truncated capture is a warning, not an error
+        let process = TsharkProcess::new(child);
+        assert!(check_exit(&process, &stderr).is_ok());
+
+        let diagnostics = stderr.diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].kind(),
+            crate::DiagnosticKind::TruncatedCapture
+        );
+        assert_eq!(diagnostics[0].severity(), DiagnosticSeverity::Warning);
+    }
+}
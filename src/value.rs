@@ -0,0 +1,387 @@
+//! Typed accessors on [Metadata] values.
+//!
+//! TShark PDML only provides strings: the "show" attribute (human readable) and the "value"
+//! attribute (hexadecimal bytes of the field). These functions convert them to Rust types.
+
+use crate::Metadata;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::Duration;
+
+/// Error returned when a [Metadata] value cannot be converted to the requested type.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValueError {
+    /// Name of the metadata
+    name: String,
+    /// Value which could not be converted
+    value: String,
+    /// Name of the requested type
+    expected: &'static str,
+}
+
+impl ValueError {
+    fn new(metadata: &Metadata, value: &str, expected: &'static str) -> Self {
+        ValueError {
+            name: metadata.name().to_owned(),
+            value: value.to_owned(),
+            expected,
+        }
+    }
+
+    /// Name of the metadata which could not be converted.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Value which could not be converted.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Name of the requested type (example: "u64").
+    pub fn expected(&self) -> &str {
+        self.expected
+    }
+}
+
+impl std::fmt::Display for ValueError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "cannot convert value '{}' of metadata '{}' to {}",
+            self.value, self.name, self.expected
+        )
+    }
+}
+
+impl std::error::Error for ValueError {}
+
+impl From<ValueError> for std::io::Error {
+    fn from(e: ValueError) -> Self {
+        std::io::Error::new(std::io::ErrorKind::InvalidData, e)
+    }
+}
+
+/// Parse an integer displayed by TShark, either in decimal or in hexadecimal with a "0x" prefix.
+fn parse_u64(value: &str) -> Option<u64> {
+    let value = value.trim();
+    match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        // from_str_radix also accepts a "+" sign, never printed by TShark
+        Some(hex) if hex.bytes().all(|b| b.is_ascii_hexdigit()) => {
+            u64::from_str_radix(hex, 16).ok()
+        }
+        None if value.bytes().all(|b| b.is_ascii_digit()) => value.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Parse a signed integer displayed by TShark, either in decimal or in hexadecimal with a "0x" prefix.
+fn parse_i64(value: &str) -> Option<i64> {
+    let value = value.trim();
+    match value.strip_prefix('-') {
+        Some(abs) => parse_u64(abs).and_then(|v| 0i64.checked_sub_unsigned(v)),
+        None => parse_u64(value).and_then(|v| i64::try_from(v).ok()),
+    }
+}
+
+/// Decode an hexadecimal string, with or without ':' separators.
+pub(crate) fn parse_hex(value: &str) -> Option<Vec<u8>> {
+    let digits: Vec<u8> = value
+        .trim()
+        .bytes()
+        .filter(|&b| b != b':' && b != b' ')
+        .collect();
+    // from_str_radix also accepts a "+" sign
+    let pairs = digits.chunks_exact(2);
+    if !pairs.remainder().is_empty() || !digits.iter().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    pairs
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
+
+/// Decode a big-endian unsigned integer of 1 to 8 bytes from an hexadecimal raw value.
+fn parse_raw_u64(raw_value: &str) -> Option<u64> {
+    let bytes = parse_hex(raw_value)?;
+    if bytes.is_empty() || bytes.len() > 8 {
+        return None;
+    }
+    Some(bytes.iter().fold(0, |acc, &b| (acc << 8) | b as u64))
+}
+
+/// Parse a number of seconds with an optional decimal part (example: "0.000123000 seconds").
+fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.split_whitespace().next()?;
+    let (secs, frac) = value.split_once('.').unwrap_or((value, ""));
+    if frac.len() > 9 || !frac.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let secs = if secs.is_empty() {
+        0
+    } else {
+        secs.parse().ok()?
+    };
+    let nanos = if frac.is_empty() {
+        0
+    } else {
+        frac.parse::<u32>().ok()? * 10u32.pow(9 - frac.len() as u32)
+    };
+    Some(Duration::new(secs, nanos))
+}
+
+impl Metadata {
+    /// Get this metadata value as an unsigned integer.
+    /// Both decimal and hexadecimal ("0x" prefix) values displayed by TShark are supported.
+    /// If the displayed value is not a number (a label like "IPv4 (0x0800)"), the raw value is decoded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let port = rtshark::Metadata::new("tcp.srcport".to_string(), "52796".to_string(), None, None, None);
+    /// assert_eq!(port.as_u64(), Ok(52796));
+    /// let flags = rtshark::Metadata::new("tcp.flags".to_string(), "0x0018".to_string(), None, None, None);
+    /// assert_eq!(flags.as_u64(), Ok(0x18));
+    /// ```
+    pub fn as_u64(&self) -> Result<u64, ValueError> {
+        parse_u64(self.value())
+            .or_else(|| self.raw_value.as_deref().and_then(parse_raw_u64))
+            .ok_or_else(|| ValueError::new(self, self.value(), "u64"))
+    }
+
+    /// Get this metadata value as a signed integer.
+    /// If the displayed value is not a number, the raw value is decoded as an unsigned integer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let rssi = rtshark::Metadata::new("wlan_radio.signal_dbm".to_string(), "-67".to_string(), None, None, None);
+    /// assert_eq!(rssi.as_i64(), Ok(-67));
+    /// ```
+    pub fn as_i64(&self) -> Result<i64, ValueError> {
+        parse_i64(self.value())
+            .or_else(|| {
+                let raw = self.raw_value.as_deref().and_then(parse_raw_u64)?;
+                i64::try_from(raw).ok()
+            })
+            .ok_or_else(|| ValueError::new(self, self.value(), "i64"))
+    }
+
+    /// Get this metadata value as a floating point number.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let rtt = rtshark::Metadata::new("tcp.analysis.ack_rtt".to_string(), "0.000123000".to_string(), None, None, None);
+    /// assert_eq!(rtt.as_f64(), Ok(0.000123));
+    /// ```
+    pub fn as_f64(&self) -> Result<f64, ValueError> {
+        self.value()
+            .trim()
+            .parse()
+            .map_err(|_| ValueError::new(self, self.value(), "f64"))
+    }
+
+    /// Get this metadata value as a boolean.
+    /// TShark displays booleans as "1"/"0" or "True"/"False", depending on its version.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let syn = rtshark::Metadata::new("tcp.flags.syn".to_string(), "True".to_string(), None, None, None);
+    /// assert_eq!(syn.as_bool(), Ok(true));
+    /// let ack = rtshark::Metadata::new("tcp.flags.ack".to_string(), "0".to_string(), None, None, None);
+    /// assert_eq!(ack.as_bool(), Ok(false));
+    /// ```
+    pub fn as_bool(&self) -> Result<bool, ValueError> {
+        let value = self.value().trim();
+        if value.eq_ignore_ascii_case("true") || value.eq_ignore_ascii_case("set") {
+            return Ok(true);
+        }
+        if value.eq_ignore_ascii_case("false") || value.eq_ignore_ascii_case("not set") {
+            return Ok(false);
+        }
+        parse_u64(value)
+            .map(|v| v != 0)
+            .ok_or_else(|| ValueError::new(self, self.value(), "bool"))
+    }
+
+    /// Get this metadata value as an IPv4 or IPv6 address.
+    /// If the displayed value is not an address, the raw value is decoded (4 or 16 bytes).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let ip_src = rtshark::Metadata::new("ip.src".to_string(), "127.0.0.1".to_string(), None, None, None);
+    /// assert_eq!(ip_src.as_ip_addr(), Ok(std::net::Ipv4Addr::LOCALHOST.into()));
+    /// ```
+    pub fn as_ip_addr(&self) -> Result<IpAddr, ValueError> {
+        if let Ok(addr) = self.value().trim().parse() {
+            return Ok(addr);
+        }
+        match parse_hex(self.raw_value()) {
+            Some(bytes) if bytes.len() == 4 => {
+                let bytes: [u8; 4] = bytes.try_into().unwrap();
+                Ok(Ipv4Addr::from(bytes).into())
+            }
+            Some(bytes) if bytes.len() == 16 => {
+                let bytes: [u8; 16] = bytes.try_into().unwrap();
+                Ok(Ipv6Addr::from(bytes).into())
+            }
+            _ => Err(ValueError::new(self, self.value(), "IP address")),
+        }
+    }
+
+    /// Get this metadata value as a MAC address (6 bytes).
+    /// If the displayed value is not a MAC address, the raw value is decoded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let eth_src = rtshark::Metadata::new("eth.src".to_string(), "00:1b:21:3a:4f:7c".to_string(), None, None, None);
+    /// assert_eq!(eth_src.as_mac(), Ok([0x00, 0x1b, 0x21, 0x3a, 0x4f, 0x7c]));
+    /// ```
+    pub fn as_mac(&self) -> Result<[u8; 6], ValueError> {
+        let from_show = self
+            .value()
+            .split([':', '-'])
+            .map(|b| match b.len() {
+                2 => u8::from_str_radix(b, 16).ok(),
+                _ => None,
+            })
+            .collect::<Option<Vec<u8>>>();
+
+        from_show
+            .filter(|bytes| bytes.len() == 6)
+            .or_else(|| parse_hex(self.raw_value()).filter(|bytes| bytes.len() == 6))
+            .map(|bytes| bytes.try_into().unwrap())
+            .ok_or_else(|| ValueError::new(self, self.value(), "MAC address"))
+    }
+
+    /// Get the bytes of this metadata, decoded from the hexadecimal raw value
+    /// ("value" attribute in PDML, with or without ':' separators).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let payload = rtshark::Metadata::new("tcp.payload".to_string(), "47:45:54".to_string(), None, None, None);
+    /// assert_eq!(payload.as_bytes(), Ok(b"GET".to_vec()));
+    /// ```
+    pub fn as_bytes(&self) -> Result<Vec<u8>, ValueError> {
+        parse_hex(self.raw_value()).ok_or_else(|| ValueError::new(self, self.raw_value(), "bytes"))
+    }
+
+    /// Get this metadata value as a duration, from a number of seconds (example: "frame.time_delta").
+    /// Epoch times (example: "frame.time_epoch") are returned as the duration since UNIX epoch.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let delta = rtshark::Metadata::new("frame.time_delta".to_string(), "0.000123000".to_string(), None, None, None);
+    /// assert_eq!(delta.as_duration(), Ok(std::time::Duration::from_micros(123)));
+    /// ```
+    pub fn as_duration(&self) -> Result<Duration, ValueError> {
+        parse_duration(self.value()).ok_or_else(|| ValueError::new(self, self.value(), "duration"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(value: &str, raw_value: Option<&str>) -> Metadata {
+        let mut metadata = Metadata::new("test".to_string(), value.to_string(), None, None, None);
+        metadata.raw_value = raw_value.map(|v| v.to_string());
+        metadata
+    }
+
+    #[test]
+    fn test_value_integers() {
+        assert_eq!(metadata("5432", None).as_u64(), Ok(5432));
+        assert_eq!(metadata("0x00000018", None).as_u64(), Ok(0x18));
+        assert_eq!(metadata("-12", None).as_i64(), Ok(-12));
+        assert_eq!(metadata("0x10", None).as_i64(), Ok(16));
+        assert!(metadata("-12", None).as_u64().is_err());
+        assert!(metadata("--12", None).as_i64().is_err());
+        assert!(metadata("Standard query", None).as_u64().is_err());
+        // TShark never prints a "+" sign
+        assert!(metadata("+5", None).as_u64().is_err());
+        assert!(metadata("0x+5", None).as_u64().is_err());
+        assert!(metadata("+5", None).as_i64().is_err());
+    }
+
+    #[test]
+    fn test_value_integers_raw() {
+        // label: fallback on raw value
+        assert_eq!(metadata("IPv4 (0x0800)", Some("0800")).as_u64(), Ok(0x0800));
+        assert_eq!(metadata("Echo (ping) request", Some("08")).as_i64(), Ok(8));
+        assert!(metadata("IPv4", None).as_u64().is_err());
+        assert!(metadata("payload", Some("0102030405060708ff"))
+            .as_u64()
+            .is_err());
+        assert!(metadata("payload", Some("ffffffffffffffff"))
+            .as_i64()
+            .is_err());
+    }
+
+    #[test]
+    fn test_value_bool() {
+        assert_eq!(metadata("1", None).as_bool(), Ok(true));
+        assert_eq!(metadata("False", None).as_bool(), Ok(false));
+        assert_eq!(metadata("Not set", None).as_bool(), Ok(false));
+        assert!(metadata("maybe", None).as_bool().is_err());
+    }
+
+    #[test]
+    fn test_value_addresses() {
+        assert_eq!(
+            metadata("::1", None).as_ip_addr(),
+            Ok(Ipv6Addr::LOCALHOST.into())
+        );
+        // resolved name: fallback on raw value
+        assert_eq!(
+            metadata("localhost", Some("7f000001")).as_ip_addr(),
+            Ok(Ipv4Addr::LOCALHOST.into())
+        );
+        assert_eq!(
+            metadata("Intel_3a:4f:7c", Some("001b213a4f7c")).as_mac(),
+            Ok([0x00, 0x1b, 0x21, 0x3a, 0x4f, 0x7c])
+        );
+        assert!(metadata("1.2.3", None).as_ip_addr().is_err());
+    }
+
+    #[test]
+    fn test_value_bytes() {
+        assert_eq!(
+            metadata("data is aa", Some("0aff")).as_bytes(),
+            Ok(vec![0x0a, 0xff])
+        );
+        assert_eq!(metadata("", None).as_bytes(), Ok(vec![]));
+        assert!(metadata("abc", None).as_bytes().is_err());
+        assert!(metadata("+a", None).as_bytes().is_err());
+    }
+
+    #[test]
+    fn test_value_duration() {
+        assert_eq!(
+            metadata("1652011560.275852000", None).as_duration(),
+            Ok(Duration::new(1652011560, 275852000))
+        );
+        assert_eq!(
+            metadata("0.5 seconds", None).as_duration(),
+            Ok(Duration::from_millis(500))
+        );
+        assert!(metadata("-0.5", None).as_duration().is_err());
+        assert!(metadata("0.1234567891", None).as_duration().is_err());
+    }
+
+    #[test]
+    fn test_value_error() {
+        let err = metadata("abc", None).as_u64().unwrap_err();
+        assert_eq!(err.name(), "test");
+        assert_eq!(err.value(), "abc");
+        assert_eq!(err.expected(), "u64");
+    }
+}